@@ -0,0 +1,290 @@
+use crate::arena::{Court, Goal};
+use crate::ball::{nearest_goal_direction, Ball, BALL_RADIUS};
+use crate::config::GameConfig;
+use crate::input::KeyBindings;
+use crate::paddle::{Paddle, PaddleBounds, PaddleController, MAX_PADDLE_TILT, PADDLE_TILT_SPEED};
+use crate::prelude::*;
+
+// AI paddles, and the debug overlay showing where they aim
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, move_ai_paddles.in_set(StepSet::MovePaddles))
+            .add_systems(Update, (toggle_ai_target, draw_ai_target).chain());
+    }
+}
+
+const PADDLE_SPEED_AI: f32 = 500.;
+// How far the AI steps from its goal line toward an incoming ball when it may move in x
+const AI_FORWARD_FACTOR: f32 = 0.3;
+// How far the AI tilts its paddle to aim returns away from the opponent
+const AI_TILT: f32 = MAX_PADDLE_TILT / 2.;
+
+// The AI prediction steps the ball this many seconds at a time, for at most AI_PREDICTION_STEPS
+const AI_PREDICTION_STEP: f32 = 1. / 120.;
+const AI_PREDICTION_STEPS: usize = 360;
+// Ball and wall restitution averaged, the way the physics combines them
+const AI_PREDICTION_BOUNCE: f32 = 0.75;
+// Balls drifting away from the AI's goal are ranked as if creeping toward it at this speed
+const AI_THREAT_MIN_SPEED: f32 = 50.;
+
+// How well the AI plays, as how fast its paddle may move
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AiDifficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl AiDifficulty {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "easy" => Some(Self::Easy),
+            "normal" => Some(Self::Normal),
+            "hard" => Some(Self::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Easy => Self::Normal,
+            Self::Normal => Self::Hard,
+            Self::Hard => Self::Easy,
+        }
+    }
+
+    pub fn paddle_speed(&self) -> f32 {
+        match self {
+            Self::Easy => PADDLE_SPEED_AI * 0.7,
+            Self::Normal => PADDLE_SPEED_AI,
+            Self::Hard => PADDLE_SPEED_AI * 1.4,
+        }
+    }
+}
+
+fn move_ai_paddles(
+    fixed_time: Res<FixedTime>,
+    mut paddles: Query<(
+        &mut LinearVelocity,
+        &mut Position,
+        &mut Rotation,
+        &Paddle,
+        &PaddleBounds,
+        &PaddleController,
+    )>,
+    balls: Query<(&Position, &LinearVelocity), (With<Ball>, Without<Paddle>)>,
+    goals: Query<&Position, (With<Goal>, Without<Paddle>)>,
+    config: Res<GameConfig>,
+    court: Res<Court>,
+) {
+    let goals: Vec<Vec2> = goals.iter().map(|goal| goal.0).collect();
+    let opponents: Vec<(Side, Vec2)> = paddles
+        .iter()
+        .map(|(_, position, _, paddle, ..)| (paddle.side.clone(), position.0))
+        .collect();
+    // Positions rather than transforms, those are blended between steps for drawing
+    for (mut velocity, mut paddle_position, mut rotation, paddle, bounds, controller) in
+        paddles.iter_mut()
+    {
+        if *controller != PaddleController::Ai {
+            continue;
+        }
+        let threats = balls
+            .iter()
+            .map(|(position, velocity)| (position.0, velocity.0));
+        let Some((ball, ball_velocity)) = most_threatening_ball(&paddle.side, &court, threats)
+        else {
+            continue;
+        };
+        let target = if paddle.side.horizontal() {
+            // The top and bottom seats only slide across to meet the ball
+            Vec2::new(ball.x, bounds.home.y)
+        } else {
+            ai_target(
+                bounds,
+                paddle_position.0,
+                ball,
+                ball_velocity,
+                &config,
+                &court,
+                &goals,
+            )
+        };
+        let to_target_position = target - paddle_position.0;
+
+        let max_speed = config.ai_difficulty.paddle_speed();
+        let new_velocity = to_target_position.normalize_or_zero()
+            * max_speed.min(to_target_position.length() / fixed_time.period.as_secs_f32());
+        *velocity = LinearVelocity(new_velocity);
+        bounds.clamp(&mut paddle_position.0, &mut velocity.0);
+
+        // The AI's answer to tilting: it angles its returns toward the half of the court the
+        // opponent's paddle isn't in. The top and bottom seats stay level
+        if !paddle.side.horizontal() {
+            let opponent = opponents
+                .iter()
+                .find(|(side, _)| *side == paddle.side.opposite());
+            let target_tilt = opponent.map_or(0., |(_, position)| {
+                -position.y.signum() * AI_TILT * paddle.side.inward().x
+            });
+            let step = PADDLE_TILT_SPEED * fixed_time.period.as_secs_f32();
+            let tilt = rotation.as_radians();
+            *rotation = Rotation::from_radians(tilt + (target_tilt - tilt).clamp(-step, step));
+        }
+    }
+}
+
+// The AI chases where the first ball will cross its depth, or the ball's height when that is
+// unknown, and steps forward too when allowed
+fn ai_target(
+    bounds: &PaddleBounds,
+    paddle: Vec2,
+    ball: Vec2,
+    ball_velocity: Vec2,
+    config: &GameConfig,
+    court: &Court,
+    goals: &[Vec2],
+) -> Vec2 {
+    let target_x = if config.ai_x_positioning {
+        ai_target_x(bounds, ball, ball_velocity)
+    } else {
+        paddle.x
+    };
+    let predicted = config
+        .ai_prediction
+        .then(|| {
+            predict_ball_path(
+                target_x,
+                ball,
+                ball_velocity,
+                config.goal_pull,
+                goals,
+                court,
+            )
+        })
+        .flatten()
+        .and_then(|path| path.last().copied());
+    Vec2::new(target_x, predicted.map_or(ball.y, |crossing| crossing.y))
+}
+
+// Steps the ball forward, bouncing off the top and bottom walls and feeling the pull toward the
+// nearest of the goals, until it reaches x. The path is the ball, every bounce and the crossing,
+// None if it never gets there
+fn predict_ball_path(
+    x: f32,
+    ball: Vec2,
+    velocity: Vec2,
+    goal_pull: f32,
+    goals: &[Vec2],
+    court: &Court,
+) -> Option<Vec<Vec2>> {
+    let limit = court.half_size.y - BALL_RADIUS;
+    let side = (x - ball.x).signum();
+    let (mut position, mut velocity) = (ball, velocity);
+    let mut path = vec![ball];
+    for _ in 0..AI_PREDICTION_STEPS {
+        if (x - position.x).signum() != side {
+            path.push(position);
+            return Some(path);
+        }
+        velocity += nearest_goal_direction(position, goals) * goal_pull * AI_PREDICTION_STEP;
+        position += velocity * AI_PREDICTION_STEP;
+        if position.y.abs() > limit {
+            position.y = limit.copysign(position.y) * 2. - position.y;
+            velocity.y = -velocity.y * AI_PREDICTION_BOUNCE;
+            path.push(position);
+        }
+    }
+    None
+}
+
+fn draw_ai_target(
+    overlays: Res<DebugOverlays>,
+    config: Res<GameConfig>,
+    court: Res<Court>,
+    mut gizmos: Gizmos,
+    paddles: Query<(&Transform, &Paddle, &PaddleBounds, &PaddleController)>,
+    balls: Query<(&Transform, &LinearVelocity), (With<Ball>, Without<Paddle>)>,
+    goals: Query<&Position, With<Goal>>,
+) {
+    if !overlays.ai_target {
+        return;
+    }
+    let goals: Vec<Vec2> = goals.iter().map(|goal| goal.0).collect();
+    let ai_paddles = paddles
+        .iter()
+        .filter(|(.., controller)| **controller == PaddleController::Ai);
+    for (transform, paddle, bounds, _) in ai_paddles {
+        let threats = balls
+            .iter()
+            .map(|(t, velocity)| (t.translation.xy(), velocity.0));
+        let Some((ball, ball_velocity)) = most_threatening_ball(&paddle.side, &court, threats)
+        else {
+            continue;
+        };
+        let position = transform.translation.xy();
+        let target = ai_target(
+            bounds,
+            position,
+            ball,
+            ball_velocity,
+            &config,
+            &court,
+            &goals,
+        );
+        gizmos.line_2d(position, target, Color::ORANGE);
+        gizmos.circle_2d(target, 8., Color::ORANGE);
+
+        // The predicted ball path up to the depth the AI is heading for
+        if config.ai_prediction {
+            let pull = config.goal_pull;
+            let path = predict_ball_path(target.x, ball, ball_velocity, pull, &goals, &court);
+            if let Some(path) = path {
+                gizmos.linestrip_2d(path.iter().copied(), Color::ORANGE.with_a(0.4));
+                if let Some(crossing) = path.last() {
+                    gizmos.circle_2d(*crossing, 5., Color::RED);
+                }
+            }
+        }
+    }
+}
+
+fn toggle_ai_target(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut overlays: ResMut<DebugOverlays>,
+) {
+    if keys.just_pressed(bindings.ai_target) {
+        overlays.ai_target = !overlays.ai_target;
+    }
+}
+
+// The ball that would reach the AI's goal soonest, judged by its distance from the goal line
+// and speed toward it
+fn most_threatening_ball(
+    side: &Side,
+    court: &Court,
+    balls: impl Iterator<Item = (Vec2, Vec2)>,
+) -> Option<(Vec2, Vec2)> {
+    let outward = -side.inward();
+    let goal_line = (court.half_size * outward.abs()).max_element() + 5.;
+    let time_to_goal = |(ball, velocity): &(Vec2, Vec2)| {
+        let toward_goal = velocity.dot(outward);
+        (goal_line - ball.dot(outward)).abs() / toward_goal.max(AI_THREAT_MIN_SPEED)
+    };
+    balls.min_by(|a, b| time_to_goal(a).total_cmp(&time_to_goal(b)))
+}
+
+// Meets balls coming into the AI's half early and falls back to the goal line otherwise
+fn ai_target_x(bounds: &PaddleBounds, ball: Vec2, ball_velocity: Vec2) -> f32 {
+    let home_x = bounds.home.x;
+    let toward_home = (ball_velocity.x > 0.) == (home_x > 0.);
+    let in_half = (ball.x > 0.) == (home_x > 0.);
+    if !(toward_home && in_half) {
+        return home_x;
+    }
+    (home_x + (ball.x - home_x) * AI_FORWARD_FACTOR).clamp(bounds.min.x, bounds.max.x)
+}
@@ -0,0 +1,626 @@
+use crate::ball::{Ball, BallAssets, BALL_RADIUS};
+use crate::config::GameConfig;
+use crate::cosmetics::CosmeticsConfig;
+use crate::paddle::{
+    spawn_paddle, Paddle, PaddleBounds, PaddleController, PaddleShape, PADDLE_SIZE,
+};
+use crate::prelude::*;
+use crate::score::Score;
+
+// Court layout, arena files, walls and the bricks of breakout
+pub struct ArenaPlugin;
+
+impl Plugin for ArenaPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ArenaDefinition>()
+            .init_asset_loader::<ArenaDefinitionLoader>()
+            .init_resource::<Court>()
+            .add_systems(
+                OnTransition {
+                    from: GameState::GameOver,
+                    to: GameState::Playing,
+                },
+                respawn_bricks,
+            )
+            .add_systems(Update, hit_bricks.in_set(GoalSet::React));
+    }
+}
+
+pub const NET_WIDTH: f32 = 5.;
+// Left paddle start x, the right one is mirrored
+const PADDLE_START_X: f32 = -(WINDOW_SIZE.x / 2.) + 20.;
+
+// Co-op balls are fired from this fraction of the way to the right goal
+pub const COOP_LAUNCHER_DEPTH: f32 = 0.6;
+
+// Breakout bricks fill columns in front of the right wall, the ones at the back are the toughest
+const BRICK_SIZE: Vec2 = Vec2::new(30., 60.);
+const BRICK_GAP: f32 = 12.;
+const BRICK_COLUMNS: usize = 3;
+const BRICK_BACK_MARGIN: f32 = 60.;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum ObstacleShape {
+    Block { width: f32, height: f32 },
+    Circle { radius: f32 },
+}
+
+impl ObstacleShape {
+    // Half size of the bounding box
+    pub fn extent(&self) -> Vec2 {
+        match self {
+            Self::Block { width, height } => Vec2::new(*width, *height) / 2.,
+            Self::Circle { radius } => Vec2::splat(*radius),
+        }
+    }
+}
+
+// A static shape the ball bounces off, paddles pass under it
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct ObstacleSpec {
+    pub shape: ObstacleShape,
+    pub position: [f32; 2],
+}
+
+impl ObstacleSpec {
+    pub fn position(&self) -> Vec2 {
+        Vec2::from(self.position)
+    }
+
+    pub fn mirrored(&self) -> Self {
+        Self {
+            position: [-self.position[0], self.position[1]],
+            ..*self
+        }
+    }
+}
+
+// An arena layout from assets/arenas, everything is described for the first player's half
+// and mirrored onto the other one so neither side is favored
+#[derive(Deserialize, TypeUuid, TypePath, Clone, PartialEq, Debug)]
+#[uuid = "4c8e2d7a-93f1-4b56-a0e2-6d1f5b7c3a98"]
+pub struct ArenaDefinition {
+    // The top, bottom and side walls sit on the edges of this box around the net
+    pub half_size: [f32; 2],
+    pub net_width: f32,
+    // Goal lines sit this far behind the side walls
+    pub goal_depth: f32,
+    pub paddle_start: [f32; 2],
+    // Box the paddle is kept in, its own size past the edges snaps it back in
+    pub paddle_area_min: [f32; 2],
+    pub paddle_area_max: [f32; 2],
+    #[serde(default)]
+    pub obstacles: Vec<ObstacleSpec>,
+}
+
+impl Default for ArenaDefinition {
+    fn default() -> Self {
+        Self {
+            half_size: (WINDOW_SIZE / 2.).to_array(),
+            net_width: NET_WIDTH,
+            goal_depth: 5.,
+            paddle_start: [PADDLE_START_X, 0.],
+            paddle_area_min: [-(WINDOW_SIZE.x * 0.5), -(WINDOW_SIZE.y * 0.5)],
+            paddle_area_max: [-(WINDOW_SIZE.x * 0.25), WINDOW_SIZE.y * 0.5],
+            obstacles: vec![],
+        }
+    }
+}
+
+impl ArenaDefinition {
+    // Cycled through by the menu, --arena also takes the name of any other file in assets/arenas
+    pub const BUILT_IN: [&str; 3] = ["open", "pillars", "bumpers"];
+
+    pub fn path(name: &str) -> String {
+        format!("arenas/{name}.arena.ron")
+    }
+
+    // Arena files can be shared between players, one the court can't be built from is refused
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let arena: Self = ron::from_str(contents).map_err(|e| e.to_string())?;
+        let half_size = arena.half_size();
+        if !half_size.is_finite() || half_size.min_element() <= 0. {
+            return Err(format!("half size {half_size} is not a court"));
+        }
+        for (name, size) in [
+            ("net width", arena.net_width),
+            ("goal depth", arena.goal_depth),
+        ] {
+            if !size.is_finite() || size <= 0. {
+                return Err(format!("{name} {size} is not a size"));
+            }
+        }
+        let (area_min, area_max) = (
+            Vec2::from(arena.paddle_area_min),
+            Vec2::from(arena.paddle_area_max),
+        );
+        if !area_min.is_finite() || !area_max.is_finite() || area_min.cmpgt(area_max).any() {
+            return Err(format!("paddle area {area_min} to {area_max} is not a box"));
+        }
+        if !Vec2::from(arena.paddle_start).is_finite() {
+            return Err("the paddle start is not a position".to_string());
+        }
+        for obstacle in &arena.obstacles {
+            let (position, extent) = (obstacle.position(), obstacle.shape.extent());
+            if !position.is_finite() || !extent.is_finite() || extent.min_element() <= 0. {
+                return Err(format!("obstacle at {position} is not a shape"));
+            }
+        }
+        Ok(arena)
+    }
+
+    pub fn half_size(&self) -> Vec2 {
+        Vec2::from(self.half_size)
+    }
+
+    pub fn paddle_bounds(&self, side: &Side) -> PaddleBounds {
+        let (area_min, area_max) = (
+            Vec2::from(self.paddle_area_min),
+            Vec2::from(self.paddle_area_max),
+        );
+        let left = PaddleBounds {
+            min: Vec2::new(area_min.x + PADDLE_SIZE.x, area_min.y - PADDLE_SIZE.y),
+            max: Vec2::new(area_max.x - PADDLE_SIZE.x, area_max.y + PADDLE_SIZE.y),
+            min_wall: area_min,
+            max_wall: area_max,
+            home: Vec2::from(self.paddle_start),
+        };
+        match side {
+            Side::Right => left.mirrored(),
+            // As far from their goal as the left paddle starts from its own, sliding across the
+            // whole width
+            Side::Top | Side::Bottom => {
+                let half_size = self.half_size();
+                let depth = self.paddle_start[0] + half_size.x;
+                let y = (half_size.y - depth) * if *side == Side::Top { 1. } else { -1. };
+                let reach = half_size.x - PADDLE_SIZE.y / 2.;
+                let (min, max) = (Vec2::new(-reach, y), Vec2::new(reach, y));
+                PaddleBounds {
+                    min,
+                    max,
+                    min_wall: min,
+                    max_wall: max,
+                    home: Vec2::new(0., y),
+                }
+            }
+            _ => left,
+        }
+    }
+
+    // The left paddle's box cut in two across the middle, the first player gets the top half
+    pub fn coop_bounds(&self, first_player: bool) -> PaddleBounds {
+        let mut bounds = self.paddle_bounds(&Side::Left);
+        let middle = (self.paddle_area_min[1] + self.paddle_area_max[1]) / 2.;
+        let quarter = (self.paddle_area_max[1] - self.paddle_area_min[1]) / 4.;
+        // Neither paddle gets past the middle, so together they can close the whole goal
+        let edge = PADDLE_SIZE.y / 2.;
+        if first_player {
+            bounds.min.y = middle + edge;
+            bounds.min_wall.y = middle + edge;
+            bounds.home.y = middle + quarter;
+        } else {
+            bounds.max.y = middle - edge;
+            bounds.max_wall.y = middle - edge;
+            bounds.home.y = middle - quarter;
+        }
+        bounds
+    }
+
+    // Both halves
+    pub fn obstacles(&self) -> Vec<ObstacleSpec> {
+        let mirrored = self.obstacles.iter().map(ObstacleSpec::mirrored);
+        self.obstacles.iter().copied().chain(mirrored).collect()
+    }
+}
+
+#[derive(Default)]
+struct ArenaDefinitionLoader;
+
+impl AssetLoader for ArenaDefinitionLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let definition = ArenaDefinition::parse(std::str::from_utf8(bytes)?)
+                .map_err(bevy::asset::Error::msg)?;
+            load_context.set_default_asset(LoadedAsset::new(definition));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["arena.ron"]
+    }
+}
+
+// Every arena the menu and --arena can pick, loaded up front so a match never waits on one
+#[derive(Resource)]
+pub struct ArenaAssets(pub Vec<(String, Handle<ArenaDefinition>)>);
+
+impl ArenaAssets {
+    pub fn get<'a>(
+        &self,
+        name: &str,
+        arenas: &'a Assets<ArenaDefinition>,
+    ) -> Option<&'a ArenaDefinition> {
+        let (_, handle) = self.0.iter().find(|(arena, _)| arena == name)?;
+        arenas.get(handle)
+    }
+}
+
+// The field of the match being played. Gameplay reads it rather than WINDOW_SIZE, which is only
+// the size the window opens at, and the camera zooms to keep all of it in view
+#[derive(Resource, Clone, Copy, PartialEq, Debug)]
+pub struct Court {
+    pub half_size: Vec2,
+}
+
+impl Default for Court {
+    fn default() -> Self {
+        Self::new(&ArenaDefinition::default())
+    }
+}
+
+impl Court {
+    pub fn new(arena: &ArenaDefinition) -> Self {
+        Self {
+            half_size: arena.half_size(),
+        }
+    }
+
+    // World units per window pixel that fit the whole court, the spare room goes to the sides
+    // or to the top and bottom
+    pub fn camera_scale(&self, window: Vec2) -> f32 {
+        (self.half_size * 2. / window.max(Vec2::ONE)).max_element()
+    }
+}
+
+#[derive(Component)]
+pub struct Goal {
+    pub side: Side,
+}
+
+#[derive(Component)]
+pub struct Wall;
+
+#[derive(Component)]
+pub struct Obstacle;
+
+// Destroyed once its health runs out, scoring its points for the first player
+#[derive(Component)]
+pub struct Brick {
+    pub health: usize,
+    pub points: usize,
+}
+
+impl Brick {
+    pub fn color(&self) -> Color {
+        match self.health {
+            1 => Color::rgb(0.45, 0.75, 0.95),
+            2 => Color::rgb(0.3, 0.5, 0.9),
+            _ => Color::rgb(0.2, 0.25, 0.7),
+        }
+    }
+}
+
+pub fn spawn_bricks(commands: &mut Commands, court: &Court) {
+    let rows = ((court.half_size.y * 2. - BRICK_GAP) / (BRICK_SIZE.y + BRICK_GAP)) as usize;
+    let top = (rows as f32 - 1.) * (BRICK_SIZE.y + BRICK_GAP) / 2.;
+    for column in 0..BRICK_COLUMNS {
+        let x =
+            court.half_size.x - BRICK_BACK_MARGIN - column as f32 * (BRICK_SIZE.x + BRICK_GAP * 2.);
+        for row in 0..rows {
+            let y = top - row as f32 * (BRICK_SIZE.y + BRICK_GAP);
+            let health = BRICK_COLUMNS - column;
+            let brick = Brick {
+                health,
+                points: health,
+            };
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: brick.color(),
+                        custom_size: Some(BRICK_SIZE),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(x, y, 1.),
+                    ..default()
+                },
+                RigidBody::Static,
+                Collider::cuboid(BRICK_SIZE.x, BRICK_SIZE.y),
+                CollisionLayers::new([Layer::Brick], [Layer::Ball]),
+                Restitution::new(0.8),
+                Friction::ZERO,
+                brick,
+                MatchEntity,
+            ));
+        }
+    }
+}
+
+// A rematch starts with a full wall of bricks again
+fn respawn_bricks(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    court: Res<Court>,
+    bricks: Query<Entity, With<Brick>>,
+) {
+    for entity in bricks.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if config.mode == GameMode::Breakout {
+        spawn_bricks(&mut commands, &court);
+    }
+}
+
+fn hit_bricks(
+    mut commands: Commands,
+    mut collision_started: EventReader<CollisionStarted>,
+    balls: Query<(), With<Ball>>,
+    mut bricks: Query<(&mut Brick, &mut Sprite)>,
+    mut score: ResMut<Score>,
+) {
+    for CollisionStarted(entity1, entity2) in collision_started.iter() {
+        let brick = if balls.contains(*entity1) {
+            *entity2
+        } else if balls.contains(*entity2) {
+            *entity1
+        } else {
+            continue;
+        };
+        let Ok((mut brick_data, mut sprite)) = bricks.get_mut(brick) else {
+            continue;
+        };
+        // Two balls can hit the same brick in one frame
+        if brick_data.health == 0 {
+            continue;
+        }
+        brick_data.health -= 1;
+        if brick_data.health == 0 {
+            score.add(Side::Left, brick_data.points);
+            commands.entity(brick).despawn_recursive();
+        } else {
+            sprite.color = brick_data.color();
+        }
+    }
+}
+
+pub fn spawn_obstacles(
+    commands: &mut Commands,
+    ball_assets: &BallAssets,
+    obstacles: &[ObstacleSpec],
+) {
+    for obstacle in obstacles {
+        let size = obstacle.shape.extent() * 2.;
+        let (collider, texture) = match obstacle.shape {
+            ObstacleShape::Block { .. } => (Collider::cuboid(size.x, size.y), Handle::default()),
+            // The ball texture doubles as a plain disc
+            ObstacleShape::Circle { radius } => {
+                (Collider::ball(radius), ball_assets.point_ball.clone())
+            }
+        };
+        commands.spawn((
+            SpriteBundle {
+                texture,
+                sprite: Sprite {
+                    color: Color::GRAY,
+                    custom_size: Some(size),
+                    ..default()
+                },
+                transform: Transform::from_translation(obstacle.position().extend(1.)),
+                ..default()
+            },
+            RigidBody::Static,
+            collider,
+            CollisionLayers::new([Layer::Obstacle], [Layer::Ball]),
+            Restitution::new(0.8),
+            Friction::ZERO,
+            Obstacle,
+            MatchEntity,
+        ));
+    }
+}
+
+pub fn spawn_arena(
+    commands: &mut Commands,
+    config: &GameConfig,
+    cosmetics: &CosmeticsConfig,
+    arena: &ArenaDefinition,
+    controllers: [PaddleController; 2],
+    shapes: [PaddleShape; 2],
+) {
+    let lone_player = config.mode.lone_player();
+    let four_player = config.mode == GameMode::FourPlayer;
+    let half_size = arena.half_size();
+
+    // Net (middle line)
+    let net_box = Vec2::new(arena.net_width, half_size.y * 2.);
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(net_box),
+                color: Color::WHITE,
+                ..default()
+            },
+            ..default()
+        },
+        RigidBody::Static,
+        Collider::cuboid(net_box.x, net_box.y),
+        CollisionLayers::new([Layer::Net], [Layer::Paddle]),
+        MatchEntity,
+    ));
+
+    // Walls
+    // Up and down walls only hold the paddles back in a four player match, goals sit behind them
+    let up_down_layers = if four_player {
+        CollisionLayers::new([Layer::Wall], [Layer::Paddle])
+    } else {
+        CollisionLayers::new([Layer::Wall], [Layer::Paddle, Layer::Ball])
+    };
+    // Up wall
+    spawn_wall(commands, 0., half_size.y, Vec2::NEG_Y, up_down_layers, None);
+    // Down wall
+    spawn_wall(commands, 0., -half_size.y, Vec2::Y, up_down_layers, None);
+    // Left wall
+    spawn_wall(
+        commands,
+        -half_size.x,
+        0.,
+        Vec2::X,
+        CollisionLayers::new([Layer::Wall], [Layer::Paddle]),
+        None,
+    );
+    // Right wall
+    spawn_wall(
+        commands,
+        half_size.x,
+        0.,
+        Vec2::NEG_X,
+        CollisionLayers::new([Layer::Wall], [Layer::Paddle]),
+        None,
+    );
+    // Left goal
+    spawn_wall(
+        commands,
+        -(half_size.x + arena.goal_depth),
+        0.,
+        Vec2::X,
+        CollisionLayers::new([Layer::Wall], [Layer::Ball]),
+        Some(Side::Left),
+    );
+    // Right goal, a plain wall bouncing balls back without a second player
+    spawn_wall(
+        commands,
+        half_size.x + arena.goal_depth,
+        0.,
+        Vec2::NEG_X,
+        CollisionLayers::new([Layer::Wall], [Layer::Ball]),
+        (!lone_player).then_some(Side::Right),
+    );
+    if four_player {
+        // Top goal
+        spawn_wall(
+            commands,
+            0.,
+            half_size.y + arena.goal_depth,
+            Vec2::NEG_Y,
+            CollisionLayers::new([Layer::Wall], [Layer::Ball]),
+            Some(Side::Top),
+        );
+        // Bottom goal
+        spawn_wall(
+            commands,
+            0.,
+            -(half_size.y + arena.goal_depth),
+            Vec2::Y,
+            CollisionLayers::new([Layer::Wall], [Layer::Ball]),
+            Some(Side::Bottom),
+        );
+    }
+
+    // Paddles
+    let colorblind = config.colorblind;
+    if config.mode == GameMode::Coop {
+        // Each player keeps to their own half of the left goal
+        let seats = [true, false].into_iter().zip(controllers).zip(shapes);
+        for ((first_player, controller), shape) in seats {
+            let paddle = Paddle {
+                first_player,
+                side: Side::Left,
+            };
+            let bounds = arena.coop_bounds(first_player);
+            spawn_paddle(
+                commands, paddle, controller, bounds, shape, cosmetics, colorblind,
+            );
+        }
+        spawn_coop_launcher(commands, half_size);
+        return;
+    }
+    let left = Paddle {
+        first_player: true,
+        side: Side::Left,
+    };
+    let bounds = arena.paddle_bounds(&Side::Left);
+    spawn_paddle(
+        commands,
+        left,
+        controllers[0],
+        bounds,
+        shapes[0],
+        cosmetics,
+        colorblind,
+    );
+    if !lone_player {
+        let right = Paddle {
+            first_player: false,
+            side: Side::Right,
+        };
+        let bounds = arena.paddle_bounds(&Side::Right);
+        spawn_paddle(
+            commands,
+            right,
+            controllers[1],
+            bounds,
+            shapes[1],
+            cosmetics,
+            colorblind,
+        );
+    }
+    // No player has the top and bottom seats
+    if four_player {
+        for side in [Side::Top, Side::Bottom] {
+            let bounds = arena.paddle_bounds(&side);
+            let paddle = Paddle {
+                first_player: false,
+                side,
+            };
+            let (controller, shape) = (PaddleController::Ai, PaddleShape::Flat);
+            spawn_paddle(
+                commands, paddle, controller, bounds, shape, cosmetics, colorblind,
+            );
+        }
+    }
+}
+
+// Only shows where co-op balls come from, it doesn't collide with anything
+fn spawn_coop_launcher(commands: &mut Commands, half_size: Vec2) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::DARK_GRAY,
+                custom_size: Some(Vec2::new(BALL_RADIUS * 4., BALL_RADIUS * 6.)),
+                ..default()
+            },
+            transform: Transform::from_xyz(half_size.x * COOP_LAUNCHER_DEPTH, 0., 0.5),
+            ..default()
+        },
+        MatchEntity,
+    ));
+}
+
+fn spawn_wall(
+    commands: &mut Commands,
+    x: f32,
+    y: f32,
+    outward_normal: Vec2,
+    collision_layers: CollisionLayers,
+    goal: Option<Side>,
+) {
+    let mut wall = commands.spawn((
+        Transform::from_xyz(x, y, 0.),
+        GlobalTransform::default(),
+        RigidBody::Static,
+        Collider::halfspace(outward_normal),
+        collision_layers,
+        Restitution::new(0.8),
+        Friction::ZERO,
+        MatchEntity,
+    ));
+
+    match goal {
+        Some(side) => wall.insert(Goal { side }),
+        None => wall.insert(Wall),
+    };
+}
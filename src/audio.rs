@@ -0,0 +1,325 @@
+use crate::arena::{Brick, Obstacle, Wall};
+use crate::ball::{check_goals, check_paddle_hits, Ball};
+use crate::menu::MenuButton;
+use crate::net::NetClient;
+use crate::prelude::*;
+
+pub const SFX_VARIANTS: usize = 3;
+const SFX_PITCH_VARIATION: f32 = 0.08;
+const MUSIC_VOLUME: f32 = 0.5;
+// Each press of a volume button raises it by this much, wrapping to silent past full
+const VOLUME_STEP: f32 = 0.1;
+const MUSIC_CROSSFADE_SECONDS: f32 = 1.5;
+
+// Music drops to this fraction on a goal and comes back up over MUSIC_DUCK_SECONDS
+const MUSIC_DUCK_LEVEL: f32 = 0.3;
+const MUSIC_DUCK_SECONDS: f32 = 1.5;
+
+#[derive(Resource)]
+pub struct AudioAssets {
+    pub paddle_hits: Vec<Handle<AudioSource>>,
+    pub wall_bounces: Vec<Handle<AudioSource>>,
+    pub serve_charge: Handle<AudioSource>,
+    pub golden_ball: Handle<AudioSource>,
+    pub goal: Handle<AudioSource>,
+    pub menu_click: Handle<AudioSource>,
+}
+
+// Sound variants and pitches are drawn from their own generator. Sounds play from Update systems in
+// no set order, drawing them from GameRng would change the serves that follow
+#[derive(Resource)]
+pub struct SfxRng(pub StdRng);
+
+// Mixed into the match seed, so a replayed match sounds the same without sharing GameRng's sequence
+const SFX_SALT: u64 = 0x5f3c_a1e9_d27b_8460;
+
+// Menu sounds before any match has been seeded
+impl Default for SfxRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+
+impl SfxRng {
+    pub fn for_seed(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed ^ SFX_SALT))
+    }
+}
+
+impl AudioAssets {
+    // Picks a variant and a slightly detuned playback speed so repeated hits don't sound identical
+    pub fn pick(
+        clips: &[Handle<AudioSource>],
+        rng: &mut SfxRng,
+    ) -> Option<(Handle<AudioSource>, f32)> {
+        if clips.is_empty() {
+            return None;
+        }
+        let clip = clips[rng.0.gen_range(0..clips.len())].clone();
+        let speed = 1. + rng.0.gen_range(-SFX_PITCH_VARIATION..=SFX_PITCH_VARIATION);
+        Some((clip, speed))
+    }
+}
+
+// Levels from 0 to 1, the master level scales both the others
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub sfx: f32,
+    pub music: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.,
+            sfx: 1.,
+            music: 1.,
+            muted: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn sfx_volume(&self) -> f32 {
+        if self.muted {
+            0.
+        } else {
+            self.master * self.sfx
+        }
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        if self.muted {
+            0.
+        } else {
+            self.master * self.music
+        }
+    }
+
+    // Playback for a one-shot sound effect
+    pub fn sfx(&self) -> PlaybackSettings {
+        PlaybackSettings::DESPAWN.with_volume(Volume::new_relative(self.sfx_volume()))
+    }
+
+    pub fn next_level(level: f32) -> f32 {
+        if level >= 1. - VOLUME_STEP / 2. {
+            0.
+        } else {
+            ((level + VOLUME_STEP) / VOLUME_STEP).round() * VOLUME_STEP
+        }
+    }
+}
+
+// Sound effects follow volume changes while they play, music has its own fade
+#[derive(Component)]
+pub struct SoundEffect;
+
+#[derive(Resource)]
+pub struct MusicAssets {
+    pub menu: Handle<AudioSource>,
+    pub gameplay: Handle<AudioSource>,
+}
+
+impl MusicAssets {
+    // Pause and game over keep the match music going
+    pub fn for_state(&self, state: &GameState) -> &Handle<AudioSource> {
+        match state {
+            GameState::Loading | GameState::Menu => &self.menu,
+            _ => &self.gameplay,
+        }
+    }
+}
+
+// Level is the crossfade position, from silent at 0 to full volume at 1
+#[derive(Component)]
+struct MusicTrack {
+    pub source: Handle<AudioSource>,
+    pub level: f32,
+    pub fading_out: bool,
+}
+
+#[derive(Resource, Default)]
+struct MusicDuck(pub Timer);
+
+pub struct SfxPlugin;
+
+impl Plugin for SfxPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AudioSettings>()
+            .init_resource::<SfxRng>()
+            .add_systems(
+                Update,
+                (
+                    play_collision_sounds.after(check_paddle_hits),
+                    play_goal_sounds.after(check_goals),
+                )
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(not(resource_exists::<NetClient>())),
+            )
+            .add_systems(
+                Update,
+                (
+                    play_menu_sounds,
+                    apply_sfx_volume.run_if(resource_changed::<AudioSettings>()),
+                ),
+            );
+    }
+}
+
+fn apply_sfx_volume(audio: Res<AudioSettings>, sinks: Query<&AudioSink, With<SoundEffect>>) {
+    for sink in sinks.iter() {
+        sink.set_volume(audio.sfx_volume());
+    }
+}
+
+fn play_collision_sounds(
+    mut commands: Commands,
+    mut paddle_hit: EventReader<PaddleHit>,
+    mut collision_started: EventReader<CollisionStarted>,
+    balls: Query<(), With<Ball>>,
+    walls: Query<(), Or<(With<Wall>, With<Obstacle>, With<Brick>)>>,
+    sfx: Res<AudioAssets>,
+    audio: Res<AudioSettings>,
+    mut rng: ResMut<SfxRng>,
+) {
+    let wall_bounces = collision_started
+        .iter()
+        .filter(|CollisionStarted(entity1, entity2)| {
+            (balls.contains(*entity1) && walls.contains(*entity2))
+                || (balls.contains(*entity2) && walls.contains(*entity1))
+        });
+    let sounds = paddle_hit
+        .iter()
+        .map(|_| &sfx.paddle_hits)
+        .chain(wall_bounces.map(|_| &sfx.wall_bounces))
+        .collect::<Vec<_>>();
+
+    for clips in sounds {
+        if let Some((source, speed)) = AudioAssets::pick(clips, &mut rng) {
+            commands.spawn((
+                AudioBundle {
+                    source,
+                    settings: audio.sfx().with_speed(speed),
+                },
+                SoundEffect,
+            ));
+        }
+    }
+}
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MusicDuck>().add_systems(
+            Update,
+            (
+                switch_music_track.run_if(state_changed::<GameState>()),
+                duck_music_on_goals,
+                fade_music,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn switch_music_track(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    music: Res<MusicAssets>,
+    mut tracks: Query<&mut MusicTrack>,
+) {
+    let source = music.for_state(state.get());
+    if tracks
+        .iter()
+        .any(|track| !track.fading_out && track.source == *source)
+    {
+        return;
+    }
+    for mut track in tracks.iter_mut() {
+        track.fading_out = true;
+    }
+    commands.spawn((
+        AudioBundle {
+            source: source.clone(),
+            settings: PlaybackSettings::LOOP.with_volume(Volume::new_relative(0.)),
+        },
+        MusicTrack {
+            source: source.clone(),
+            level: 0.,
+            fading_out: false,
+        },
+    ));
+}
+
+fn duck_music_on_goals(mut goal_scored: EventReader<GoalScored>, mut duck: ResMut<MusicDuck>) {
+    if !goal_scored.is_empty() {
+        goal_scored.clear();
+        duck.0 = Timer::from_seconds(MUSIC_DUCK_SECONDS, TimerMode::Once);
+    }
+}
+
+fn fade_music(
+    mut commands: Commands,
+    time: Res<Time>,
+    audio: Res<AudioSettings>,
+    mut duck: ResMut<MusicDuck>,
+    mut tracks: Query<(Entity, &mut MusicTrack, Option<&AudioSink>)>,
+) {
+    duck.0.tick(time.delta());
+    let duck_level = MUSIC_DUCK_LEVEL + (1. - MUSIC_DUCK_LEVEL) * duck.0.percent();
+    let step = time.delta_seconds() / MUSIC_CROSSFADE_SECONDS;
+    for (entity, mut track, sink) in tracks.iter_mut() {
+        let direction = if track.fading_out { -1. } else { 1. };
+        track.level = (track.level + direction * step).clamp(0., 1.);
+        if track.fading_out && track.level == 0. {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        // The sink only shows up once the clip has loaded and started
+        if let Some(sink) = sink {
+            sink.set_volume(audio.music_volume() * MUSIC_VOLUME * track.level * duck_level);
+        }
+    }
+}
+
+fn play_goal_sounds(
+    mut commands: Commands,
+    mut goal_scored: EventReader<GoalScored>,
+    sfx: Res<AudioAssets>,
+    audio: Res<AudioSettings>,
+) {
+    // Balls scored together in one frame ring out once
+    if !goal_scored.is_empty() {
+        goal_scored.clear();
+        commands.spawn((
+            AudioBundle {
+                source: sfx.goal.clone(),
+                settings: audio.sfx(),
+            },
+            SoundEffect,
+        ));
+    }
+}
+
+fn play_menu_sounds(
+    mut commands: Commands,
+    buttons: Query<&Interaction, (Changed<Interaction>, With<MenuButton>)>,
+    sfx: Res<AudioAssets>,
+    audio: Res<AudioSettings>,
+) {
+    if buttons
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        commands.spawn((
+            AudioBundle {
+                source: sfx.menu_click.clone(),
+                settings: audio.sfx(),
+            },
+            SoundEffect,
+        ));
+    }
+}
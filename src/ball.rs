@@ -0,0 +1,1382 @@
+use crate::arena::{Court, Goal, COOP_LAUNCHER_DEPTH};
+use crate::audio::{AudioAssets, AudioSettings, SoundEffect};
+use crate::config::{read_shared_file, GameConfig, GameSpeed};
+use crate::cosmetics::CosmeticsConfig;
+use crate::input::KeyBindings;
+use crate::paddle::{Charge, Paddle, PaddleShape, PaddleSize, PADDLE_SIZE, SMASH_IMPULSE};
+use crate::power_ups::{has_effect, EffectKind, StatusEffect};
+use crate::prelude::*;
+use crate::score::{GoalCelebration, SurvivalRun, RALLY_BONUS_POINTS};
+
+// Serving balls, everything that steers them in flight, goals and paddle hits, and drawing them
+// between fixed steps
+pub struct BallPlugin;
+
+impl Plugin for BallPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnExit(GameState::GameOver), reset_next_serve)
+            .add_systems(
+                FixedUpdate,
+                (
+                    remember_previous_positions.before(SimulationSet),
+                    apply_delayed_external_forces.in_set(StepSet::Forces),
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                (interpolate_transforms, update_ball_trails)
+                    .chain()
+                    .before(TransformSystem::TransformPropagate),
+            )
+            .add_systems(
+                Update,
+                (
+                    check_goals.in_set(GoalSet::Detect),
+                    (remove_scored_balls, start_serve_countdown, aim_next_serve)
+                        .in_set(GoalSet::React),
+                    tick_serve_countdown.in_set(GoalSet::Countdown),
+                    (
+                        spawn_ball.run_if(
+                            |countdown: Res<ServeCountdown>, celebration: Res<GoalCelebration>| {
+                                !countdown.running() && !celebration.running()
+                            },
+                        ),
+                        launch_pending_serves,
+                    )
+                        .chain()
+                        .in_set(GoalSet::Serve),
+                    check_paddle_hits.in_set(HitSet::Detect),
+                    (
+                        update_last_touched,
+                        update_rally_counter,
+                        (
+                            aim_paddle_bounces,
+                            smash_balls,
+                            release_charged_smashes,
+                            boost_fast_shots,
+                            speed_up_rallies,
+                        )
+                            .chain(),
+                    )
+                        .in_set(HitSet::React),
+                    (
+                        pull_balls_toward_goals,
+                        curve_spinning_balls,
+                        cap_ball_speed,
+                    )
+                        .chain()
+                        .in_set(SimulationSet),
+                    remove_escaped_balls.in_set(SimulationSet),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    steady_ball_text,
+                    spin_ball_sprites,
+                    (toggle_ball_inspector, update_ball_inspector).chain(),
+                ),
+            );
+    }
+}
+
+pub const BALL_RADIUS: f32 = 15.;
+pub const MAX_BALL_SPEED: f32 = 1500.;
+
+// Speed multiplier on every return while fast shots last
+const FAST_SHOT_SCALE: f32 = 1.4;
+
+pub const MAX_SERVES: usize = 4096;
+// Outer share of each goal half, top and bottom, that counts as a corner with goal zones on
+const GOAL_CORNER_FRACTION: f32 = 0.25;
+// Size of the serve warning ring when the ball launches, in ball diameters
+const SERVE_WARNING_SCALE: f32 = 3.;
+
+// Seconds of the serve_charge clip at normal speed
+const SERVE_CHARGE_CLIP_LENGTH: f32 = 0.4;
+
+// Extra points on a ball served as a point ball, and how many of those may be out at once
+const SERVED_POINT_BALL_POINTS: usize = 2;
+const MAX_SERVED_POINT_BALLS: u8 = 2;
+
+const INITIAL_FORCE: f32 = 20000000.;
+
+// Return angle off a paddle's very end, the middle sends the ball straight back
+pub const MAX_BOUNCE_ANGLE: f32 = PI / 3.;
+// A tilted paddle can send returns wider than that, but never so wide they don't cross the court
+const MAX_RETURN_ANGLE: f32 = PI * 5. / 12.;
+// Share of the paddle's sliding speed that becomes ball spin
+const CURVE_TRANSFER: f32 = 0.05;
+// Sideways acceleration per unit of spin and speed
+const MAGNUS_FACTOR: f32 = 0.025;
+// Spin lost per second, so curve shots straighten out
+const SPIN_DECAY: f32 = 1.;
+
+// Survival serves start slow and ramp up to the minimum interval and maximum speed
+const SURVIVAL_START_INTERVAL: f32 = 3.;
+const SURVIVAL_MIN_INTERVAL: f32 = 0.4;
+const SURVIVAL_INTERVAL_RAMP: f32 = 0.03;
+const SURVIVAL_SPEED_RAMP: f32 = 0.01;
+const SURVIVAL_MAX_SPEED_SCALE: f32 = 2.;
+
+// Drawn positions kept per ball, a ball at top speed shows all of them
+const BALL_TRAIL_LENGTH: usize = 14;
+// How far past the court's edges a ball has to get before it's taken as lost
+const ESCAPED_BALL_MARGIN: f32 = 200.;
+
+#[derive(Component)]
+pub struct Ball {
+    pub points: usize,
+    pub kind: BallKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BallKind {
+    #[default]
+    Normal,
+    Point,
+    // Scoring it wins the match outright
+    Golden,
+}
+
+impl BallKind {
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Normal => Color::WHITE,
+            Self::Point => Color::GOLD,
+            Self::Golden => Color::YELLOW,
+        }
+    }
+}
+
+#[derive(Component, Default, Debug)]
+pub struct LastTouchedBy(pub Option<Side>);
+
+// Paddle hits this ball took, each one speeds it up
+#[derive(Component, Default, Debug)]
+struct RallyCount(pub usize);
+
+impl Ball {
+    pub fn get_radius(&self) -> f32 {
+        if self.kind == BallKind::Golden {
+            BALL_RADIUS * 1.3
+        } else if self.points > 0 {
+            BALL_RADIUS * 0.85
+        } else {
+            BALL_RADIUS
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct BallTextMarker;
+
+#[derive(Component)]
+pub struct BallSprite;
+
+// Where the ball was drawn over the last frames, newest first
+#[derive(Component, Default)]
+struct BallTrail(pub VecDeque<Vec2>);
+
+// One of a ball's trail copies, 0 is the closest to the ball
+#[derive(Component)]
+pub struct TrailDot(pub usize);
+
+// A ball scored with reset_balls_on_goal, hidden and out of play until a serve takes it back
+#[derive(Component)]
+pub struct ParkedBall;
+
+#[derive(Component)]
+pub struct BallInspectorMarker;
+
+#[derive(Component)]
+pub struct DelayedExternalForce(pub ExternalForce);
+
+#[derive(Resource, Default)]
+pub struct PointBallCount(pub u8);
+
+// Paddle hits since the last goal
+#[derive(Resource, Default)]
+pub struct RallyCounter(pub usize);
+
+// How often serves come and how many balls may be in play, ramping with the match's play time
+#[derive(Resource, Clone, Debug)]
+pub struct BallSpawnConfig {
+    pub interval: f32,
+    pub min_interval: f32,
+    // Seconds taken off the interval per second played
+    pub interval_ramp: f32,
+    // Serve speed gained per second played, up to max_speed_scale
+    pub speed_ramp: f32,
+    pub max_speed_scale: f32,
+    // Serves wait while this many balls are in play, bonus and multiball copies stop there too
+    pub max_balls: usize,
+}
+
+impl BallSpawnConfig {
+    pub fn for_config(config: &GameConfig) -> Self {
+        let mode_default = if config.mode.endless() {
+            Self {
+                interval: SURVIVAL_START_INTERVAL,
+                min_interval: SURVIVAL_MIN_INTERVAL,
+                interval_ramp: SURVIVAL_INTERVAL_RAMP,
+                speed_ramp: SURVIVAL_SPEED_RAMP,
+                max_speed_scale: SURVIVAL_MAX_SPEED_SCALE,
+                max_balls: config.max_balls,
+            }
+        } else {
+            Self {
+                interval: 0.01,
+                min_interval: 0.01,
+                interval_ramp: 0.,
+                speed_ramp: 0.,
+                max_speed_scale: 1.,
+                max_balls: config.max_balls,
+            }
+        };
+        let interval = config.serve_interval.unwrap_or(mode_default.interval);
+        Self {
+            interval,
+            min_interval: mode_default.min_interval.min(interval),
+            interval_ramp: config.serve_ramp.unwrap_or(mode_default.interval_ramp),
+            ..mode_default
+        }
+    }
+
+    // Time until the next serve and the serve speed scale, this far into the match
+    pub fn at(&self, elapsed: f32) -> (Duration, f32) {
+        let interval = (self.interval - elapsed * self.interval_ramp).max(self.min_interval);
+        let speed_scale = (1. + elapsed * self.speed_ramp).min(self.max_speed_scale);
+        (Duration::from_secs_f32(interval), speed_scale)
+    }
+}
+
+// Where serve directions come from, tournaments use a reproducible sequence instead of GameRng
+#[derive(Resource, Default)]
+pub enum ServeQueue {
+    #[default]
+    Fresh,
+    Seeded(StdRng),
+    Fixed {
+        serves: Vec<Vec2>,
+        next: usize,
+    },
+}
+
+impl ServeQueue {
+    pub fn from_config(config: &GameConfig, court: &Court) -> Self {
+        if config.corner_drill {
+            return Self::Fixed {
+                serves: corner_drill_serves(court).to_vec(),
+                next: 0,
+            };
+        }
+        if let Some(path) = &config.serve_file {
+            match load_serve_file(path) {
+                Ok(serves) if !serves.is_empty() => return Self::Fixed { serves, next: 0 },
+                Ok(_) => warn!("Serve file {path} has no serves, using random serves"),
+                Err(error) => warn!("Could not load serve file {path}: {error}"),
+            }
+        }
+        match config.serve_seed {
+            Some(seed) => Self::Seeded(StdRng::seed_from_u64(seed)),
+            None => Self::Fresh,
+        }
+    }
+
+    pub fn next(&mut self, side: Side, rng: &mut GameRng) -> Vec2 {
+        match self {
+            Self::Fresh => serve_direction(side, &mut rng.0),
+            Self::Seeded(serve_rng) => serve_direction(side, serve_rng),
+            Self::Fixed { serves, next } => {
+                let serve = serves[*next % serves.len()];
+                *next += 1;
+                serve
+            }
+        }
+    }
+}
+
+// Training drill, from the serve spot toward each corner in turn: top-left, bottom-left,
+// top-right, bottom-right, a ball's width away from the walls
+fn corner_drill_serves(court: &Court) -> [Vec2; 4] {
+    let corner = court.half_size - Vec2::splat(BALL_RADIUS * 2.);
+    [
+        Vec2::new(-corner.x, corner.y),
+        Vec2::new(-corner.x, -corner.y),
+        Vec2::new(corner.x, corner.y),
+        Vec2::new(corner.x, -corner.y),
+    ]
+    .map(Vec2::normalize)
+}
+
+// One serve per line, "left" or "right" followed by the angle in degrees, e.g. "left 20"
+fn load_serve_file(path: &str) -> Result<Vec<Vec2>, String> {
+    parse_serves(&read_shared_file(path)?)
+}
+
+pub fn parse_serves(contents: &str) -> Result<Vec<Vec2>, String> {
+    let mut serves = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let to_left = match parts.next() {
+            Some("left") => true,
+            Some("right") => false,
+            _ => return Err(format!("line {}: expected left or right", number + 1)),
+        };
+        let angle = parts
+            .next()
+            .and_then(|v| v.parse::<f32>().ok())
+            .filter(|angle| angle.is_finite() && angle.abs() < 90.)
+            .ok_or_else(|| format!("line {}: expected an angle below 90 degrees", number + 1))?;
+        if let Some(extra) = parts.next() {
+            return Err(format!(
+                "line {}: unexpected {extra:?} after the angle",
+                number + 1
+            ));
+        }
+        if serves.len() == MAX_SERVES {
+            return Err(format!("more than {MAX_SERVES} serves"));
+        }
+        let mut direction = Vec2::from_angle(angle.to_radians());
+        if to_left {
+            direction.x = -direction.x;
+        }
+        serves.push(direction);
+    }
+    Ok(serves)
+}
+
+// A telegraphed serve, the ball launches from here when the timer runs out
+#[derive(Component)]
+pub struct PendingServe {
+    pub timer: Timer,
+    pub direction: Vec2,
+    pub points: usize,
+    pub kind: BallKind,
+}
+
+// Holds back serves after a goal so players can reposition
+#[derive(Resource, Default)]
+pub struct ServeCountdown(pub Option<Timer>);
+
+impl ServeCountdown {
+    pub fn running(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+#[derive(Component)]
+pub struct ServeCountdownText;
+
+// Serves head to whoever conceded last, like table tennis, and randomly until the first goal
+#[derive(Resource)]
+pub struct NextServe(pub Side);
+
+impl Default for NextServe {
+    fn default() -> Self {
+        Self(Side::Random)
+    }
+}
+
+#[derive(Resource)]
+pub struct BallAssets {
+    pub point_ball: Handle<Image>,
+}
+
+pub fn spawn_ball(
+    mut commands: Commands,
+    (ball_assets, cosmetics): (Res<BallAssets>, Res<CosmeticsConfig>),
+    spatial_query: SpatialQuery,
+    mut timer: Local<Timer>,
+    mut blocked_for: Local<Stopwatch>,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    (survival, spawn_config): (Res<SurvivalRun>, Res<BallSpawnConfig>),
+    mut rng: ResMut<GameRng>,
+    (mut serves, next_serve, court): (ResMut<ServeQueue>, Res<NextServe>, Res<Court>),
+    (stray_balls, parked): (Query<(), With<Ball>>, Query<Entity, With<ParkedBall>>),
+    paddles: Query<(&Position, &PaddleSize, &Paddle)>,
+    pending_serves: Query<(), With<PendingServe>>,
+    (sfx, audio): (Res<AudioAssets>, Res<AudioSettings>),
+    game_speed: Res<GameSpeed>,
+    mut point_ball_count: ResMut<PointBallCount>,
+) {
+    timer.tick(time.delta());
+    // A full court holds the serve until a ball leaves, rather than piling more on
+    if stray_balls.iter().count() >= spawn_config.max_balls {
+        return;
+    }
+    if timer.finished() && pending_serves.is_empty() {
+        let ball_collider = Collider::ball(BALL_RADIUS);
+        // Co-op balls come from the launcher on the right
+        let mut ball_position = if config.mode == GameMode::Coop {
+            Vec2::new(court.half_size.x * COOP_LAUNCHER_DEPTH, 0.)
+        } else {
+            Vec2::ZERO
+        };
+        let blockers = |spot: Vec2| -> Vec<Entity> {
+            spatial_query
+                .shape_intersections(
+                    &ball_collider,
+                    spot,
+                    0.,
+                    SpatialQueryFilter::new().with_masks([Layer::Ball, Layer::Paddle]),
+                )
+                .into_iter()
+                .filter(|entity| match paddles.get(*entity) {
+                    // The catch assist widens paddle colliders, only the paddle body itself blocks
+                    Ok((position, size, paddle)) => {
+                        let extent = paddle.side.oriented(size.extent());
+                        rect_overlaps_circle(position.0, extent, spot, BALL_RADIUS)
+                    }
+                    Err(_) => true,
+                })
+                .collect()
+        };
+        let mut intersections = blockers(ball_position);
+
+        // A paddle parked on the serve spot doesn't stall the match, serve beside it instead
+        if !intersections.is_empty() && intersections.iter().all(|e| paddles.contains(*e)) {
+            let step = PADDLE_SIZE.y + BALL_RADIUS * 2.;
+            if let Some(spot) = [step, -step, step * 2., -step * 2.]
+                .into_iter()
+                .map(|y| Vec2::new(0., y))
+                .find(|spot| blockers(*spot).is_empty())
+            {
+                ball_position = spot;
+                intersections.clear();
+            }
+        }
+
+        let mut blocked = !intersections.is_empty();
+        if blocked {
+            blocked_for.tick(time.delta());
+            if blocked_for.elapsed_secs() >= config.serve_block_timeout {
+                warn!(
+                    "Serve spot blocked for {:.1}s, clearing {} stray ball(s) and serving anyway",
+                    blocked_for.elapsed_secs(),
+                    intersections
+                        .iter()
+                        .filter(|e| stray_balls.contains(**e))
+                        .count(),
+                );
+                for entity in intersections {
+                    if stray_balls.contains(entity) {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+                blocked = false;
+            }
+        }
+
+        if !blocked {
+            blocked_for.reset();
+            // The play time doubles as the ramp clock, it restarts with the match
+            let (interval, speed_scale) = spawn_config.at(survival.time.elapsed_secs());
+            let spawn_direction = match config.mode {
+                GameMode::Versus
+                | GameMode::Timed
+                | GameMode::PointBallRace
+                | GameMode::Breakout
+                | GameMode::FourPlayer => next_serve.0.clone(),
+                GameMode::Survival | GameMode::Coop => Side::Left,
+            };
+
+            timer.set_duration(interval);
+            timer.reset();
+
+            let direction =
+                serves.next(spawn_direction, &mut rng) * speed_scale * game_speed.serve_scale();
+            // A lone player has no opponent to win the match from, and a zero chance leaves the
+            // random sequence untouched
+            let golden = !config.mode.lone_player()
+                && config.golden_ball_chance > 0.
+                && rng.0.gen::<f32>() < config.golden_ball_chance;
+            let point_ball = !golden
+                && config.point_ball_chance > 0.
+                && point_ball_count.0 < MAX_SERVED_POINT_BALLS
+                && rng.0.gen::<f32>() < config.point_ball_chance;
+            let (kind, points) = if golden {
+                (BallKind::Golden, 0)
+            } else if point_ball {
+                point_ball_count.0 += 1;
+                (BallKind::Point, SERVED_POINT_BALL_POINTS)
+            } else {
+                (BallKind::Normal, 0)
+            };
+            if golden {
+                commands.spawn((
+                    AudioBundle {
+                        source: sfx.golden_ball.clone(),
+                        settings: audio.sfx(),
+                    },
+                    SoundEffect,
+                ));
+            }
+            if config.serve_warning <= 0. {
+                serve_ball_at(
+                    &mut commands,
+                    parked.iter().next(),
+                    &ball_assets,
+                    &cosmetics,
+                    ball_position,
+                    direction,
+                    points,
+                    kind,
+                );
+                return;
+            }
+            let skin = cosmetics.ball_skins[0];
+            commands.spawn((
+                SpriteBundle {
+                    texture: skin.texture(&ball_assets),
+                    sprite: Sprite {
+                        color: skin.color(kind).with_a(0.3),
+                        custom_size: Some(Vec2::ONE * (BALL_RADIUS * 2.)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(ball_position.extend(3.)),
+                    ..default()
+                },
+                PendingServe {
+                    timer: Timer::from_seconds(config.serve_warning, TimerMode::Once),
+                    direction,
+                    points,
+                    kind,
+                },
+                MatchEntity,
+            ));
+            // Sped up to fit the whole rising clip into the warning
+            commands.spawn((
+                AudioBundle {
+                    source: sfx.serve_charge.clone(),
+                    settings: audio
+                        .sfx()
+                        .with_speed(SERVE_CHARGE_CLIP_LENGTH / config.serve_warning),
+                },
+                SoundEffect,
+            ));
+        }
+    }
+}
+
+fn start_serve_countdown(
+    mut goal_scored: EventReader<GoalScored>,
+    config: Res<GameConfig>,
+    mut countdown: ResMut<ServeCountdown>,
+) {
+    if goal_scored.iter().count() > 0 && config.serve_countdown > 0. {
+        countdown.0 = Some(Timer::from_seconds(config.serve_countdown, TimerMode::Once));
+    }
+}
+
+fn aim_next_serve(mut goal_scored: EventReader<GoalScored>, mut next_serve: ResMut<NextServe>) {
+    if let Some(goal) = goal_scored.iter().last() {
+        next_serve.0 = goal.side.clone();
+    }
+}
+
+// A new match starts with a random serve again
+fn reset_next_serve(mut next_serve: ResMut<NextServe>) {
+    *next_serve = NextServe::default();
+}
+
+fn tick_serve_countdown(
+    time: Res<Time>,
+    mut countdown: ResMut<ServeCountdown>,
+    mut texts: Query<&mut Text, With<ServeCountdownText>>,
+) {
+    let Some(timer) = countdown.0.as_mut() else {
+        return;
+    };
+    timer.tick(time.delta());
+    let label = if timer.finished() {
+        countdown.0 = None;
+        String::new()
+    } else {
+        // 3, 2, 1 for a three second countdown
+        format!("{}", timer.remaining_secs().ceil())
+    };
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = label.clone();
+    }
+}
+
+pub fn launch_pending_serves(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    ball_assets: Res<BallAssets>,
+    cosmetics: Res<CosmeticsConfig>,
+    mut pending_serves: Query<(Entity, &mut PendingServe, &mut Transform)>,
+    parked: Query<Entity, With<ParkedBall>>,
+) {
+    // Each parked ball is only taken back once, even when two serves launch on the same frame
+    let mut parked = parked.iter();
+    for (entity, mut serve, mut transform) in pending_serves.iter_mut() {
+        serve.timer.tick(time.delta());
+        if !config.reduced_motion {
+            transform.scale = Vec3::splat(1. + serve.timer.percent() * (SERVE_WARNING_SCALE - 1.));
+        }
+        if serve.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            serve_ball_at(
+                &mut commands,
+                parked.next(),
+                &ball_assets,
+                &cosmetics,
+                transform.translation.xy(),
+                serve.direction,
+                serve.points,
+                serve.kind,
+            );
+        }
+    }
+}
+
+fn rect_overlaps_circle(center: Vec2, size: Vec2, circle: Vec2, radius: f32) -> bool {
+    let closest = circle.clamp(center - size / 2., center + size / 2.);
+    closest.distance_squared(circle) < radius * radius
+}
+
+pub fn serve_direction(spawn_direction: Side, rng: &mut impl Rng) -> Vec2 {
+    let direction_angle = rng.gen::<f32>() * (PI / 2. - PI / 4.);
+    let mut direction = Vec2::from_angle(direction_angle);
+    // Serves toward the top and bottom goals are turned a quarter
+    if spawn_direction.horizontal() {
+        return if spawn_direction == Side::Top {
+            direction.perp()
+        } else {
+            -direction.perp()
+        };
+    }
+    if spawn_direction == Side::Left || (spawn_direction == Side::Random && rng.gen::<bool>()) {
+        direction.x = -direction.x;
+    }
+    direction
+}
+
+pub fn spawn_ball_at(
+    commands: &mut Commands,
+    ball_assets: &BallAssets,
+    cosmetics: &CosmeticsConfig,
+    position: Vec2,
+    direction: Vec2,
+    points: usize,
+    kind: BallKind,
+) -> Entity {
+    let mut entity = commands.spawn_empty();
+    insert_ball(
+        &mut entity,
+        ball_assets,
+        cosmetics,
+        position,
+        direction,
+        points,
+        kind,
+    );
+    entity.id()
+}
+
+// Serves go through here, a parked ball is taken back as if it were new: its children, trail,
+// spin and status effects go and everything a fresh ball has is put back
+fn serve_ball_at(
+    commands: &mut Commands,
+    parked: Option<Entity>,
+    ball_assets: &BallAssets,
+    cosmetics: &CosmeticsConfig,
+    position: Vec2,
+    direction: Vec2,
+    points: usize,
+    kind: BallKind,
+) {
+    let Some(parked) = parked else {
+        spawn_ball_at(
+            commands,
+            ball_assets,
+            cosmetics,
+            position,
+            direction,
+            points,
+            kind,
+        );
+        return;
+    };
+    let mut entity = commands.entity(parked);
+    // Physics only reads the transform when a body is first added, the position is set directly
+    entity
+        .despawn_descendants()
+        .remove::<(ParkedBall, Sleeping)>()
+        .insert((
+            Position(position),
+            LinearVelocity::ZERO,
+            AngularVelocity::ZERO,
+        ));
+    insert_ball(
+        &mut entity,
+        ball_assets,
+        cosmetics,
+        position,
+        direction,
+        points,
+        kind,
+    );
+}
+
+fn insert_ball(
+    entity: &mut EntityCommands,
+    ball_assets: &BallAssets,
+    cosmetics: &CosmeticsConfig,
+    position: Vec2,
+    direction: Vec2,
+    points: usize,
+    kind: BallKind,
+) {
+    let ball = Ball { points, kind };
+    let radius = ball.get_radius();
+    // Served in the first player's skin, see reskin_returned_balls
+    let skin = cosmetics.ball_skins[0];
+    let color = skin.color(kind);
+
+    entity
+        .insert((
+            SpatialBundle::from_transform(Transform::from_translation(position.extend(4.))),
+            PreviousPosition(position),
+            RigidBody::Dynamic,
+            Collider::ball(radius),
+            CollisionLayers::new(
+                [Layer::Ball],
+                [
+                    Layer::Ball,
+                    Layer::Paddle,
+                    Layer::Wall,
+                    Layer::PowerUp,
+                    Layer::Obstacle,
+                    Layer::Brick,
+                ],
+            ),
+            DelayedExternalForce(
+                ExternalForce::new(direction * INITIAL_FORCE).with_persistence(false),
+            ),
+            Restitution::new(0.7),
+            Friction::ZERO,
+            LockedAxes::ROTATION_LOCKED,
+            ball,
+            skin,
+            LastTouchedBy::default(),
+            RallyCount::default(),
+            BallTrail::default(),
+            MatchEntity,
+        ))
+        .with_children(|builder| {
+            for index in 0..BALL_TRAIL_LENGTH {
+                let fraction = 1. - index as f32 / BALL_TRAIL_LENGTH as f32;
+                builder.spawn((
+                    SpriteBundle {
+                        texture: skin.texture(ball_assets),
+                        sprite: Sprite {
+                            color: color.with_a(0.35 * fraction),
+                            custom_size: Some(Vec2::ONE * (radius * 2. * (0.4 + 0.6 * fraction))),
+                            ..default()
+                        },
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    TrailDot(index),
+                ));
+            }
+            // Its own entity so it can spin while the physics body stays rotation locked
+            builder.spawn((
+                SpriteBundle {
+                    texture: skin.texture(ball_assets),
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::ONE * (radius * 2.)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                BallSprite,
+            ));
+            builder.spawn((
+                Text2dBundle {
+                    text: Text {
+                        sections: vec![TextSection::new(
+                            point_label(points),
+                            TextStyle {
+                                font_size: 20.0,
+                                color: Color::BLACK,
+                                ..default()
+                            },
+                        )],
+                        ..Default::default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, 99.0),
+                    ..default()
+                },
+                BallTextMarker,
+            ));
+        });
+}
+
+// Plain balls carry no number, point balls show the extra points they are worth
+pub fn point_label(points: usize) -> String {
+    if points > 0 {
+        format!("+{points}")
+    } else {
+        String::new()
+    }
+}
+
+fn remember_previous_positions(mut bodies: Query<(&Position, &mut PreviousPosition)>) {
+    for (position, mut previous) in bodies.iter_mut() {
+        previous.0 = position.0;
+    }
+}
+
+// Drawn between the last two fixed steps, by how far the clock has run into the next one
+fn interpolate_transforms(
+    fixed_time: Res<FixedTime>,
+    mut bodies: Query<(&Position, &PreviousPosition, &mut Transform)>,
+) {
+    let blend = (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.);
+    for (position, previous, mut transform) in bodies.iter_mut() {
+        let z = transform.translation.z;
+        transform.translation = previous.0.lerp(position.0, blend).extend(z);
+    }
+}
+
+// Trail copies are children of the ball, placed back where it was drawn on earlier frames
+fn update_ball_trails(
+    config: Res<GameConfig>,
+    game_speed: Res<GameSpeed>,
+    mut balls: Query<(&Transform, &LinearVelocity, &mut BallTrail, &Children)>,
+    mut dots: Query<(&TrailDot, &mut Transform, &mut Visibility), Without<BallTrail>>,
+) {
+    for (transform, velocity, mut trail, children) in balls.iter_mut() {
+        let position = transform.translation.truncate();
+        trail.0.push_front(position);
+        trail.0.truncate(BALL_TRAIL_LENGTH + 1);
+        let speed = (velocity.length() / game_speed.max_ball_speed()).min(1.);
+        let shown = if config.ball_trail {
+            (speed * BALL_TRAIL_LENGTH as f32).ceil() as usize
+        } else {
+            0
+        };
+        for child in children.iter() {
+            let Ok((dot, mut dot_transform, mut visibility)) = dots.get_mut(*child) else {
+                continue;
+            };
+            // The newest entry is the ball itself, dot 0 sits one frame behind it
+            match trail.0.get(dot.0 + 1) {
+                Some(point) if dot.0 < shown => {
+                    dot_transform.translation =
+                        (*point - position).extend(-1. - dot.0 as f32 * 0.01);
+                    *visibility = Visibility::Inherited;
+                }
+                _ => *visibility = Visibility::Hidden,
+            }
+        }
+    }
+}
+
+pub fn apply_delayed_external_forces(
+    mut commands: Commands,
+    q: Query<(Entity, &DelayedExternalForce)>,
+) {
+    for (e, d) in q.iter() {
+        commands
+            .entity(e)
+            .insert(d.0)
+            .remove::<DelayedExternalForce>();
+    }
+}
+
+pub fn check_goals(
+    mut collision_event_reader: EventReader<Collision>,
+    config: Res<GameConfig>,
+    court: Res<Court>,
+    goals: Query<&Goal>,
+    balls: Query<(&Ball, &Position, &LastTouchedBy)>,
+    mut goal_scored: EventWriter<GoalScored>,
+) {
+    // Only finds the goals, the score, the ball and everything else a goal changes are left to
+    // the systems reading GoalScored. Collision events come in no particular order, so goals are
+    // sorted before being sent to keep simultaneous goals resolving the same way every run
+    let mut scored = Vec::new();
+    for Collision(contact) in collision_event_reader.iter() {
+        if let Some((goal, _goal_entity, ball, ball_entity)) =
+            if let Ok(goal) = goals.get(contact.entity1) {
+                if let Ok(ball) = balls.get(contact.entity2) {
+                    Some((goal, contact.entity1, ball, contact.entity2))
+                } else {
+                    None
+                }
+            } else if let Ok(goal) = goals.get(contact.entity2) {
+                if let Ok(ball) = balls.get(contact.entity1) {
+                    Some((goal, contact.entity2, ball, contact.entity1))
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        {
+            let (ball, position, last_touched) = ball;
+            // Corners are harder to defend than the middle of the goal
+            let corner = position.y.abs() > court.half_size.y * (1. - GOAL_CORNER_FRACTION);
+            let value = match config.corner_goal_points {
+                Some(points) if corner => points,
+                _ => 1,
+            } + ball.points;
+            // The goal owner concedes. The point goes to the other player, or in a four player
+            // match to whoever touched the ball last unless that was the owner
+            let scorer = if config.mode == GameMode::FourPlayer {
+                last_touched.0.clone().filter(|side| *side != goal.side)
+            } else {
+                Some(goal.side.opposite())
+            };
+            scored.push(GoalScored {
+                side: goal.side.clone(),
+                scorer,
+                ball: ball_entity,
+                position: position.0,
+                points: ball.points,
+                kind: ball.kind,
+                value,
+            });
+        }
+    }
+
+    scored.sort_by_key(|goal| goal.ball);
+    scored.dedup_by_key(|goal| goal.ball);
+    goal_scored.send_batch(scored);
+}
+
+// A scored ball leaves the court, or with reset_balls_on_goal is parked until the next serve
+pub fn remove_scored_balls(
+    mut commands: Commands,
+    mut goal_scored: EventReader<GoalScored>,
+    mut point_ball_count: ResMut<PointBallCount>,
+    config: Res<GameConfig>,
+) {
+    for goal in goal_scored.iter() {
+        if goal.points > 0 {
+            if let Some(new_score) = point_ball_count.0.checked_sub(1) {
+                point_ball_count.0 = new_score;
+            }
+        }
+
+        let Some(mut entity) = commands.get_entity(goal.ball) else {
+            continue;
+        };
+        if config.reset_balls_on_goal {
+            // Without Ball nothing counts, steers or draws it, and it collides with nothing
+            entity.remove::<Ball>().insert((
+                ParkedBall,
+                Visibility::Hidden,
+                CollisionLayers::none(),
+                LinearVelocity::ZERO,
+                AngularVelocity::ZERO,
+            ));
+        } else {
+            entity.despawn_recursive();
+        }
+    }
+}
+
+fn aim_paddle_bounces(
+    mut paddle_hit: EventReader<PaddleHit>,
+    config: Res<GameConfig>,
+    paddles: Query<(&Position, &Rotation, &PaddleSize, &PaddleShape), With<Paddle>>,
+    mut balls: Query<(&Position, &mut LinearVelocity, &Ball), Without<Paddle>>,
+) {
+    if !config.paddle_aim {
+        paddle_hit.clear();
+        return;
+    }
+    for hit in paddle_hit.iter() {
+        let (Ok((paddle, rotation, size, shape)), Ok((ball, mut velocity, ball_kind))) =
+            (paddles.get(hit.paddle), balls.get_mut(hit.ball))
+        else {
+            continue;
+        };
+        // Paddles across the top and bottom goals bounce the same way turned a quarter
+        let (across, along) = if hit.side.horizontal() {
+            (Vec2::Y, Vec2::NEG_X)
+        } else {
+            (Vec2::X, Vec2::Y)
+        };
+        let tilt = rotation.as_radians();
+        // -1 at the bottom end of the paddle, 1 at the top, measured along the tilted paddle
+        let reach = size.extent().y / 2. + ball_kind.get_radius();
+        let tilted_along = Vec2::from_angle(tilt).rotate(along);
+        let offset = ((ball.0 - paddle.0).dot(tilted_along) / reach).clamp(-1., 1.);
+        let away = if (ball.0 - paddle.0).dot(across) >= 0. {
+            1.
+        } else {
+            -1.
+        };
+        // A counterclockwise tilt turns the paddle's face, and the return, with it
+        let angle =
+            (shape.bounce_angle(offset) + away * tilt).clamp(-MAX_RETURN_ANGLE, MAX_RETURN_ANGLE);
+        velocity.0 = (across * angle.cos() * away + along * angle.sin()) * velocity.length();
+    }
+}
+
+fn smash_balls(
+    mut paddle_hit: EventReader<PaddleHit>,
+    config: Res<GameConfig>,
+    paddles: Query<(&Position, &LinearVelocity), With<Paddle>>,
+    mut balls: Query<
+        (
+            &Position,
+            &mut LinearVelocity,
+            &mut AngularVelocity,
+            &mut LockedAxes,
+            &Ball,
+        ),
+        Without<Paddle>,
+    >,
+) {
+    for hit in paddle_hit.iter() {
+        let (
+            Ok((paddle, paddle_velocity)),
+            Ok((ball, mut velocity, mut spin, mut locked, ball_kind)),
+        ) = (paddles.get(hit.paddle), balls.get_mut(hit.ball))
+        else {
+            continue;
+        };
+        velocity.0 += paddle_velocity.0 * config.smash_transfer;
+        if config.curve_shots {
+            // Brushing up the ball's left side spins it clockwise
+            let away = if ball.x >= paddle.x { 1. } else { -1. };
+            *locked = LockedAxes::new();
+            spin.0 = -away * paddle_velocity.y / ball_kind.get_radius() * CURVE_TRANSFER;
+        }
+    }
+}
+
+// Pushes returns along after aiming, so the charge speeds the ball up without changing its angle
+fn release_charged_smashes(
+    mut paddle_hit: EventReader<PaddleHit>,
+    game_speed: Res<GameSpeed>,
+    mut paddles: Query<&mut Charge>,
+    mut balls: Query<&mut LinearVelocity, With<Ball>>,
+) {
+    for hit in paddle_hit.iter() {
+        let (Ok(mut charge), Ok(mut velocity)) =
+            (paddles.get_mut(hit.paddle), balls.get_mut(hit.ball))
+        else {
+            continue;
+        };
+        if charge.window <= 0. {
+            continue;
+        }
+        velocity.0 = (velocity.0 + velocity.normalize_or_zero() * SMASH_IMPULSE * charge.released)
+            .clamp_length_max(game_speed.max_ball_speed());
+        charge.window = 0.;
+        charge.released = 0.;
+    }
+}
+
+// Magnus effect, a spinning ball is pushed sideways to its motion
+fn curve_spinning_balls(
+    time: Res<Time>,
+    mut balls: Query<(&mut LinearVelocity, &mut AngularVelocity), With<Ball>>,
+) {
+    for (mut velocity, mut spin) in balls.iter_mut() {
+        if spin.0 == 0. {
+            continue;
+        }
+        let curve = velocity.perp() * spin.0 * MAGNUS_FACTOR * time.delta_seconds();
+        velocity.0 += curve;
+        spin.0 *= (-SPIN_DECAY * time.delta_seconds()).exp();
+    }
+}
+
+fn boost_fast_shots(
+    mut paddle_hit: EventReader<PaddleHit>,
+    game_speed: Res<GameSpeed>,
+    paddles: Query<Option<&Children>, With<Paddle>>,
+    effects: Query<&StatusEffect>,
+    mut balls: Query<&mut LinearVelocity, With<Ball>>,
+) {
+    let fast = |paddle| {
+        paddles
+            .get(paddle)
+            .is_ok_and(|children| has_effect(children, &effects, EffectKind::FastShots))
+    };
+    for hit in paddle_hit.iter().filter(|hit| fast(hit.paddle)) {
+        if let Ok(mut velocity) = balls.get_mut(hit.ball) {
+            velocity.0 =
+                (velocity.0 * FAST_SHOT_SCALE).clamp_length_max(game_speed.max_ball_speed());
+        }
+    }
+}
+
+fn speed_up_rallies(
+    mut paddle_hit: EventReader<PaddleHit>,
+    config: Res<GameConfig>,
+    game_speed: Res<GameSpeed>,
+    mut balls: Query<(&mut LinearVelocity, &mut RallyCount)>,
+) {
+    for hit in paddle_hit.iter() {
+        let Ok((mut velocity, mut rally)) = balls.get_mut(hit.ball) else {
+            continue;
+        };
+        rally.0 += 1;
+        if config.rally_speed_up > 0. {
+            velocity.0 = (velocity.0 * (1. + config.rally_speed_up))
+                .clamp_length_max(game_speed.max_ball_speed());
+        }
+    }
+}
+
+fn cap_ball_speed(game_speed: Res<GameSpeed>, mut balls: Query<&mut LinearVelocity, With<Ball>>) {
+    let max_speed = game_speed.max_ball_speed();
+    for mut velocity in balls.iter_mut() {
+        if velocity.length() > max_speed {
+            velocity.0 = velocity.0.clamp_length_max(max_speed);
+        }
+    }
+}
+
+// A fast enough ball can tunnel through a goal or wall between two steps and fly off forever,
+// it's removed so the serves carry on without it
+fn remove_escaped_balls(
+    mut commands: Commands,
+    court: Res<Court>,
+    balls: Query<(Entity, &Position, &Ball)>,
+    mut point_ball_count: ResMut<PointBallCount>,
+) {
+    let limit = court.half_size + Vec2::splat(ESCAPED_BALL_MARGIN);
+    for (entity, position, ball) in balls.iter() {
+        if position.x.abs() <= limit.x && position.y.abs() <= limit.y {
+            continue;
+        }
+        warn!("A ball escaped the court at {}, removing it", position.0);
+        if ball.points > 0 {
+            point_ball_count.0 = point_ball_count.0.saturating_sub(1);
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn pull_balls_toward_goals(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    game_speed: Res<GameSpeed>,
+    goals: Query<&Position, With<Goal>>,
+    mut balls: Query<(&Position, &mut LinearVelocity), With<Ball>>,
+) {
+    let goals: Vec<Vec2> = goals.iter().map(|goal| goal.0).collect();
+    if config.goal_pull <= 0. || goals.is_empty() {
+        return;
+    }
+    for (position, mut velocity) in balls.iter_mut() {
+        let pull = nearest_goal_direction(position.0, &goals) * config.goal_pull;
+        velocity.0 += pull * time.delta_seconds();
+        velocity.0 = velocity.0.clamp_length_max(game_speed.max_ball_speed());
+    }
+}
+
+// Toward the closest goal, on the top and bottom too in four player matches. Zero without goals
+pub fn nearest_goal_direction(position: Vec2, goals: &[Vec2]) -> Vec2 {
+    goals
+        .iter()
+        .min_by(|a, b| {
+            a.distance_squared(position)
+                .total_cmp(&b.distance_squared(position))
+        })
+        .map_or(Vec2::ZERO, |goal| (*goal - position).normalize_or_zero())
+}
+
+pub fn check_paddle_hits(
+    mut collision_started: EventReader<CollisionStarted>,
+    balls: Query<(), With<Ball>>,
+    paddles: Query<&Paddle>,
+    mut paddle_hit: EventWriter<PaddleHit>,
+) {
+    for CollisionStarted(entity1, entity2) in collision_started.iter() {
+        let (ball, paddle_entity) = if balls.contains(*entity1) {
+            (*entity1, *entity2)
+        } else if balls.contains(*entity2) {
+            (*entity2, *entity1)
+        } else {
+            continue;
+        };
+        if let Ok(paddle) = paddles.get(paddle_entity) {
+            paddle_hit.send(PaddleHit {
+                ball,
+                paddle: paddle_entity,
+                side: paddle.side.clone(),
+            });
+        }
+    }
+}
+
+fn update_last_touched(
+    mut paddle_hit: EventReader<PaddleHit>,
+    mut balls: Query<&mut LastTouchedBy>,
+) {
+    for hit in paddle_hit.iter() {
+        if let Ok(mut last_touched) = balls.get_mut(hit.ball) {
+            last_touched.0 = Some(hit.side.clone());
+        }
+    }
+}
+
+fn update_rally_counter(
+    mut commands: Commands,
+    mut paddle_hit: EventReader<PaddleHit>,
+    mut goal_scored: EventReader<GoalScored>,
+    mut rally: ResMut<RallyCounter>,
+    mut point_ball_count: ResMut<PointBallCount>,
+    config: Res<GameConfig>,
+    spawn_config: Res<BallSpawnConfig>,
+    ball_assets: Res<BallAssets>,
+    cosmetics: Res<CosmeticsConfig>,
+    mut rng: ResMut<GameRng>,
+    balls: Query<(), With<Ball>>,
+    court: Res<Court>,
+) {
+    if !goal_scored.is_empty() {
+        goal_scored.clear();
+        rally.0 = 0;
+    }
+
+    let mut ball_count = balls.iter().count();
+    for _ in paddle_hit.iter() {
+        rally.0 += 1;
+        if config.rally_bonus_every == 0 || rally.0 % config.rally_bonus_every != 0 {
+            continue;
+        }
+        if ball_count >= spawn_config.max_balls {
+            continue;
+        }
+
+        let height = court.half_size.y * 0.5;
+        let position = Vec2::new(0., if rng.0.gen::<bool>() { height } else { -height });
+        let direction = serve_direction(Side::Random, &mut rng.0);
+        spawn_ball_at(
+            &mut commands,
+            &ball_assets,
+            &cosmetics,
+            position,
+            direction,
+            RALLY_BONUS_POINTS,
+            BallKind::Point,
+        );
+        point_ball_count.0 = point_ball_count.0.saturating_add(1);
+        ball_count += 1;
+    }
+}
+
+fn toggle_ball_inspector(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut overlays: ResMut<DebugOverlays>,
+    mut panel: Query<&mut Visibility, With<BallInspectorMarker>>,
+) {
+    if !keys.just_pressed(bindings.ball_inspector) {
+        return;
+    }
+    overlays.ball_inspector = !overlays.ball_inspector;
+    for mut visibility in panel.iter_mut() {
+        *visibility = if overlays.ball_inspector {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+fn update_ball_inspector(
+    overlays: Res<DebugOverlays>,
+    balls: Query<(
+        Entity,
+        &Ball,
+        &Position,
+        &LinearVelocity,
+        &LastTouchedBy,
+        &RallyCount,
+    )>,
+    mut panel: Query<&mut Text, With<BallInspectorMarker>>,
+) {
+    if !overlays.ball_inspector {
+        return;
+    }
+    let Ok(mut text) = panel.get_single_mut() else {
+        return;
+    };
+
+    let mut balls = balls.iter().collect::<Vec<_>>();
+    balls.sort_by_key(|(entity, ..)| *entity);
+
+    let mut lines = format!("Balls: {}", balls.len());
+    for (entity, ball, position, velocity, last_touched, rally) in balls {
+        lines.push_str(&format!(
+            "\n{:?} pos ({:.0}, {:.0}) speed {:.0} hits {} points {} kind {:?} last touched {:?}",
+            entity,
+            position.x,
+            position.y,
+            velocity.length(),
+            rally.0,
+            ball.points,
+            ball.kind,
+            last_touched.0,
+        ));
+    }
+    text.sections[0].value = lines;
+}
+
+// Rolls the sprite as if the ball rolled along its horizontal motion
+fn spin_ball_sprites(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    balls: Query<(&LinearVelocity, &Ball, &Children)>,
+    mut sprites: Query<&mut Transform, With<BallSprite>>,
+) {
+    if !config.ball_spin || config.reduced_motion {
+        return;
+    }
+    for (velocity, ball, children) in balls.iter() {
+        let angle = -velocity.x / ball.get_radius() * time.delta_seconds();
+        for child in children.iter() {
+            if let Ok(mut transform) = sprites.get_mut(*child) {
+                transform.rotate_z(angle);
+            }
+        }
+    }
+}
+
+fn steady_ball_text(
+    config: Res<GameConfig>,
+    cameras: Query<&OrthographicProjection, With<Camera2d>>,
+    balls: Query<&Transform, (With<Ball>, Without<BallTextMarker>)>,
+    mut texts: Query<(&Parent, &mut Transform), With<BallTextMarker>>,
+) {
+    if !config.steady_ball_text {
+        for (_, mut transform) in texts.iter_mut() {
+            transform.scale = Vec3::splat(config.ui_scale);
+        }
+        return;
+    }
+    let zoom = cameras
+        .iter()
+        .next()
+        .map_or(1., |projection| projection.scale);
+    for (parent, mut transform) in texts.iter_mut() {
+        let Ok(ball) = balls.get(parent.get()) else {
+            continue;
+        };
+        transform.rotation = ball.rotation.inverse();
+        transform.scale = Vec3::splat(zoom * config.ui_scale);
+    }
+}
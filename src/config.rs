@@ -0,0 +1,940 @@
+use crate::ai::AiDifficulty;
+use crate::arena::ArenaDefinition;
+use crate::audio::AudioSettings;
+use crate::ball::MAX_BALL_SPEED;
+use crate::cosmetics::CosmeticsConfig;
+use crate::input::{InputSource, KeyBindings};
+use crate::net::DEFAULT_NET_PORT;
+use crate::paddle::PaddleShape;
+use crate::prelude::*;
+use crate::score::{WinningScore, TOURNAMENT_MIN_PLAYERS};
+use crate::ui::{UI_SCALE_MAX, UI_SCALE_MIN};
+
+const MAX_SHARED_FILE_BYTES: u64 = 1024 * 1024;
+pub const SETTINGS_FILE: &str = "settings.ron";
+
+// Extra collider margin around the human paddle at full catch assist strength
+const CATCH_ASSIST_MAX_MARGIN: f32 = 12.;
+
+// How a timed match tied at the final whistle is decided
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TieBreak {
+    // Keep playing, the next goal wins
+    #[default]
+    SuddenDeath,
+    LongestRally,
+    // Fewest goals conceded during the last minute
+    RecentGoals,
+    Draw,
+}
+
+impl TieBreak {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "sudden-death" => Some(Self::SuddenDeath),
+            "longest-rally" => Some(Self::LongestRally),
+            "recent-goals" => Some(Self::RecentGoals),
+            "draw" => Some(Self::Draw),
+            _ => None,
+        }
+    }
+}
+
+// Overall pace of the ball, independent from how well the AI plays
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum GameSpeed {
+    Slow,
+    #[default]
+    Normal,
+    Fast,
+}
+
+impl GameSpeed {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "slow" => Some(Self::Slow),
+            "normal" => Some(Self::Normal),
+            "fast" => Some(Self::Fast),
+            _ => None,
+        }
+    }
+
+    pub fn serve_scale(&self) -> f32 {
+        match self {
+            Self::Slow => 0.75,
+            Self::Normal => 1.,
+            Self::Fast => 1.35,
+        }
+    }
+
+    pub fn max_ball_speed(&self) -> f32 {
+        MAX_BALL_SPEED * self.serve_scale()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MsaaSetting {
+    Off,
+    X2,
+    X4,
+}
+
+impl MsaaSetting {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "off" | "0" | "1" => Some(Self::Off),
+            "2" | "2x" => Some(Self::X2),
+            "4" | "4x" => Some(Self::X4),
+            _ => None,
+        }
+    }
+}
+
+impl Default for MsaaSetting {
+    // Matches Bevy's own default
+    fn default() -> Self {
+        Self::X4
+    }
+}
+
+// How the game window fills the screen, cycled with F11 or from the options menu
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DisplayMode {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl DisplayMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "windowed" => Some(Self::Windowed),
+            "borderless" => Some(Self::Borderless),
+            "fullscreen" => Some(Self::Fullscreen),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Windowed => Self::Borderless,
+            Self::Borderless => Self::Fullscreen,
+            Self::Fullscreen => Self::Windowed,
+        }
+    }
+}
+
+impl From<DisplayMode> for WindowMode {
+    fn from(mode: DisplayMode) -> Self {
+        match mode {
+            DisplayMode::Windowed => WindowMode::Windowed,
+            DisplayMode::Borderless => WindowMode::BorderlessFullscreen,
+            // Exclusive, the monitor switches to the closest video mode
+            DisplayMode::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+}
+
+impl From<MsaaSetting> for Msaa {
+    fn from(setting: MsaaSetting) -> Self {
+        match setting {
+            MsaaSetting::Off => Msaa::Off,
+            MsaaSetting::X2 => Msaa::Sample2,
+            MsaaSetting::X4 => Msaa::Sample4,
+        }
+    }
+}
+
+// Who plays the right paddle
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Opponent {
+    #[default]
+    Ai,
+    // Hotseat, a second player on the same keyboard
+    Human,
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct GameConfig {
+    pub mode: GameMode,
+    pub opponent: Opponent,
+    // Accessibility aid, 0 is off and 1 is the widest forgiveness margin
+    pub catch_assist: f32,
+    // Competitive play ignores every assist
+    pub competitive: bool,
+    pub winning_score: usize,
+    // Games of winning_score points, the match goes to whoever wins the majority
+    pub best_of: usize,
+    // A bonus point ball joins the rally every this many paddle hits, 0 disables it
+    pub rally_bonus_every: usize,
+    // Fraction of speed a ball gains on every paddle hit, up to the game speed's cap
+    pub rally_speed_up: f32,
+    // Where the ball meets the paddle sets its return angle, like classic pong
+    pub paddle_aim: bool,
+    // Fraction of the paddle's velocity a hit passes on to the ball
+    pub smash_transfer: f32,
+    // Hits from a paddle moving along its length spin the ball, which then curves
+    pub curve_shots: bool,
+    // Every paddle hit adds a point to the ball, scored on top of the goal itself
+    pub points_per_hit: bool,
+    // Seconds the serve spot may stay blocked before it is forcibly cleared
+    pub serve_block_timeout: f32,
+    // Seconds counted down on screen after a goal before the next serve, 0 disables it
+    pub serve_countdown: f32,
+    // Seconds between serves and how much quicker they get per second played, None keeps the
+    // mode's own pace
+    pub serve_interval: Option<f32>,
+    pub serve_ramp: Option<f32>,
+    // Balls allowed in play at once, serves and bonus balls wait below it
+    pub max_balls: usize,
+    pub msaa: MsaaSetting,
+    pub display_mode: DisplayMode,
+    // Size of the HUD, menus and ball numbers, 1 is their designed size
+    pub ui_scale: f32,
+    // Skips purely cosmetic motion effects
+    pub reduced_motion: bool,
+    pub ai_difficulty: AiDifficulty,
+    // Paddle colors told apart with any kind of color blindness
+    pub colorblind: bool,
+    // Indexed by player like MatchStats
+    pub paddle_shapes: [PaddleShape; 2],
+    // Camera shake on goals and hard hits, 0 turns it off
+    pub screen_shake: f32,
+    // Self-handicap, the scorer's paddle loses this fraction of its height per goal
+    pub shrink_per_goal: f32,
+    pub shrink_floor: f32,
+    // Seeds the gameplay randomness, a fresh seed is picked when missing
+    pub seed: Option<u64>,
+    // Tournament serves, every match replays the same serve sequence from a seed or a file
+    pub serve_seed: Option<u64>,
+    pub serve_file: Option<String>,
+    pub key_bindings: KeyBindings,
+    // Chaos modifier, acceleration pulling every ball toward its nearest goal
+    pub goal_pull: f32,
+    // Lets the AI step forward to meet incoming balls instead of only moving vertically
+    pub ai_x_positioning: bool,
+    // The AI heads for where the ball will cross its paddle, off it only follows the ball's height
+    pub ai_prediction: bool,
+    // Seconds without input on the menu before a demo match starts, 0 disables it
+    pub attract_delay: f32,
+    // UDP port a LAN host listens on, and the host a LAN client joins
+    pub net_port: u16,
+    pub join_address: String,
+    // Entrants of the next local tournament, the menu edits the one at editing_name as it is typed
+    pub tournament_names: Vec<String>,
+    pub editing_name: Option<usize>,
+    // Goals a lone player can concede before a survival or breakout run ends
+    pub lives: usize,
+    // Seconds on the clock in timed mode
+    pub match_duration: f32,
+    pub tie_break: TieBreak,
+    // Paddle hits in a single rally that earn a defensive wall, 0 disables it
+    pub defensive_wall_streak: usize,
+    // Keeps the on-ball number upright and the same size on screen whatever the camera zoom
+    pub steady_ball_text: bool,
+    pub power_ups: bool,
+    pub max_power_ups: usize,
+    // Minimum seconds between two power-up spawns
+    pub power_up_interval: f32,
+    // Every this many seconds the trailing player gets a temporary bigger paddle, 0 disables it
+    pub handicap_interval: f32,
+    pub game_speed: GameSpeed,
+    // Serves cycle through the four corners instead of random angles, takes over the serve seed and file
+    pub corner_drill: bool,
+    // Score for a goal in a corner zone, flat scoring when off
+    pub corner_goal_points: Option<usize>,
+    // Fills the window behind the arena, Bevy's clear color is kept when missing
+    pub background_color: Option<Color>,
+    // Image from the assets folder stretched behind the arena
+    pub background_image: Option<String>,
+    // Name of the arena file in assets/arenas
+    pub arena: String,
+    // Chance that a serve is a golden ball, which wins the match for whoever scores it
+    pub golden_ball_chance: f32,
+    // Chance for a serve to be a point ball worth extra points
+    pub point_ball_chance: f32,
+    // Purely visual roll of the ball sprite, the physics body never rotates
+    pub ball_spin: bool,
+    // Fading copies behind each ball, longer the faster it goes
+    pub ball_trail: bool,
+    // Opens a second window with a large scoreboard, for streams and local tournaments
+    pub scoreboard_window: bool,
+    // Seconds of sound and visual warning before each serve, 0 serves at once
+    pub serve_warning: f32,
+    // Scored balls are parked out of play for the next serve to take back, not despawned
+    pub reset_balls_on_goal: bool,
+    // Seconds before a player who just picked a power-up can pick another one
+    pub pickup_cooldown: f32,
+    pub input_priority: Vec<InputSource>,
+    // Point balls to score for an outright win in the point ball race
+    pub point_ball_target: usize,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            mode: GameMode::Versus,
+            opponent: Opponent::default(),
+            catch_assist: 0.,
+            competitive: false,
+            winning_score: WinningScore::default().0,
+            best_of: 1,
+            rally_bonus_every: 10,
+            rally_speed_up: 0.05,
+            paddle_aim: true,
+            smash_transfer: 0.1,
+            curve_shots: false,
+            points_per_hit: false,
+            serve_block_timeout: 3.,
+            serve_countdown: 3.,
+            serve_interval: None,
+            serve_ramp: None,
+            max_balls: 24,
+            msaa: MsaaSetting::default(),
+            display_mode: DisplayMode::default(),
+            ui_scale: 1.,
+            reduced_motion: false,
+            ai_difficulty: AiDifficulty::default(),
+            colorblind: false,
+            paddle_shapes: [PaddleShape::Flat; 2],
+            screen_shake: 1.,
+            shrink_per_goal: 0.,
+            shrink_floor: 0.5,
+            seed: None,
+            serve_seed: None,
+            serve_file: None,
+            key_bindings: KeyBindings::default(),
+            goal_pull: 0.,
+            ai_x_positioning: false,
+            ai_prediction: true,
+            attract_delay: 20.,
+            net_port: DEFAULT_NET_PORT,
+            join_address: format!("127.0.0.1:{DEFAULT_NET_PORT}"),
+            tournament_names: (1..=TOURNAMENT_MIN_PLAYERS)
+                .map(|i| format!("Player {i}"))
+                .collect(),
+            editing_name: None,
+            lives: 3,
+            match_duration: 120.,
+            tie_break: TieBreak::default(),
+            defensive_wall_streak: 0,
+            steady_ball_text: true,
+            power_ups: false,
+            max_power_ups: 3,
+            power_up_interval: 8.,
+            handicap_interval: 0.,
+            reset_balls_on_goal: false,
+            serve_warning: 0.4,
+            scoreboard_window: false,
+            ball_spin: false,
+            ball_trail: true,
+            game_speed: GameSpeed::default(),
+            golden_ball_chance: 0.,
+            point_ball_chance: 0.1,
+            background_color: None,
+            corner_goal_points: None,
+            corner_drill: false,
+            background_image: None,
+            arena: ArenaDefinition::BUILT_IN[0].to_string(),
+            pickup_cooldown: 5.,
+            point_ball_target: 3,
+            input_priority: InputSource::DEFAULT_PRIORITY.to_vec(),
+        }
+    }
+}
+
+impl GameConfig {
+    // Command line flags win over the saved settings the config starts from
+    pub fn with_args(mut config: Self) -> Self {
+        let mut args = std::env::args().skip(1).peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--catch-assist" => {
+                    if let Some(strength) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.catch_assist = strength.clamp(0., 1.);
+                    }
+                }
+                "--competitive" => config.competitive = true,
+                "--reduced-motion" => config.reduced_motion = true,
+                "--shrink-per-goal" => {
+                    if let Some(fraction) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.shrink_per_goal = fraction.clamp(0., 1.);
+                    }
+                }
+                "--shrink-floor" => {
+                    if let Some(floor) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.shrink_floor = floor.clamp(0.1, 1.);
+                    }
+                }
+                "--seed" => config.seed = args.next().and_then(|v| v.parse::<u64>().ok()),
+                "--serve-seed" => {
+                    config.serve_seed = args.next().and_then(|v| v.parse::<u64>().ok())
+                }
+                "--serve-file" => config.serve_file = args.next(),
+                "--goal-pull" => {
+                    if let Some(pull) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.goal_pull = pull.max(0.);
+                    }
+                }
+                "--ai-x-positioning" => config.ai_x_positioning = true,
+                "--no-ai-prediction" => config.ai_prediction = false,
+                "--port" => {
+                    if let Some(port) = args.next().and_then(|v| v.parse::<u16>().ok()) {
+                        config.net_port = port;
+                    }
+                }
+                "--join-address" => {
+                    if let Some(address) = args.next() {
+                        config.join_address = address;
+                    }
+                }
+                "--attract-delay" => {
+                    if let Some(seconds) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.attract_delay = seconds.max(0.);
+                    }
+                }
+                "--no-steady-ball-text" => config.steady_ball_text = false,
+                "--power-ups" => config.power_ups = true,
+                "--max-power-ups" => {
+                    if let Some(count) = args.next().and_then(|v| v.parse::<usize>().ok()) {
+                        config.max_power_ups = count;
+                    }
+                }
+                "--power-up-interval" => {
+                    if let Some(seconds) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.power_up_interval = seconds.max(0.);
+                    }
+                }
+                "--pickup-cooldown" => {
+                    if let Some(seconds) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.pickup_cooldown = seconds.max(0.);
+                    }
+                }
+                "--corner-drill" => config.corner_drill = true,
+                "--goal-zones" => {
+                    // The count can be left out too
+                    let points = args.peek().and_then(|v| v.parse::<usize>().ok());
+                    if points.is_some() {
+                        args.next();
+                    }
+                    config.corner_goal_points = Some(points.unwrap_or(2).max(1));
+                }
+                "--background-color" => {
+                    let value = args.next().unwrap_or_default();
+                    match Color::hex(&value) {
+                        Ok(color) => config.background_color = Some(color),
+                        Err(_) => eprintln!(
+                            "Invalid background color {value:?}, expected hex like 102030"
+                        ),
+                    }
+                }
+                "--background-image" => config.background_image = args.next(),
+                "--arena" => {
+                    if let Some(name) = args.next() {
+                        config.arena = name;
+                    }
+                }
+                "--golden-ball-chance" => {
+                    if let Some(chance) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.golden_ball_chance = chance.clamp(0., 1.);
+                    }
+                }
+                "--point-ball-chance" => {
+                    if let Some(chance) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.point_ball_chance = chance.clamp(0., 1.);
+                    }
+                }
+                "--game-speed" => {
+                    let value = args.next().unwrap_or_default();
+                    config.game_speed = GameSpeed::parse(&value).unwrap_or_else(|| {
+                        eprintln!("Invalid game speed {value:?}, using normal");
+                        GameSpeed::default()
+                    });
+                }
+                "--difficulty" => {
+                    let value = args.next().unwrap_or_default();
+                    config.ai_difficulty = AiDifficulty::parse(&value).unwrap_or_else(|| {
+                        eprintln!("Invalid difficulty {value:?}, using normal");
+                        AiDifficulty::default()
+                    });
+                }
+                "--colorblind" => config.colorblind = true,
+                "--paddle-shape" | "--p2-paddle-shape" => {
+                    let player = if arg == "--paddle-shape" { 0 } else { 1 };
+                    let value = args.next().unwrap_or_default();
+                    config.paddle_shapes[player] =
+                        PaddleShape::parse(&value).unwrap_or_else(|| {
+                            eprintln!("Invalid paddle shape {value:?}, using flat");
+                            PaddleShape::default()
+                        });
+                }
+                "--screen-shake" => {
+                    if let Some(intensity) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.screen_shake = intensity.clamp(0., 1.);
+                    }
+                }
+                "--ui-scale" => {
+                    if let Some(scale) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.ui_scale = scale.clamp(UI_SCALE_MIN, UI_SCALE_MAX);
+                    }
+                }
+                "--display-mode" => {
+                    let value = args.next().unwrap_or_default();
+                    config.display_mode = DisplayMode::parse(&value).unwrap_or_else(|| {
+                        eprintln!("Invalid display mode {value:?}, using windowed");
+                        DisplayMode::default()
+                    });
+                }
+                "--ball-spin" => config.ball_spin = true,
+                "--no-ball-trail" => config.ball_trail = false,
+                "--scoreboard-window" => config.scoreboard_window = true,
+                "--serve-warning" => {
+                    if let Some(seconds) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.serve_warning = seconds.max(0.);
+                    }
+                }
+                "--reset-balls-on-goal" => config.reset_balls_on_goal = true,
+                "--handicap-timer" => {
+                    if let Some(seconds) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.handicap_interval = seconds.max(0.);
+                    }
+                }
+                "--defensive-wall-streak" => {
+                    if let Some(hits) = args.next().and_then(|v| v.parse::<usize>().ok()) {
+                        config.defensive_wall_streak = hits;
+                    }
+                }
+                "--bind" => {
+                    let binding = args.next().unwrap_or_default();
+                    if let Err(error) = config.key_bindings.rebind(&binding) {
+                        eprintln!("Ignoring key binding: {error}");
+                    }
+                }
+                "--survival" => config.mode = GameMode::Survival,
+                "--lives" => {
+                    if let Some(lives) = args.next().and_then(|v| v.parse::<usize>().ok()) {
+                        config.lives = lives.max(1);
+                    }
+                }
+                "--hotseat" => config.opponent = Opponent::Human,
+                "--timed" => config.mode = GameMode::Timed,
+                "--breakout" => config.mode = GameMode::Breakout,
+                "--four-player" => config.mode = GameMode::FourPlayer,
+                "--coop" => config.mode = GameMode::Coop,
+                "--point-ball-race" => {
+                    config.mode = GameMode::PointBallRace;
+                    // The target can be left out, the next flag isn't taken for it then
+                    let target = args.peek().and_then(|v| v.parse::<usize>().ok());
+                    if let Some(target) = target {
+                        args.next();
+                        config.point_ball_target = target.max(1);
+                    }
+                }
+                "--match-duration" => {
+                    if let Some(seconds) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.match_duration = seconds.max(1.);
+                    }
+                }
+                "--input-priority" => {
+                    let value = args.next().unwrap_or_default();
+                    match InputSource::parse_priority(&value) {
+                        Ok(priority) => config.input_priority = priority,
+                        Err(error) => eprintln!("Ignoring input priority: {error}"),
+                    }
+                }
+                "--tie-break" => {
+                    let value = args.next().unwrap_or_default();
+                    config.tie_break = TieBreak::parse(&value).unwrap_or_else(|| {
+                        eprintln!("Invalid tie break {value:?}, using sudden death");
+                        TieBreak::default()
+                    });
+                }
+                "--rally-bonus-every" => {
+                    if let Some(hits) = args.next().and_then(|v| v.parse::<usize>().ok()) {
+                        config.rally_bonus_every = hits;
+                    }
+                }
+                "--serve-block-timeout" => {
+                    if let Some(seconds) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.serve_block_timeout = seconds.max(0.);
+                    }
+                }
+                "--serve-interval" => {
+                    if let Some(seconds) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.serve_interval = Some(seconds.max(0.01));
+                    }
+                }
+                "--serve-ramp" => {
+                    if let Some(seconds) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.serve_ramp = Some(seconds.max(0.));
+                    }
+                }
+                "--max-balls" => {
+                    if let Some(count) = args.next().and_then(|v| v.parse::<usize>().ok()) {
+                        config.max_balls = count.max(1);
+                    }
+                }
+                "--no-paddle-aim" => config.paddle_aim = false,
+                "--smash-transfer" => {
+                    if let Some(fraction) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.smash_transfer = fraction.max(0.);
+                    }
+                }
+                "--curve-shots" => config.curve_shots = true,
+                "--points-per-hit" => config.points_per_hit = true,
+                "--rally-speed-up" => {
+                    if let Some(fraction) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.rally_speed_up = fraction.max(0.);
+                    }
+                }
+                "--serve-countdown" => {
+                    if let Some(seconds) = args.next().and_then(|v| v.parse::<f32>().ok()) {
+                        config.serve_countdown = seconds.max(0.);
+                    }
+                }
+                "--msaa" => {
+                    let value = args.next().unwrap_or_default();
+                    config.msaa = MsaaSetting::parse(&value).unwrap_or_else(|| {
+                        eprintln!("Invalid MSAA setting {value:?}, expected off, 2 or 4");
+                        MsaaSetting::default()
+                    });
+                }
+                "--winning-score" => {
+                    if let Some(score) = args.next().and_then(|v| v.parse::<usize>().ok()) {
+                        config.winning_score = score.max(1);
+                    }
+                }
+                // Rounded up to an odd count so a match can't end level
+                "--best-of" => {
+                    if let Some(games) = args.next().and_then(|v| v.parse::<usize>().ok()) {
+                        config.best_of = games.max(1) | 1;
+                    }
+                }
+                _ => eprintln!("Ignoring unknown argument {arg}"),
+            }
+        }
+        config
+    }
+
+    // LAN matches are played against someone else, like competitive ones they get no assist
+    pub fn catch_assist_margin(&self, networked: bool) -> f32 {
+        if self.competitive || networked {
+            0.
+        } else {
+            self.catch_assist * CATCH_ASSIST_MAX_MARGIN
+        }
+    }
+}
+
+// Files passed around between players are untrusted, oversized ones are refused before reading
+pub fn read_shared_file(path: &str) -> Result<String, String> {
+    let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if size > MAX_SHARED_FILE_BYTES {
+        return Err(format!(
+            "{size} bytes is over the {MAX_SHARED_FILE_BYTES} byte limit"
+        ));
+    }
+    std::fs::read_to_string(path).map_err(|e| e.to_string())
+}
+
+// Menu choices kept between runs, written to the platform's config folder whenever one changes
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub input_priority: Vec<InputSource>,
+    pub audio: AudioSettings,
+    pub cosmetics: CosmeticsConfig,
+    pub ai_difficulty: AiDifficulty,
+    pub winning_score: usize,
+    pub best_of: usize,
+    pub colorblind: bool,
+    pub paddle_shapes: [PaddleShape; 2],
+    pub screen_shake: f32,
+    pub ball_trail: bool,
+    pub display_mode: DisplayMode,
+    pub ui_scale: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::from_config(
+            &GameConfig::default(),
+            &AudioSettings::default(),
+            &CosmeticsConfig::default(),
+        )
+    }
+}
+
+impl Settings {
+    pub fn from_config(
+        config: &GameConfig,
+        audio: &AudioSettings,
+        cosmetics: &CosmeticsConfig,
+    ) -> Self {
+        Self {
+            input_priority: config.input_priority.clone(),
+            audio: *audio,
+            cosmetics: *cosmetics,
+            ai_difficulty: config.ai_difficulty,
+            winning_score: config.winning_score,
+            best_of: config.best_of,
+            colorblind: config.colorblind,
+            paddle_shapes: config.paddle_shapes,
+            screen_shake: config.screen_shake,
+            ball_trail: config.ball_trail,
+            display_mode: config.display_mode,
+            ui_scale: config.ui_scale,
+        }
+    }
+
+    // Hand-edited files can hold anything, values that would break a match are put back in range
+    pub fn apply(&self, config: &mut GameConfig) {
+        // No input sources at all would leave the paddle unmovable
+        config.input_priority = if self.input_priority.is_empty() {
+            InputSource::DEFAULT_PRIORITY.to_vec()
+        } else {
+            self.input_priority.clone()
+        };
+        config.ai_difficulty = self.ai_difficulty;
+        config.winning_score = self.winning_score.max(1);
+        config.best_of = self.best_of.max(1) | 1;
+        config.colorblind = self.colorblind;
+        config.paddle_shapes = self.paddle_shapes;
+        config.screen_shake = self.screen_shake.clamp(0., 1.);
+        config.ball_trail = self.ball_trail;
+        config.display_mode = self.display_mode;
+        config.ui_scale = self.ui_scale.clamp(UI_SCALE_MIN, UI_SCALE_MAX);
+    }
+}
+
+// Every setting that changes how a match plays out, a replay carries them to play its match
+// again. Gameplay settings added later belong here too, older replays get their default
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct MatchRules {
+    pub mode: GameMode,
+    pub opponent: Opponent,
+    pub catch_assist: f32,
+    pub competitive: bool,
+    pub winning_score: usize,
+    pub best_of: usize,
+    pub rally_bonus_every: usize,
+    pub rally_speed_up: f32,
+    pub paddle_aim: bool,
+    pub smash_transfer: f32,
+    pub curve_shots: bool,
+    pub points_per_hit: bool,
+    pub serve_block_timeout: f32,
+    pub serve_countdown: f32,
+    pub serve_interval: Option<f32>,
+    pub serve_ramp: Option<f32>,
+    pub max_balls: usize,
+    pub ai_difficulty: AiDifficulty,
+    pub paddle_shapes: [PaddleShape; 2],
+    pub shrink_per_goal: f32,
+    pub shrink_floor: f32,
+    pub serve_seed: Option<u64>,
+    pub serve_file: Option<String>,
+    pub goal_pull: f32,
+    pub ai_x_positioning: bool,
+    pub ai_prediction: bool,
+    pub lives: usize,
+    pub match_duration: f32,
+    pub tie_break: TieBreak,
+    pub defensive_wall_streak: usize,
+    pub power_ups: bool,
+    pub max_power_ups: usize,
+    pub power_up_interval: f32,
+    pub handicap_interval: f32,
+    pub game_speed: GameSpeed,
+    pub corner_drill: bool,
+    pub corner_goal_points: Option<usize>,
+    pub arena: String,
+    pub golden_ball_chance: f32,
+    pub point_ball_chance: f32,
+    pub serve_warning: f32,
+    pub reset_balls_on_goal: bool,
+    pub pickup_cooldown: f32,
+    pub point_ball_target: usize,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self::from_config(&GameConfig::default())
+    }
+}
+
+impl MatchRules {
+    pub fn from_config(config: &GameConfig) -> Self {
+        Self {
+            mode: config.mode,
+            opponent: config.opponent,
+            catch_assist: config.catch_assist,
+            competitive: config.competitive,
+            winning_score: config.winning_score,
+            best_of: config.best_of,
+            rally_bonus_every: config.rally_bonus_every,
+            rally_speed_up: config.rally_speed_up,
+            paddle_aim: config.paddle_aim,
+            smash_transfer: config.smash_transfer,
+            curve_shots: config.curve_shots,
+            points_per_hit: config.points_per_hit,
+            serve_block_timeout: config.serve_block_timeout,
+            serve_countdown: config.serve_countdown,
+            serve_interval: config.serve_interval,
+            serve_ramp: config.serve_ramp,
+            max_balls: config.max_balls,
+            ai_difficulty: config.ai_difficulty,
+            paddle_shapes: config.paddle_shapes,
+            shrink_per_goal: config.shrink_per_goal,
+            shrink_floor: config.shrink_floor,
+            serve_seed: config.serve_seed,
+            serve_file: config.serve_file.clone(),
+            goal_pull: config.goal_pull,
+            ai_x_positioning: config.ai_x_positioning,
+            ai_prediction: config.ai_prediction,
+            lives: config.lives,
+            match_duration: config.match_duration,
+            tie_break: config.tie_break,
+            defensive_wall_streak: config.defensive_wall_streak,
+            power_ups: config.power_ups,
+            max_power_ups: config.max_power_ups,
+            power_up_interval: config.power_up_interval,
+            handicap_interval: config.handicap_interval,
+            game_speed: config.game_speed,
+            corner_drill: config.corner_drill,
+            corner_goal_points: config.corner_goal_points,
+            arena: config.arena.clone(),
+            golden_ball_chance: config.golden_ball_chance,
+            point_ball_chance: config.point_ball_chance,
+            serve_warning: config.serve_warning,
+            reset_balls_on_goal: config.reset_balls_on_goal,
+            pickup_cooldown: config.pickup_cooldown,
+            point_ball_target: config.point_ball_target,
+        }
+    }
+
+    // A match can't be played from these, clamping them would change the match that was recorded
+    pub fn validate(&self) -> Result<(), String> {
+        if self.winning_score == 0 {
+            return Err("the winning score is 0".to_string());
+        }
+        if self.best_of % 2 == 0 {
+            return Err(format!("best of {} can end level", self.best_of));
+        }
+        let values = [
+            self.catch_assist,
+            self.rally_speed_up,
+            self.smash_transfer,
+            self.serve_block_timeout,
+            self.serve_countdown,
+            self.shrink_per_goal,
+            self.shrink_floor,
+            self.goal_pull,
+            self.match_duration,
+            self.power_up_interval,
+            self.handicap_interval,
+            self.golden_ball_chance,
+            self.point_ball_chance,
+            self.serve_warning,
+            self.pickup_cooldown,
+        ];
+        let optional = self.serve_interval.into_iter().chain(self.serve_ramp);
+        if !values.into_iter().chain(optional).all(f32::is_finite) {
+            return Err("a setting is not a number".to_string());
+        }
+        Ok(())
+    }
+
+    // Shared files can hold anything, values that would break a match are put back in range like
+    // the command line flags do
+    pub fn apply(&self, config: &mut GameConfig) {
+        config.mode = self.mode;
+        config.opponent = self.opponent;
+        config.catch_assist = self.catch_assist.clamp(0., 1.);
+        config.competitive = self.competitive;
+        config.winning_score = self.winning_score.max(1);
+        config.best_of = self.best_of.max(1) | 1;
+        config.rally_bonus_every = self.rally_bonus_every;
+        config.rally_speed_up = self.rally_speed_up.max(0.);
+        config.paddle_aim = self.paddle_aim;
+        config.smash_transfer = self.smash_transfer.max(0.);
+        config.curve_shots = self.curve_shots;
+        config.points_per_hit = self.points_per_hit;
+        config.serve_block_timeout = self.serve_block_timeout.max(0.);
+        config.serve_countdown = self.serve_countdown.max(0.);
+        config.serve_interval = self.serve_interval.map(|seconds| seconds.max(0.01));
+        config.serve_ramp = self.serve_ramp.map(|seconds| seconds.max(0.));
+        config.max_balls = self.max_balls.max(1);
+        config.ai_difficulty = self.ai_difficulty;
+        config.paddle_shapes = self.paddle_shapes;
+        config.shrink_per_goal = self.shrink_per_goal.clamp(0., 1.);
+        config.shrink_floor = self.shrink_floor.clamp(0.1, 1.);
+        config.serve_seed = self.serve_seed;
+        config.serve_file = self.serve_file.clone();
+        config.goal_pull = self.goal_pull.max(0.);
+        config.ai_x_positioning = self.ai_x_positioning;
+        config.ai_prediction = self.ai_prediction;
+        config.lives = self.lives.max(1);
+        config.match_duration = self.match_duration.max(1.);
+        config.tie_break = self.tie_break;
+        config.defensive_wall_streak = self.defensive_wall_streak;
+        config.power_ups = self.power_ups;
+        config.max_power_ups = self.max_power_ups;
+        config.power_up_interval = self.power_up_interval.max(0.);
+        config.handicap_interval = self.handicap_interval.max(0.);
+        config.game_speed = self.game_speed;
+        config.corner_drill = self.corner_drill;
+        config.corner_goal_points = self.corner_goal_points.map(|points| points.max(1));
+        config.arena = self.arena.clone();
+        config.golden_ball_chance = self.golden_ball_chance.clamp(0., 1.);
+        config.point_ball_chance = self.point_ball_chance.clamp(0., 1.);
+        config.serve_warning = self.serve_warning.max(0.);
+        config.reset_balls_on_goal = self.reset_balls_on_goal;
+        config.pickup_cooldown = self.pickup_cooldown.max(0.);
+        config.point_ball_target = self.point_ball_target.max(1);
+    }
+}
+
+// Files kept between runs, None on the web or when the platform's usual environment variables are missing
+pub fn config_file_path(name: &str) -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).map(PathBuf::from);
+    let folder = if cfg!(target_os = "windows") {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|home| home.join(".config")))
+    };
+    Some(folder?.join("air_oki").join(name))
+}
+
+// A missing file is a first run, a broken one is reported and replaced on the next save
+pub fn load_config_file<T: DeserializeOwned + Default>(name: &str) -> T {
+    let Some(path) = config_file_path(name) else {
+        return T::default();
+    };
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return T::default(),
+        Err(error) => {
+            eprintln!("Could not read {}: {error}", path.display());
+            return T::default();
+        }
+    };
+    ron::from_str(&contents).unwrap_or_else(|error| {
+        eprintln!("Ignoring {}: {error}", path.display());
+        T::default()
+    })
+}
+
+pub fn save_config_file<T: Serialize>(name: &str, value: &T) -> Result<(), String> {
+    let path = config_file_path(name).ok_or("no config folder on this platform")?;
+    if let Some(folder) = path.parent() {
+        std::fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+    }
+    let contents = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|e| e.to_string())?;
+    std::fs::write(&path, contents).map_err(|e| e.to_string())
+}
@@ -0,0 +1,150 @@
+use crate::ball::{Ball, BallAssets, BallKind, BallSprite, TrailDot};
+use crate::paddle::Paddle;
+use crate::prelude::*;
+
+pub struct CosmeticsPlugin;
+
+impl Plugin for CosmeticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CosmeticsConfig>()
+            .add_systems(Update, reskin_returned_balls.in_set(HitSet::React));
+    }
+}
+
+// Looks picked per player under Options and kept with the settings. Nothing here changes play,
+// so replays and LAN matches leave them to whoever is watching
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct CosmeticsConfig {
+    pub paddle_skins: [PaddleSkin; 2],
+    pub ball_skins: [BallSkin; 2],
+}
+
+impl CosmeticsConfig {
+    pub fn paddle_skin(&self, paddle: &Paddle) -> PaddleSkin {
+        // The AI seats across the top and bottom aren't anyone's to dress up
+        if paddle.side.horizontal() {
+            PaddleSkin::Classic
+        } else {
+            self.paddle_skins[if paddle.first_player { 0 } else { 1 }]
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PaddleSkin {
+    #[default]
+    Classic,
+    Neon,
+    Pastel,
+    Mono,
+}
+
+impl PaddleSkin {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Classic => Self::Neon,
+            Self::Neon => Self::Pastel,
+            Self::Pastel => Self::Mono,
+            Self::Mono => Self::Classic,
+        }
+    }
+
+    // Each skin has a color per player, so both picking the same one still tells them apart.
+    // Colorblind mode only changes the classic colors, the others are picked knowingly
+    pub fn color(&self, paddle: &Paddle, colorblind: bool) -> Color {
+        match (self, paddle.first_player) {
+            // The colorblind colors come from the Okabe-Ito palette, the second player keeps
+            // their color on either side
+            (Self::Classic, _) => match (&paddle.side, paddle.first_player, colorblind) {
+                (Side::Left, true, true) => Color::rgb(0.9, 0.62, 0.),
+                (Side::Left, true, false) => Color::ORANGE,
+                (Side::Left | Side::Right, _, true) => Color::rgb(0., 0.45, 0.7),
+                (Side::Left | Side::Right, _, false) => Color::PURPLE,
+                (Side::Top, _, true) => Color::rgb(0., 0.62, 0.45),
+                (Side::Top, _, false) => Color::LIME_GREEN,
+                (_, _, true) => Color::rgb(0.8, 0.47, 0.65),
+                (_, _, false) => Color::CYAN,
+            },
+            (Self::Neon, true) => Color::rgb(1., 0.1, 0.6),
+            (Self::Neon, false) => Color::rgb(0.1, 1., 0.9),
+            (Self::Pastel, true) => Color::rgb(1., 0.75, 0.6),
+            (Self::Pastel, false) => Color::rgb(0.75, 0.7, 1.),
+            (Self::Mono, true) => Color::WHITE,
+            (Self::Mono, false) => Color::GRAY,
+        }
+    }
+}
+
+// Worn by every ball, so a ball turned back into a plain one gets its skin's color again
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BallSkin {
+    #[default]
+    Classic,
+    Ember,
+    Frost,
+    // A plain square, like the very first tennis games
+    Retro,
+}
+
+impl BallSkin {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Classic => Self::Ember,
+            Self::Ember => Self::Frost,
+            Self::Frost => Self::Retro,
+            Self::Retro => Self::Classic,
+        }
+    }
+
+    // The default handle is a plain white image
+    pub fn texture(&self, ball_assets: &BallAssets) -> Handle<Image> {
+        match self {
+            Self::Retro => Handle::default(),
+            _ => ball_assets.point_ball.clone(),
+        }
+    }
+
+    // Point and golden balls keep their colors whatever the skin, they mean something
+    pub fn color(&self, kind: BallKind) -> Color {
+        match (self, kind) {
+            (Self::Ember, BallKind::Normal) => Color::rgb(1., 0.45, 0.2),
+            (Self::Frost, BallKind::Normal) => Color::rgb(0.6, 0.9, 1.),
+            _ => kind.color(),
+        }
+    }
+}
+
+// Balls are served in the first player's skin and take on the skin of whoever returns them
+fn reskin_returned_balls(
+    mut paddle_hit: EventReader<PaddleHit>,
+    cosmetics: Res<CosmeticsConfig>,
+    ball_assets: Res<BallAssets>,
+    paddles: Query<&Paddle>,
+    mut balls: Query<(&Ball, &mut BallSkin, &Children)>,
+    mut sprites: Query<(&mut Sprite, &mut Handle<Image>), Or<(With<BallSprite>, With<TrailDot>)>>,
+) {
+    for hit in paddle_hit.iter() {
+        let Ok(paddle) = paddles.get(hit.paddle) else {
+            continue;
+        };
+        if paddle.side.horizontal() {
+            continue;
+        }
+        let skin = cosmetics.ball_skins[if paddle.first_player { 0 } else { 1 }];
+        let Ok((ball, mut ball_skin, children)) = balls.get_mut(hit.ball) else {
+            continue;
+        };
+        if *ball_skin == skin {
+            continue;
+        }
+        *ball_skin = skin;
+        // Trail dots keep their own fade
+        for child in children.iter() {
+            if let Ok((mut sprite, mut texture)) = sprites.get_mut(*child) {
+                sprite.color = skin.color(ball.kind).with_a(sprite.color.a());
+                *texture = skin.texture(&ball_assets);
+            }
+        }
+    }
+}
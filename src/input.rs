@@ -0,0 +1,698 @@
+use crate::config::{GameConfig, Opponent};
+use crate::paddle::{
+    steer_paddle, Charge, Paddle, PaddleBounds, PaddleController, MAX_PADDLE_TILT, PADDLE_SPEED,
+    PADDLE_TILT_SPEED, PADDLE_TILT_STEP, SMASH_CHARGE_TIME, SMASH_WINDOW,
+};
+use crate::prelude::*;
+use crate::replay::{ReplayPlayback, ReplayRecorder};
+
+// Keyboard, gamepad and touch controls for the local players' paddles
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActiveGamepad>()
+            .init_resource::<TouchScreen>()
+            .init_resource::<PaddleDash>()
+            .add_systems(OnExit(GameState::Menu), reset_paddle_dash)
+            .add_systems(
+                OnExit(GameState::GameOver),
+                (reset_paddle_dash, reset_smash_charges),
+            )
+            .add_systems(
+                FixedUpdate,
+                (move_player_paddle, move_hotseat_paddle).in_set(StepSet::MovePaddles),
+            )
+            .add_systems(
+                Update,
+                (
+                    track_gamepads,
+                    (start_paddle_dash, update_dash_hud)
+                        .chain()
+                        .in_set(SimulationSet),
+                    charge_smashes.in_set(HitSet::Detect),
+                    tilt_player_paddle.in_set(SimulationSet),
+                ),
+            );
+    }
+}
+
+// Where the first player's paddle input comes from, the first active source in the priority wins
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputSource {
+    Touch,
+    Gamepad,
+    Keyboard,
+    Mouse,
+}
+
+impl InputSource {
+    // A live finger or a pushed stick beats keys, and any of them beats a mouse left lying around
+    pub const DEFAULT_PRIORITY: [Self; 4] =
+        [Self::Touch, Self::Gamepad, Self::Keyboard, Self::Mouse];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "touch" => Some(Self::Touch),
+            "gamepad" => Some(Self::Gamepad),
+            "keyboard" => Some(Self::Keyboard),
+            "mouse" => Some(Self::Mouse),
+            _ => None,
+        }
+    }
+
+    // Name of a priority list for the menu, the default order reads every source
+    pub fn scheme_name(priority: &[Self]) -> &'static str {
+        match priority {
+            [Self::Mouse] => "Mouse",
+            [Self::Keyboard] => "Keyboard",
+            _ if priority == Self::DEFAULT_PRIORITY => "Auto",
+            _ => "Custom",
+        }
+    }
+
+    // Parses a comma separated list, sources left out are never read
+    pub fn parse_priority(value: &str) -> Result<Vec<Self>, String> {
+        let mut priority = Vec::new();
+        for name in value.split(',').map(str::trim) {
+            let source =
+                Self::parse(name).ok_or_else(|| format!("unknown input source {name:?}"))?;
+            if !priority.contains(&source) {
+                priority.push(source);
+            }
+        }
+        Ok(priority)
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct KeyBindings {
+    pub defensive_wall: KeyCode,
+    pub help: KeyCode,
+    pub restart: KeyCode,
+    pub ball_inspector: KeyCode,
+    pub ai_target: KeyCode,
+    pub pause: KeyCode,
+    pub save_replay: KeyCode,
+    pub display_mode: KeyCode,
+    // Against the AI the second player's keys move the first player's paddle too
+    pub move_up: KeyCode,
+    pub move_down: KeyCode,
+    pub move_left: KeyCode,
+    pub move_right: KeyCode,
+    pub tilt_left: KeyCode,
+    pub tilt_right: KeyCode,
+    pub p2_up: KeyCode,
+    pub p2_down: KeyCode,
+    pub p2_left: KeyCode,
+    pub p2_right: KeyCode,
+    pub p2_defensive_wall: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            defensive_wall: KeyCode::Space,
+            help: KeyCode::F1,
+            restart: KeyCode::Return,
+            ball_inspector: KeyCode::F7,
+            ai_target: KeyCode::F8,
+            pause: KeyCode::Escape,
+            save_replay: KeyCode::F5,
+            display_mode: KeyCode::F11,
+            move_up: KeyCode::W,
+            move_down: KeyCode::S,
+            move_left: KeyCode::A,
+            move_right: KeyCode::D,
+            tilt_left: KeyCode::Q,
+            tilt_right: KeyCode::E,
+            p2_up: KeyCode::Up,
+            p2_down: KeyCode::Down,
+            p2_left: KeyCode::Left,
+            p2_right: KeyCode::Right,
+            p2_defensive_wall: KeyCode::RShift,
+        }
+    }
+}
+
+impl KeyBindings {
+    // Rebinds from an "action=key" pair, e.g. "help=H"
+    pub fn rebind(&mut self, binding: &str) -> Result<(), String> {
+        let (action, key) = binding
+            .split_once('=')
+            .ok_or_else(|| format!("expected action=key, got {binding}"))?;
+        let key = key_from_name(key).ok_or_else(|| format!("unknown key {key}"))?;
+        match action {
+            "defensive_wall" => self.defensive_wall = key,
+            "help" => self.help = key,
+            "restart" => self.restart = key,
+            "ball_inspector" => self.ball_inspector = key,
+            "ai_target" => self.ai_target = key,
+            "pause" => self.pause = key,
+            "save_replay" => self.save_replay = key,
+            "display_mode" => self.display_mode = key,
+            "move_up" => self.move_up = key,
+            "move_down" => self.move_down = key,
+            "move_left" => self.move_left = key,
+            "move_right" => self.move_right = key,
+            "tilt_left" => self.tilt_left = key,
+            "tilt_right" => self.tilt_right = key,
+            "p2_up" => self.p2_up = key,
+            "p2_down" => self.p2_down = key,
+            "p2_left" => self.p2_left = key,
+            "p2_right" => self.p2_right = key,
+            "p2_defensive_wall" => self.p2_defensive_wall = key,
+            _ => return Err(format!("unknown action {action}")),
+        }
+        Ok(())
+    }
+
+    pub fn first_player_moves(&self) -> [KeyCode; 4] {
+        [
+            self.move_up,
+            self.move_down,
+            self.move_left,
+            self.move_right,
+        ]
+    }
+
+    pub fn second_player_moves(&self) -> [KeyCode; 4] {
+        [self.p2_up, self.p2_down, self.p2_left, self.p2_right]
+    }
+
+    pub fn help_lines(&self) -> Vec<(String, String)> {
+        vec![
+            (
+                "Move paddle".to_string(),
+                format!(
+                    "{}{}{}{}, or hold left mouse button",
+                    key_name(self.move_up),
+                    key_name(self.move_left),
+                    key_name(self.move_down),
+                    key_name(self.move_right)
+                ),
+            ),
+            (
+                "Player 2 (hotseat)".to_string(),
+                format!(
+                    "{} {} {} {} to move, {} for the wall",
+                    key_name(self.p2_up),
+                    key_name(self.p2_left),
+                    key_name(self.p2_down),
+                    key_name(self.p2_right),
+                    key_name(self.p2_defensive_wall)
+                ),
+            ),
+            ("Defensive wall".to_string(), key_name(self.defensive_wall)),
+            (
+                "Tilt paddle".to_string(),
+                format!(
+                    "{} {}, or the mouse wheel",
+                    key_name(self.tilt_left),
+                    key_name(self.tilt_right)
+                ),
+            ),
+            (
+                "Dash toward the cursor".to_string(),
+                "Double click".to_string(),
+            ),
+            (
+                "Charged smash".to_string(),
+                "Hold right mouse button, let go as the ball arrives".to_string(),
+            ),
+            (
+                "Gamepad".to_string(),
+                "Left stick moves, South raises the wall, Start pauses".to_string(),
+            ),
+            ("Pause".to_string(), key_name(self.pause)),
+            ("Controls help".to_string(), key_name(self.help)),
+            ("Play again".to_string(), key_name(self.restart)),
+            ("Ball inspector".to_string(), key_name(self.ball_inspector)),
+            ("AI target".to_string(), key_name(self.ai_target)),
+            ("Save replay".to_string(), key_name(self.save_replay)),
+            (
+                "Windowed, borderless, fullscreen".to_string(),
+                key_name(self.display_mode),
+            ),
+        ]
+    }
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    match key {
+        KeyCode::Return => "Enter".to_string(),
+        KeyCode::Back => "Backspace".to_string(),
+        key => format!("{key:?}"),
+    }
+}
+
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    let name = name.to_ascii_uppercase();
+    let key = match name.as_str() {
+        "A" => KeyCode::A,
+        "B" => KeyCode::B,
+        "C" => KeyCode::C,
+        "D" => KeyCode::D,
+        "E" => KeyCode::E,
+        "F" => KeyCode::F,
+        "G" => KeyCode::G,
+        "H" => KeyCode::H,
+        "I" => KeyCode::I,
+        "J" => KeyCode::J,
+        "K" => KeyCode::K,
+        "L" => KeyCode::L,
+        "M" => KeyCode::M,
+        "N" => KeyCode::N,
+        "O" => KeyCode::O,
+        "P" => KeyCode::P,
+        "Q" => KeyCode::Q,
+        "R" => KeyCode::R,
+        "S" => KeyCode::S,
+        "T" => KeyCode::T,
+        "U" => KeyCode::U,
+        "V" => KeyCode::V,
+        "W" => KeyCode::W,
+        "X" => KeyCode::X,
+        "Y" => KeyCode::Y,
+        "Z" => KeyCode::Z,
+        "0" => KeyCode::Key0,
+        "1" => KeyCode::Key1,
+        "2" => KeyCode::Key2,
+        "3" => KeyCode::Key3,
+        "4" => KeyCode::Key4,
+        "5" => KeyCode::Key5,
+        "6" => KeyCode::Key6,
+        "7" => KeyCode::Key7,
+        "8" => KeyCode::Key8,
+        "9" => KeyCode::Key9,
+        "F1" => KeyCode::F1,
+        "F2" => KeyCode::F2,
+        "F3" => KeyCode::F3,
+        "F4" => KeyCode::F4,
+        "F5" => KeyCode::F5,
+        "F6" => KeyCode::F6,
+        "F7" => KeyCode::F7,
+        "F8" => KeyCode::F8,
+        "F9" => KeyCode::F9,
+        "F10" => KeyCode::F10,
+        "F11" => KeyCode::F11,
+        "F12" => KeyCode::F12,
+        "ESCAPE" | "ESC" => KeyCode::Escape,
+        "ENTER" | "RETURN" => KeyCode::Return,
+        "SPACE" => KeyCode::Space,
+        "TAB" => KeyCode::Tab,
+        "BACKSPACE" => KeyCode::Back,
+        "UP" => KeyCode::Up,
+        "DOWN" => KeyCode::Down,
+        "LEFT" => KeyCode::Left,
+        "RIGHT" => KeyCode::Right,
+        "LSHIFT" => KeyCode::ShiftLeft,
+        "RSHIFT" => KeyCode::ShiftRight,
+        _ => return None,
+    };
+    Some(key)
+}
+
+// Set by the first touch, there's no way to ask whether the device has a touchscreen before that
+#[derive(Resource, Default)]
+pub struct TouchScreen(pub bool);
+
+// The pad driving the first player, the most recently connected one still plugged in
+#[derive(Resource, Default)]
+pub struct ActiveGamepad(pub Option<Gamepad>);
+
+impl ActiveGamepad {
+    pub fn just_pressed(
+        &self,
+        buttons: &Input<GamepadButton>,
+        button_type: GamepadButtonType,
+    ) -> bool {
+        self.0
+            .is_some_and(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, button_type)))
+    }
+}
+
+fn track_gamepads(
+    mut events: EventReader<GamepadEvent>,
+    gamepads: Res<Gamepads>,
+    mut active: ResMut<ActiveGamepad>,
+) {
+    for event in events.iter() {
+        let GamepadEvent::Connection(connection) = event else {
+            continue;
+        };
+        if connection.connected() {
+            info!("Gamepad {:?} connected", connection.gamepad);
+            active.0 = Some(connection.gamepad);
+        } else if active.0 == Some(connection.gamepad) {
+            // Another pad still plugged in takes over, otherwise the other inputs do
+            active.0 = gamepads
+                .iter()
+                .find(|gamepad| *gamepad != connection.gamepad);
+            info!(
+                "Gamepad {:?} disconnected, now using {:?}",
+                connection.gamepad, active.0
+            );
+        }
+    }
+}
+
+// Below this the stick is considered resting
+const GAMEPAD_DEADZONE: f32 = 0.2;
+
+// A second click this soon after the first dashes the first player's paddle toward the cursor
+const DASH_DOUBLE_CLICK: f32 = 0.3;
+const DASH_DURATION: f32 = 0.15;
+const DASH_SPEED_SCALE: f32 = 3.;
+const DASH_COOLDOWN: f32 = 3.;
+
+// The first player's dash, seconds left of it and until the next one can start
+#[derive(Resource, Default)]
+pub struct PaddleDash {
+    // Elapsed seconds of the last click that didn't start a dash
+    pub last_click: Option<f32>,
+    pub remaining: f32,
+    pub cooldown: f32,
+}
+
+impl PaddleDash {
+    pub fn active(&self) -> bool {
+        self.remaining > 0.
+    }
+
+    pub fn speed(&self) -> f32 {
+        if self.active() {
+            PADDLE_SPEED * DASH_SPEED_SCALE
+        } else {
+            PADDLE_SPEED
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct DashHud;
+
+fn reset_paddle_dash(mut dash: ResMut<PaddleDash>) {
+    *dash = PaddleDash::default();
+}
+
+fn start_paddle_dash(
+    time: Res<Time>,
+    buttons: Res<Input<MouseButton>>,
+    config: Res<GameConfig>,
+    mut dash: ResMut<PaddleDash>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    let delta_seconds = time.delta_seconds();
+    dash.remaining = (dash.remaining - delta_seconds).max(0.);
+    dash.cooldown = (dash.cooldown - delta_seconds).max(0.);
+    let started = match playback.as_ref().and_then(|playback| playback.frame()) {
+        Some(frame) => frame.dash,
+        None if !config.input_priority.contains(&InputSource::Mouse) => false,
+        None if !buttons.just_pressed(MouseButton::Left) => false,
+        None => {
+            let now = time.elapsed_seconds();
+            let double_click = dash
+                .last_click
+                .is_some_and(|last| now - last <= DASH_DOUBLE_CLICK);
+            dash.last_click = Some(now);
+            double_click && dash.cooldown <= 0.
+        }
+    };
+    if let Some(mut recorder) = recorder {
+        recorder.frame.dash = started;
+    }
+    if started {
+        dash.last_click = None;
+        dash.remaining = DASH_DURATION;
+        dash.cooldown = DASH_COOLDOWN;
+    }
+}
+
+fn reset_smash_charges(mut charges: Query<&mut Charge>) {
+    for mut charge in charges.iter_mut() {
+        *charge = Charge::default();
+    }
+}
+
+fn charge_smashes(
+    time: Res<Time>,
+    buttons: Res<Input<MouseButton>>,
+    config: Res<GameConfig>,
+    mut charges: Query<&mut Charge>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    let held = match playback.as_ref().and_then(|playback| playback.frame()) {
+        Some(frame) => frame.charging,
+        None => {
+            config.input_priority.contains(&InputSource::Mouse)
+                && buttons.pressed(MouseButton::Right)
+        }
+    };
+    if let Some(mut recorder) = recorder {
+        recorder.frame.charging = held;
+    }
+    let delta_seconds = time.delta_seconds();
+    for mut charge in charges.iter_mut() {
+        charge.window = (charge.window - delta_seconds).max(0.);
+        if held {
+            charge.level = (charge.level + delta_seconds / SMASH_CHARGE_TIME).min(1.);
+        } else if charge.level > 0. {
+            charge.released = charge.level;
+            charge.window = SMASH_WINDOW;
+            charge.level = 0.;
+        }
+    }
+}
+
+// Turns the first player's paddle, the keys at a steady rate and the wheel a notch at a time
+fn tilt_player_paddle(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut wheel: EventReader<MouseWheel>,
+    config: Res<GameConfig>,
+    mut paddles: Query<(&mut Rotation, &Paddle, &PaddleController)>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    let notches: f32 = wheel.iter().map(|event| event.y.signum()).sum();
+    for (mut rotation, paddle, controller) in paddles.iter_mut() {
+        if !paddle.first_player {
+            continue;
+        }
+        let tilt = match controller {
+            PaddleController::Replay => {
+                let Some(frame) = playback.as_ref().and_then(|playback| playback.frame()) else {
+                    continue;
+                };
+                frame.tilt
+            }
+            PaddleController::Player => {
+                let mut turn = 0.;
+                if config.input_priority.contains(&InputSource::Keyboard) {
+                    // Counterclockwise is positive
+                    let (left, right) = (
+                        keys.pressed(bindings.tilt_left),
+                        keys.pressed(bindings.tilt_right),
+                    );
+                    let direction = match (left, right) {
+                        (true, false) => 1.,
+                        (false, true) => -1.,
+                        _ => 0.,
+                    };
+                    turn += direction * PADDLE_TILT_SPEED * time.delta_seconds();
+                }
+                if config.input_priority.contains(&InputSource::Mouse) {
+                    turn += notches * PADDLE_TILT_STEP;
+                }
+                (rotation.as_radians() + turn).clamp(-MAX_PADDLE_TILT, MAX_PADDLE_TILT)
+            }
+            _ => continue,
+        };
+        if let Some(recorder) = recorder.as_deref_mut() {
+            recorder.frame.tilt = tilt;
+        }
+        *rotation = Rotation::from_radians(tilt);
+    }
+}
+
+fn update_dash_hud(
+    config: Res<GameConfig>,
+    dash: Res<PaddleDash>,
+    paddles: Query<(&Paddle, &PaddleController)>,
+    mut hud: Query<&mut Text, With<DashHud>>,
+) {
+    let Ok(mut text) = hud.get_single_mut() else {
+        return;
+    };
+    // Only a mouse player, or the replay of one, has a dash to show
+    let dashing_paddle = paddles.iter().any(|(paddle, controller)| {
+        paddle.first_player
+            && matches!(
+                controller,
+                PaddleController::Player | PaddleController::Replay
+            )
+    });
+    let mouse = config.input_priority.contains(&InputSource::Mouse);
+    text.sections[0].value = if !dashing_paddle || !mouse {
+        String::new()
+    } else if dash.cooldown > 0. {
+        format!("Dash {:.1}s", dash.cooldown)
+    } else {
+        "Dash ready (double click)".to_string()
+    };
+}
+
+// Everything the first player can steer with, shared by the local paddle and a LAN client
+#[derive(SystemParam)]
+pub struct PlayerInput<'w, 's> {
+    pub time: Res<'w, Time>,
+    pub fixed_time: Res<'w, FixedTime>,
+    pub q_windows: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    pub buttons: Res<'w, Input<MouseButton>>,
+    pub keys: Res<'w, Input<KeyCode>>,
+    pub bindings: Res<'w, KeyBindings>,
+    pub touches: Res<'w, Touches>,
+    pub gamepad: Res<'w, ActiveGamepad>,
+    pub axes: Res<'w, Axis<GamepadAxis>>,
+    pub camera: Query<'w, 's, (&'static GlobalTransform, &'static Camera)>,
+    pub config: Res<'w, GameConfig>,
+}
+
+impl PlayerInput<'_, '_> {
+    pub fn to_world(&self, screen_position: Vec2) -> Option<Vec2> {
+        let (camera_transform, camera) = self.camera.iter().next()?;
+        camera.viewport_to_world_2d(camera_transform, screen_position)
+    }
+
+    // The mouse pointer on the court, button held or not
+    pub fn cursor(&self) -> Option<Vec2> {
+        self.q_windows
+            .single()
+            .cursor_position()
+            .and_then(|position| self.to_world(position))
+    }
+
+    // Where the paddle at this position should head, None when no input source is in use
+    pub fn target(&self, paddle_position: Vec2) -> Option<Vec2> {
+        // Relative inputs aim one step of full speed ahead of the paddle
+        let step = PADDLE_SPEED * self.fixed_time.period.as_secs_f32();
+        self.config
+            .input_priority
+            .iter()
+            .find_map(|source| match source {
+                InputSource::Touch => self
+                    .touches
+                    .iter()
+                    .next()
+                    .and_then(|touch| self.to_world(touch.position())),
+                InputSource::Gamepad => self.gamepad.0.and_then(|gamepad| {
+                    let stick = Vec2::new(
+                        self.axes
+                            .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))?,
+                        self.axes
+                            .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))?,
+                    );
+                    (stick.length() > GAMEPAD_DEADZONE)
+                        .then(|| paddle_position + stick.clamp_length_max(1.) * step)
+                }),
+                InputSource::Keyboard => {
+                    let mut direction =
+                        key_direction(&self.keys, self.bindings.first_player_moves());
+                    if self.config.opponent == Opponent::Ai {
+                        direction += key_direction(&self.keys, self.bindings.second_player_moves());
+                    }
+                    (direction != Vec2::ZERO)
+                        .then(|| paddle_position + direction.normalize() * step)
+                }
+                InputSource::Mouse => {
+                    if !self.buttons.pressed(MouseButton::Left) {
+                        return None;
+                    }
+                    self.cursor()
+                }
+            })
+    }
+}
+
+fn move_player_paddle(
+    input: PlayerInput,
+    dash: Res<PaddleDash>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    mut paddles: Query<(
+        &mut LinearVelocity,
+        &mut Position,
+        &PaddleBounds,
+        &Paddle,
+        &PaddleController,
+    )>,
+) {
+    for (mut velocity, mut paddle_position, bounds, paddle, controller) in paddles.iter_mut() {
+        if *controller != PaddleController::Player {
+            continue;
+        }
+        // A dash heads for the cursor whichever input source is steering
+        let target = if dash.active() {
+            input.cursor().or_else(|| input.target(paddle_position.0))
+        } else {
+            input.target(paddle_position.0)
+        };
+        ReplayRecorder::record_target(&mut recorder, paddle, target);
+        let Some(position) = target else {
+            *velocity = LinearVelocity(Vec2::ZERO);
+            continue;
+        };
+        steer_paddle(
+            bounds,
+            position,
+            dash.speed(),
+            input.fixed_time.period.as_secs_f32(),
+            &mut paddle_position.0,
+            &mut velocity.0,
+        );
+    }
+}
+
+fn move_hotseat_paddle(
+    fixed_time: Res<FixedTime>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    mut paddles: Query<(
+        &mut LinearVelocity,
+        &mut Position,
+        &PaddleBounds,
+        &Paddle,
+        &PaddleController,
+    )>,
+) {
+    for (mut velocity, mut paddle_position, bounds, paddle, controller) in paddles.iter_mut() {
+        if *controller != PaddleController::HotseatPlayer {
+            continue;
+        }
+        let direction = key_direction(&keys, bindings.second_player_moves()).normalize_or_zero();
+        let delta_seconds = fixed_time.period.as_secs_f32();
+        let target = paddle_position.0 + direction * PADDLE_SPEED * delta_seconds;
+        ReplayRecorder::record_target(&mut recorder, paddle, Some(target));
+        steer_paddle(
+            bounds,
+            target,
+            PADDLE_SPEED,
+            delta_seconds,
+            &mut paddle_position.0,
+            &mut velocity.0,
+        );
+    }
+}
+
+fn key_direction(keys: &Input<KeyCode>, [up, down, left, right]: [KeyCode; 4]) -> Vec2 {
+    let axis = |negative, positive| match (keys.pressed(negative), keys.pressed(positive)) {
+        (false, true) => 1.,
+        (true, false) => -1.,
+        _ => 0.,
+    };
+    Vec2::new(axis(left, right), axis(down, up))
+}
@@ -0,0 +1,153 @@
+use crate::arena::ArenaAssets;
+use crate::audio::{AudioAssets, MusicAssets};
+use crate::ball::BallAssets;
+use crate::menu::MENU_BUTTON_COLOR;
+use crate::prelude::*;
+
+// Holds the game on a progress bar until everything setup asked the asset server for is in, so
+// nothing is drawn untextured or plays silent
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Loading), spawn_loading_screen)
+            .add_systems(OnExit(GameState::Loading), despawn_loading_screen)
+            .add_systems(Update, track_loading.run_if(in_state(GameState::Loading)));
+    }
+}
+
+#[derive(Component)]
+struct LoadingScreen;
+
+// Filled to the share of assets done loading
+#[derive(Component)]
+struct LoadingBar;
+
+fn spawn_loading_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.),
+                    ..default()
+                },
+                ..default()
+            },
+            LoadingScreen,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                "Loading",
+                TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+            builder
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(300.),
+                        height: Val::Px(16.),
+                        ..default()
+                    },
+                    background_color: MENU_BUTTON_COLOR.into(),
+                    ..default()
+                })
+                .with_children(|builder| {
+                    builder.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.),
+                                height: Val::Percent(100.),
+                                ..default()
+                            },
+                            background_color: Color::WHITE.into(),
+                            ..default()
+                        },
+                        LoadingBar,
+                    ));
+                });
+        });
+}
+
+fn track_loading(
+    assets: Res<AssetServer>,
+    ball_assets: Res<BallAssets>,
+    arena_assets: Res<ArenaAssets>,
+    audio_assets: Res<AudioAssets>,
+    music_assets: Res<MusicAssets>,
+    // The background image, the only sprite there is before the menu
+    sprites: Query<&Handle<Image>, With<Sprite>>,
+    mut bars: Query<&mut Style, With<LoadingBar>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let sounds = audio_assets
+        .paddle_hits
+        .iter()
+        .chain(&audio_assets.wall_bounces)
+        .chain([
+            &audio_assets.serve_charge,
+            &audio_assets.golden_ball,
+            &audio_assets.goal,
+            &audio_assets.menu_click,
+            &music_assets.menu,
+            &music_assets.gameplay,
+        ])
+        .map(|handle| handle.id());
+    let handles = arena_assets
+        .0
+        .iter()
+        .map(|(_, handle)| handle.id())
+        .chain(sounds)
+        .chain(
+            sprites
+                .iter()
+                .chain([&ball_assets.point_ball])
+                .map(|handle| handle.id()),
+        )
+        // Handles that don't come from a file, like a sprite's default texture, never load
+        .filter(|handle| assets.get_handle_path(*handle).is_some())
+        .collect::<Vec<_>>();
+
+    // A missing file counts as done, the game goes on without that sound or texture as before
+    let done = handles
+        .iter()
+        .filter(|handle| {
+            matches!(
+                assets.get_load_state(**handle),
+                LoadState::Loaded | LoadState::Failed
+            )
+        })
+        .count();
+    for mut style in bars.iter_mut() {
+        style.width = Val::Percent(done as f32 / handles.len().max(1) as f32 * 100.);
+    }
+    if done == handles.len() {
+        // The game ships without its sounds and music, say which files it went without
+        for handle in &handles {
+            if assets.get_load_state(*handle) != LoadState::Failed {
+                continue;
+            }
+            if let Some(path) = assets.get_handle_path(*handle) {
+                warn!(
+                    "Could not load {}, going on without it",
+                    path.path().display()
+                );
+            }
+        }
+        next_state.set(GameState::Menu);
+    }
+}
+
+fn despawn_loading_screen(mut commands: Commands, screens: Query<Entity, With<LoadingScreen>>) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
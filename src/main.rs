@@ -1,565 +1,707 @@
-use std::collections::VecDeque;
-use std::f32::consts::PI;
-use std::time::Duration;
-
-use bevy::math::Vec3Swizzles;
-use bevy::prelude::*;
-use bevy::time::Stopwatch;
-use bevy::window::{PrimaryWindow, WindowResolution};
-use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
-
-use bevy_xpbd_2d::prelude::*;
-
-const WINDOW_SIZE: Vec2 = Vec2 { x: 1280., y: 720. };
-
-const PADDLE_SIZE: Vec2 = Vec2 { x: 15., y: 60. };
-
-const P1_LEFT_BOUND: f32 = -(-PADDLE_SIZE.x + WINDOW_SIZE.x * 0.5);
-const P1_RIGHT_BOUND: f32 = -(PADDLE_SIZE.x + WINDOW_SIZE.x * 0.25);
-const P1_TOP_BOUND: f32 = PADDLE_SIZE.y + WINDOW_SIZE.y * 0.5;
-const P1_BOTTOM_BOUND: f32 = -(PADDLE_SIZE.y + WINDOW_SIZE.y * 0.5);
-const LEFT_WALL: f32 = -(WINDOW_SIZE.x * 0.5);
-const RIGHT_WALL: f32 = -(WINDOW_SIZE.x * 0.25);
-const TOP_WALL: f32 = WINDOW_SIZE.y * 0.5;
-const BOTTOM_WALL: f32 = -(WINDOW_SIZE.y * 0.5);
-
-const BALL_RADIUS: f32 = 15.;
-
-const INITIAL_FORCE: f32 = 20000000.;
-const PADDLE_SPEED: f32 = 5000.;
-const PADDLE_SPEED_AI: f32 = 500.;
-
-#[derive(Resource, Default)]
-struct Score {
-    first_player: usize,
-    second_player: usize,
-}
-
-#[derive(Component, Debug)]
-struct Paddle {
-    first_player: bool,
-    side: Side,
-}
-
-#[derive(Component)]
-struct Goal {
-    first_player: bool,
-    side: Side,
-}
-
-#[derive(Component)]
-struct Ball {
-    points: usize,
-}
+// Bevy systems take every resource and query they use as a parameter, and spell out the
+// components each query reads in its type. Many systems go past clippy's seven parameters and
+// its type complexity limit that way, Bevy's own limit of sixteen parameters still holds
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+mod ai;
+mod arena;
+mod audio;
+mod ball;
+mod config;
+mod cosmetics;
+mod input;
+mod loading;
+mod menu;
+mod net;
+mod paddle;
+mod power_ups;
+mod prelude;
+mod replay;
+mod score;
+mod ui;
+
+#[cfg(test)]
+mod tests;
 
-impl Ball {
-    fn get_radius(&self) -> f32 {
-        if self.points > 0 {
-            BALL_RADIUS * 0.85
-        } else {
-            BALL_RADIUS
-        }
-    }
-}
-
-#[derive(Component)]
-struct BallTextMarker;
-#[derive(Component)]
-struct Player1ScoreMarker;
-#[derive(Component)]
-struct Player2ScoreMarker;
-
-#[derive(Component)]
-struct DelayedExternalForce(pub ExternalForce);
-
-
-#[derive(Resource, Default)]
-struct PointBallCount(u8);
-
-#[derive(Resource)]
-struct BallAssets {
-    point_ball: Handle<Image>,
-}
-
-#[derive(PartialEq, Eq, Clone, Debug)]
-enum Side {
-    Random,
-    Left,
-    Right,
-}
-
-impl Side {
-    fn opposite(&self) -> Self {
-        match self {
-            Self::Left => Self::Right,
-            Self::Right => Self::Left,
-            _ => Self::Random,
-        }
-    }
-}
+use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 
-#[derive(PhysicsLayer)]
-enum Layer {
-    Wall,
-    Net,
-    Paddle,
-    Ball,
-}
+use crate::ai::AiPlugin;
+use crate::arena::{
+    spawn_arena, spawn_bricks, spawn_obstacles, ArenaAssets, ArenaDefinition, ArenaPlugin, Court,
+};
+use crate::audio::{AudioAssets, MusicAssets, MusicPlugin, SfxPlugin, SfxRng, SFX_VARIANTS};
+use crate::ball::{
+    BallAssets, BallInspectorMarker, BallPlugin, BallSpawnConfig, NextServe, PointBallCount,
+    RallyCounter, ServeCountdown, ServeCountdownText, ServeQueue,
+};
+use crate::config::{load_config_file, GameConfig, Settings, SETTINGS_FILE};
+use crate::cosmetics::{CosmeticsConfig, CosmeticsPlugin};
+use crate::input::{ControlsPlugin, DashHud, TouchScreen};
+use crate::loading::LoadingPlugin;
+use crate::menu::{MenuPlugin, PauseButton, MENU_BUTTON_COLOR, TOUCH_BUTTON_SIZE};
+use crate::net::{NetClient, NetHost, NetworkPlugin, SpectatorHud};
+use crate::paddle::{PaddleController, PaddlePlugin, PaddleShape};
+use crate::power_ups::{
+    DefensiveWallCharges, DefensiveWallHud, HandicapTimer, PaddleBuffHud, PowerUpCooldowns,
+    PowerUpPlugin,
+};
+use crate::prelude::*;
+use crate::replay::{Replay, ReplayPlayback, ReplayPlugin, ReplayRecorder};
+use crate::score::{
+    GamesWon, GamesWonMarker, GoalCelebration, MatchStats, MatchTimer, Player1ScoreMarker,
+    Player2ScoreMarker, PlayerStats, Score, ScorePlugin, SeatScoreText, SurvivalRun, Tournament,
+    TournamentMatchText, WinningScore, WinningScoreMarker, STATS_FILE,
+};
+use crate::ui::{spawn_scoreboard_window, HudPlugin};
 
 fn main() {
     let mut app = App::new();
+    let saved_settings: Settings = load_config_file(SETTINGS_FILE);
+    let mut config = GameConfig::default();
+    saved_settings.apply(&mut config);
+    let config = GameConfig::with_args(config);
+    // Compared against on every change, so flags given for one run are only saved along with a menu change
+    let settings = Settings::from_config(&config, &saved_settings.audio, &saved_settings.cosmetics);
 
     #[cfg(feature = "fps")]
-    app.add_plugins((LogDiagnosticsPlugin::default(), FrameTimeDiagnosticsPlugin::default()));
+    app.add_plugins((
+        LogDiagnosticsPlugin::default(),
+        FrameTimeDiagnosticsPlugin::default(),
+    ));
 
-    app
-        .add_plugins((
-            DefaultPlugins.set(WindowPlugin {
-                primary_window: Some(Window {
-                    resolution: WindowResolution::new(WINDOW_SIZE.x, WINDOW_SIZE.y),
-                    ..default()
-                }),
+    app.add_plugins((
+        DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                resolution: WindowResolution::new(WINDOW_SIZE.x, WINDOW_SIZE.y),
+                mode: config.display_mode.into(),
+                // Only used on the web, where the page decides how big the game is
+                fit_canvas_to_parent: true,
                 ..default()
             }),
-            PhysicsPlugins::default(),
-        ))
-        .insert_resource(Gravity::ZERO)
-        .add_systems(Startup, setup)
-        .add_systems(
-            Update,
-            (
-                apply_delayed_external_forces,
+            // The scoreboard window can be closed on its own, the game window ends the app
+            exit_condition: ExitCondition::OnPrimaryClosed,
+            ..default()
+        }),
+        PhysicsPlugins::new(FixedUpdate),
+    ))
+    .insert_resource(FixedTime::new(FIXED_TIMESTEP))
+    .insert_resource(PhysicsTimestep::FixedOnce(FIXED_TIMESTEP.as_secs_f32()))
+    .insert_resource(Gravity::ZERO)
+    .insert_resource(Msaa::from(config.msaa))
+    .insert_resource(config.game_speed)
+    .insert_resource(config.key_bindings.clone())
+    .insert_resource(settings.audio)
+    .insert_resource(settings.cosmetics)
+    .insert_resource(settings)
+    .insert_resource(load_config_file::<PlayerStats>(STATS_FILE))
+    .insert_resource(config)
+    .add_state::<GameState>()
+    .add_plugins((GameplayPlugin, HudPlugin, LoadingPlugin))
+    .add_plugins((
+        MenuPlugin,
+        NetworkPlugin,
+        SfxPlugin,
+        MusicPlugin,
+        ReplayPlugin,
+        CosmeticsPlugin,
+    ))
+    .add_systems(Startup, setup)
+    .run();
+}
+
+// The match itself: the sets its systems run in, the plugins simulating it and its setup when the
+// menu is left. Windows, menus, sound and LAN are left to the game, so tests run it headless too
+struct GameplayPlugin;
+
+impl Plugin for GameplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GoalScored>()
+            .add_event::<PaddleHit>()
+            .init_resource::<DebugOverlays>()
+            .configure_set(
+                FixedUpdate,
+                SimulationSet
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(not(resource_exists::<NetClient>()))
+                    .before(PhysicsSet::Prepare),
+            )
+            .configure_sets(
+                FixedUpdate,
                 (
-                    check_goals,
-                    spawn_ball,
+                    StepSet::Forces,
+                    StepSet::MovePaddles,
+                    StepSet::PaddleEffects,
                 )
-                    .chain(),
-                move_paddle,
-                update_score.run_if(resource_changed::<Score>()),
-            ),
-        )
-        .run();
+                    .chain()
+                    .in_set(SimulationSet),
+            )
+            .configure_set(
+                Update,
+                SimulationSet
+                    .run_if(in_state(GameState::Playing))
+                    // A LAN client only shows what the host simulates
+                    .run_if(not(resource_exists::<NetClient>())),
+            )
+            .configure_sets(
+                Update,
+                (
+                    GoalSet::Detect,
+                    GoalSet::React,
+                    GoalSet::Victory,
+                    GoalSet::Countdown,
+                    GoalSet::Serve,
+                )
+                    .chain()
+                    .in_set(SimulationSet),
+            )
+            .configure_sets(
+                Update,
+                (HitSet::Detect, HitSet::React, HitSet::Collect)
+                    .chain()
+                    .in_set(SimulationSet),
+            )
+            .add_plugins((
+                ArenaPlugin,
+                BallPlugin,
+                PaddlePlugin,
+                AiPlugin,
+                ControlsPlugin,
+                ScorePlugin,
+                PowerUpPlugin,
+            ))
+            .add_systems(OnExit(GameState::Menu), setup_match)
+            .add_systems(OnEnter(GameState::Paused), pause_physics)
+            .add_systems(
+                OnExit(GameState::Paused),
+                (resume_physics, drop_paused_fixed_time),
+            )
+            .add_systems(OnEnter(GameState::GameOver), pause_physics)
+            .add_systems(OnExit(GameState::GameOver), resume_physics)
+            .add_systems(OnEnter(GameState::Intermission), pause_physics)
+            .add_systems(
+                OnExit(GameState::Intermission),
+                (resume_physics, drop_paused_fixed_time),
+            );
+    }
 }
 
-fn setup(mut commands: Commands, assets: Res<AssetServer>) {
+fn setup(mut commands: Commands, assets: Res<AssetServer>, config: Res<GameConfig>) {
     // Assets
     commands.insert_resource(BallAssets {
         point_ball: assets.load("ball_blue_large.png"),
     });
+    let arena_names = ArenaDefinition::BUILT_IN
+        .into_iter()
+        .chain([config.arena.as_str()]);
+    commands.insert_resource(ArenaAssets(
+        arena_names
+            .map(|name| (name.to_string(), assets.load(ArenaDefinition::path(name))))
+            .collect(),
+    ));
+    commands.insert_resource(AudioAssets {
+        paddle_hits: (1..=SFX_VARIANTS)
+            .map(|i| assets.load(format!("sounds/paddle_hit_{i}.ogg").as_str()))
+            .collect(),
+        wall_bounces: (1..=SFX_VARIANTS)
+            .map(|i| assets.load(format!("sounds/wall_bounce_{i}.ogg").as_str()))
+            .collect(),
+        serve_charge: assets.load("sounds/serve_charge.ogg"),
+        golden_ball: assets.load("sounds/golden_ball.ogg"),
+        goal: assets.load("sounds/goal.ogg"),
+        menu_click: assets.load("sounds/menu_click.ogg"),
+    });
+    commands.insert_resource(MusicAssets {
+        menu: assets.load("music/menu.ogg"),
+        gameplay: assets.load("music/gameplay.ogg"),
+    });
 
-    // Spawner
-    commands.init_resource::<PointBallCount>();
+    // Camera
+    commands.spawn((Camera2dBundle { ..default() }, MainCamera));
 
-    // Score
-    commands.init_resource::<Score>();
+    // Background, purely a sprite far behind everything so it never collides
+    if let Some(color) = config.background_color {
+        commands.insert_resource(ClearColor(color));
+    }
+    if let Some(path) = &config.background_image {
+        commands.spawn(SpriteBundle {
+            texture: assets.load(path.as_str()),
+            sprite: Sprite {
+                custom_size: Some(WINDOW_SIZE),
+                ..default()
+            },
+            transform: Transform::from_xyz(0., 0., -10.),
+            ..default()
+        });
+    }
 
-    // Camera
-    commands.spawn(Camera2dBundle { ..default() });
+    if config.scoreboard_window && !cfg!(target_arch = "wasm32") {
+        spawn_scoreboard_window(&mut commands);
+    }
 
-    // Net (middle line)
-    let net_box = Vec2::new(5., WINDOW_SIZE.y);
+    // Debug ball inspector, hidden until toggled
     commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                custom_size: Some(net_box),
-                color: Color::WHITE,
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 14.0,
+                    color: Color::YELLOW_GREEN,
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.),
+                top: Val::Px(10.),
                 ..default()
             },
+            visibility: Visibility::Hidden,
             ..default()
         },
-        RigidBody::Static,
-        Collider::cuboid(net_box.x, net_box.y),
-        CollisionLayers::new([Layer::Net], [Layer::Paddle]),
+        BallInspectorMarker,
     ));
+}
 
-    // Walls
-    // Up wall
-    spawn_wall(
-        &mut commands,
-        0.,
-        WINDOW_SIZE.y / 2.,
-        Vec2::NEG_Y,
-        CollisionLayers::new([Layer::Wall], [Layer::Paddle, Layer::Ball]),
-        false,
-    );
-    // Down wall
-    spawn_wall(
-        &mut commands,
-        0.,
-        -WINDOW_SIZE.y / 2.,
-        Vec2::Y,
-        CollisionLayers::new([Layer::Wall], [Layer::Paddle, Layer::Ball]),
-        false,
-    );
-    // Left wall
-    spawn_wall(
-        &mut commands,
-        -WINDOW_SIZE.x / 2.,
-        0.,
-        Vec2::X,
-        CollisionLayers::new([Layer::Wall], [Layer::Paddle]),
-        false,
-    );
-    // Right wall
-    spawn_wall(
-        &mut commands,
-        WINDOW_SIZE.x / 2.,
-        0.,
-        Vec2::NEG_X,
-        CollisionLayers::new([Layer::Wall], [Layer::Paddle]),
-        false,
-    );
-    // Left goal
-    spawn_wall(
-        &mut commands,
-        -(WINDOW_SIZE.x / 2. + 5.),
-        0.,
-        Vec2::X,
-        CollisionLayers::new([Layer::Wall], [Layer::Ball]),
-        true,
-    );
-    // Right goal
-    spawn_wall(
+// Everything a match needs, built once the menu is left so the options picked there apply
+fn setup_match(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    cosmetics: Res<CosmeticsConfig>,
+    ball_assets: Res<BallAssets>,
+    arena_assets: Res<ArenaAssets>,
+    arenas: Res<Assets<ArenaDefinition>>,
+    demo: Option<Res<DemoMatch>>,
+    hosting: Option<Res<NetHost>>,
+    client: Option<Res<NetClient>>,
+    playback: Option<Res<ReplayPlayback>>,
+    touch_screen: Res<TouchScreen>,
+    tournament: Option<Res<Tournament>>,
+) {
+    // Resources are replaced rather than initialized, a match left for the menu leaves its own behind
+    // Spawner
+    // The seed is always explicit so the match can be recorded
+    let seed = match &playback {
+        Some(playback) => playback.replay.seed,
+        None => config.seed.unwrap_or_else(rand::random),
+    };
+    commands.insert_resource(GameRng::new(Some(seed)));
+    commands.insert_resource(SfxRng::for_seed(seed));
+    // A LAN client's randomness is the host's, its own seed means nothing
+    if client.is_none() {
+        commands.insert_resource(MatchSeed(seed));
+    } else {
+        commands.remove_resource::<MatchSeed>();
+    }
+    // Time left over from the menu would shift when the fixed steps fall
+    commands.insert_resource(FixedTime::new(FIXED_TIMESTEP));
+    commands.insert_resource(PointBallCount::default());
+    commands.insert_resource(RallyCounter::default());
+    commands.insert_resource(ServeCountdown::default());
+    commands.insert_resource(GoalCelebration::default());
+    commands.insert_resource(NextServe::default());
+    commands.insert_resource(BallSpawnConfig::for_config(&config));
+
+    // Score
+    commands.insert_resource(Score::default());
+    commands.insert_resource(WinningScore(config.winning_score));
+    commands.insert_resource(GamesWon::default());
+    commands.insert_resource(SurvivalRun::default());
+    commands.insert_resource(MatchStats::default());
+    commands.insert_resource(MatchTimer::new(&config));
+    commands.insert_resource(DefensiveWallCharges::default());
+    commands.insert_resource(PowerUpCooldowns::default());
+
+    let controllers = [true, false].map(|first_player| {
+        PaddleController::for_paddle(first_player, &config, demo.is_some(), hosting.is_some())
+    });
+    commands.remove_resource::<ReplayRecorder>();
+    let controllers = match &playback {
+        // The AI replays itself from the seed, only the players' inputs were recorded
+        Some(playback) => playback
+            .replay
+            .controllers
+            .map(|controller| match controller {
+                PaddleController::Ai => PaddleController::Ai,
+                _ => PaddleController::Replay,
+            }),
+        None => {
+            if demo.is_none() && client.is_none() {
+                let replay = Replay::new(&config, seed, controllers);
+                commands.insert_resource(ReplayRecorder::new(replay));
+            }
+            controllers
+        }
+    };
+    // A missing or broken arena file falls back to the open arena
+    let arena = arena_assets
+        .get(&config.arena, &arenas)
+        .cloned()
+        .unwrap_or_else(|| {
+            warn!(
+                "Arena {:?} is not loaded, playing the open arena",
+                config.arena
+            );
+            ArenaDefinition::default()
+        });
+    let court = Court::new(&arena);
+    commands.insert_resource(court);
+    commands.insert_resource(ServeQueue::from_config(&config, &court));
+    // Shapes aren't sent over LAN, both ends play flat paddles so they see the same court
+    let shapes = if hosting.is_some() || client.is_some() {
+        [PaddleShape::Flat; 2]
+    } else {
+        config.paddle_shapes
+    };
+    spawn_arena(
         &mut commands,
-        WINDOW_SIZE.x / 2. + 5.,
-        0.,
-        Vec2::NEG_X,
-        CollisionLayers::new([Layer::Wall], [Layer::Ball]),
-        true,
+        &config,
+        &cosmetics,
+        &arena,
+        controllers,
+        shapes,
     );
-
-    // Paddles
-    spawn_paddle(&mut commands, true);
-    spawn_paddle(&mut commands, false);
+    spawn_obstacles(&mut commands, &ball_assets, &arena.obstacles());
+    if config.mode == GameMode::Breakout {
+        spawn_bricks(&mut commands, &court);
+    }
+    if config.mode == GameMode::FourPlayer {
+        for (side, y) in [(Side::Top, 1.), (Side::Bottom, -1.)] {
+            commands.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "0",
+                        TextStyle {
+                            font_size: 48.0,
+                            color: Color::WHITE.with_a(0.6),
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_xyz(0., y * (court.half_size.y - 70.), 0.5),
+                    ..default()
+                },
+                SeatScoreText(side),
+                MatchEntity,
+            ));
+        }
+    }
 
     // Game UI including Score Display
-    commands.spawn(NodeBundle {
-        style: Style { 
-            width: Val::Percent(100.),
-            height: Val::Percent(20.),
-            display: Display::Flex,
-            flex_direction: FlexDirection::Row,
-            justify_content: JustifyContent::Center,
-            ..default()
-        },
-        ..default()
-    })
-    .with_children(|builder| {
-        builder.spawn(NodeBundle {
-            style: Style { 
-                width: Val::Percent(20.),
-                height: Val::Percent(20.),
-                display: Display::Flex,
-                flex_direction: FlexDirection::Row,
-                justify_content: JustifyContent::SpaceBetween,
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(20.),
+                    display: Display::Flex,
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
                 ..default()
             },
-            ..default()
-        })
+            MatchEntity,
+        ))
         .with_children(|builder| {
-            builder.spawn((TextBundle {
-                text: Text::from_section(
-                    "0",
-                    TextStyle {
-                        font_size: 50.0,
-                        color: Color::WHITE,
+            builder
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(20.),
+                        height: Val::Percent(20.),
+                        display: Display::Flex,
+                        flex_direction: FlexDirection::Row,
+                        justify_content: JustifyContent::SpaceBetween,
                         ..default()
                     },
-                ).with_alignment(TextAlignment::Left),
-                ..default()
-            }, Player1ScoreMarker));
+                    ..default()
+                })
+                .with_children(|builder| {
+                    builder.spawn((
+                        TextBundle {
+                            text: Text::from_section(
+                                "0",
+                                TextStyle {
+                                    font_size: 50.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                },
+                            )
+                            .with_alignment(TextAlignment::Left),
+                            ..default()
+                        },
+                        Player1ScoreMarker,
+                    ));
 
-            builder.spawn((TextBundle {
-                text: Text::from_section(
-                    "0",
+                    builder.spawn((
+                        TextBundle {
+                            text: Text::from_section(
+                                "0",
+                                TextStyle {
+                                    font_size: 50.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                },
+                            )
+                            .with_alignment(TextAlignment::Right),
+                            ..default()
+                        },
+                        Player2ScoreMarker,
+                    ));
+                });
+
+            builder.spawn((
+                TextBundle {
+                    text: Text::from_section(
+                        format!("First to {}", config.winning_score),
+                        TextStyle {
+                            font_size: 20.0,
+                            color: Color::GRAY,
+                            ..default()
+                        },
+                    ),
+                    ..default()
+                },
+                WinningScoreMarker,
+            ));
+
+            builder.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::GRAY,
+                        ..default()
+                    },
+                ),
+                GamesWonMarker,
+            ));
+
+            let pairing = tournament
+                .as_ref()
+                .and_then(|tournament| tournament.next_names())
+                .map_or(String::new(), |[left, right]| format!("{left} vs {right}"));
+            builder.spawn((
+                TextBundle::from_section(
+                    pairing,
                     TextStyle {
-                        font_size: 50.0,
+                        font_size: 24.0,
                         color: Color::WHITE,
                         ..default()
                     },
-                ).with_alignment(TextAlignment::Right),
-                ..default()
-            }, Player2ScoreMarker));
+                ),
+                TournamentMatchText,
+            ));
         });
-    });
-}
-
-fn mirror_transform(transform: &mut Transform) {
-    transform.translation.x = -transform.translation.x;
-}
 
-fn spawn_paddle(commands: &mut Commands, first_player: bool) {
-    let mut transform = Transform::from_xyz(-(WINDOW_SIZE.x / 2.) + 20., 0., 5.);
-    let (color, side) = if first_player {
-        // Not colorblind friendly, use images that look different in black and white
-        (Color::ORANGE, Side::Left)
-    } else {
-        mirror_transform(&mut transform);
-        (Color::PURPLE, Side::Right)
-    };
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color,
-                custom_size: Some(PADDLE_SIZE),
+    // Defensive wall status, one per player in the bottom corners
+    for first_player in [true, false] {
+        let mut style = Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.),
+            ..default()
+        };
+        if first_player {
+            style.left = Val::Px(10.);
+        } else {
+            style.right = Val::Px(10.);
+        }
+        commands.spawn((
+            TextBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::GRAY,
+                        ..default()
+                    },
+                ),
+                style,
                 ..default()
             },
-            transform,
-            ..default()
-        },
-        RigidBody::Kinematic,
-        Collider::cuboid(PADDLE_SIZE.x, PADDLE_SIZE.y),
-        CollisionLayers::new([Layer::Paddle], [Layer::Ball, Layer::Wall, Layer::Net]),
-        Restitution::new(0.8),
-        Paddle { first_player, side },
-    ));
-}
-
-fn spawn_wall(
-    commands: &mut Commands,
-    x: f32,
-    y: f32,
-    outward_normal: Vec2,
-    collision_layers: CollisionLayers,
-    goal: bool,
-) {
-    let mut wall = commands.spawn((
-        Transform::from_xyz(x, y, 0.),
-        GlobalTransform::default(),
-        RigidBody::Static,
-        Collider::halfspace(outward_normal),
-        collision_layers,
-        Restitution::new(0.8),
-        Friction::ZERO,
-    ));
-
-    if goal {
-        let left = x < 0.;
-        wall.insert(Goal {
-            first_player: left,
-            side: if left { Side::Left } else { Side::Right },
-        });
+            DefensiveWallHud { first_player },
+            MatchEntity,
+        ));
     }
-}
-
-fn spawn_ball(
-    mut commands: Commands,
-    ball_assets: Res<BallAssets>,
-    spatial_query: SpatialQuery,
-    mut timer: Local<Timer>,
-    time: Res<Time>,
-) {
-    timer.tick(time.delta());
-    if timer.finished() {
-        let ball_collider = Collider::ball(BALL_RADIUS);
-        let ball_position = Vec2::ZERO;
-        let intersections = spatial_query.shape_intersections(
-            &ball_collider,
-            ball_position,
-            0.,
-            SpatialQueryFilter::new().with_masks([Layer::Ball, Layer::Paddle]),
-        );
-
-        if intersections.is_empty() {
-            let spawn_direction = Side::Random;
-
-            timer.set_duration(Duration::from_millis(10));
-            timer.reset();
-
-            let direction_angle = rand::random::<f32>() * (PI / 2. - PI / 4.);
-            let mut direction = Vec2::from_angle(direction_angle);
-            if spawn_direction == Side::Left
-                || (spawn_direction == Side::Random && rand::random::<bool>())
-            {
-                direction.x = -direction.x;
-            }
 
-            commands.spawn((
-                SpriteBundle {
-                    texture: ball_assets.point_ball.clone(),
-                    sprite: Sprite {
-                        color: Color::WHITE,
-                        custom_size: Some(Vec2::ONE * (BALL_RADIUS * 2.)),
+    // Same conditions as the pause key
+    if demo.is_none() && playback.is_none() && client.is_none() {
+        commands
+            .spawn((
+                ButtonBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        top: Val::Px(10.),
+                        right: Val::Px(10.),
+                        width: Val::Px(TOUCH_BUTTON_SIZE),
+                        height: Val::Px(TOUCH_BUTTON_SIZE),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
                         ..default()
                     },
-                    transform: Transform::from_translation(ball_position.extend(4.)),
+                    background_color: MENU_BUTTON_COLOR.with_a(0.6).into(),
+                    visibility: if touch_screen.0 {
+                        Visibility::Inherited
+                    } else {
+                        Visibility::Hidden
+                    },
                     ..default()
                 },
-                RigidBody::Dynamic,
-                ball_collider,
-                CollisionLayers::new([Layer::Ball], [Layer::Ball, Layer::Paddle, Layer::Wall]),
-                DelayedExternalForce(
-                    ExternalForce::new(direction * INITIAL_FORCE).with_persistence(false),
-                ),
-                Restitution::new(0.7),
-                Friction::ZERO,
-                LockedAxes::ROTATION_LOCKED,
-                Ball { points: 0 },
+                PauseButton,
+                MatchEntity,
             ))
             .with_children(|builder| {
-                builder.spawn((
-                    Text2dBundle {
-                        text: Text {
-                            sections: vec![TextSection::new(
-                                "0",
-                                TextStyle {
-                                    font_size: 20.0,
-                                    color: Color::WHITE,
-                                    ..default()
-                                },
-                            )],
-                            ..Default::default()
-                        },
-                        transform: Transform::from_xyz(0.0, 0.0, 99.0),
+                builder.spawn(TextBundle::from_section(
+                    "II",
+                    TextStyle {
+                        font_size: 36.0,
+                        color: Color::WHITE,
                         ..default()
-                    }
-                ,BallTextMarker));
+                    },
+                ));
             });
-        }
     }
-}
-
-fn apply_delayed_external_forces(
-    mut commands: Commands,
-    q: Query<(Entity, &DelayedExternalForce)>,
-) {
-    for (e, d) in q.iter() {
-        commands
-            .entity(e)
-            .insert(d.0)
-            .remove::<DelayedExternalForce>();
-    }
-}
 
-fn check_goals(
-    mut commands: Commands,
-    mut collision_event_reader: EventReader<Collision>,
-    mut goals: Query<&mut Goal>,
-    balls: Query<&Ball>,
-    mut score: ResMut<Score>,
-    mut point_ball_count: ResMut<PointBallCount>,
-    mut paddles: Query<(&mut Position, &mut Paddle)>,
-) {
-    for Collision(contact) in collision_event_reader.iter() {
-        if let Some((goal, _goal_entity, ball, ball_entity)) =
-            if let Ok(goal) = goals.get(contact.entity1) {
-                if let Ok(ball) = balls.get(contact.entity2) {
-                    Some((goal, contact.entity1, ball, contact.entity2))
-                } else {
-                    None
-                }
-            } else if let Ok(goal) = goals.get(contact.entity2) {
-                if let Ok(ball) = balls.get(contact.entity1) {
-                    Some((goal, contact.entity2, ball, contact.entity1))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        {
-            if goal.first_player {
-                score.first_player += 1;
-            } else {
-                score.second_player += 1;
-            }
-            if let Some(new_score) = point_ball_count.0.checked_sub(1) {
-                point_ball_count.0 = new_score;
+    if demo.is_some() {
+        commands.spawn((
+            TextBundle {
+                text: Text::from_section(
+                    "DEMO - press any button",
+                    TextStyle {
+                        font_size: 24.0,
+                        color: Color::YELLOW,
+                        ..default()
+                    },
+                ),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(60.),
+                    width: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
             }
+            .with_text_alignment(TextAlignment::Center),
+            MatchEntity,
+        ));
+    }
 
-            if let Some(entity) = commands.get_entity(ball_entity) {
-                entity.despawn_recursive();
-            }
+    // Serve countdown, big in the middle of the court
+    commands.spawn((
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 120.0,
+                    color: Color::WHITE.with_a(0.8),
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(40.),
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
         }
+        .with_text_alignment(TextAlignment::Center),
+        ServeCountdownText,
+        MatchEntity,
+    ));
 
-//      if let Some((goal, _goal_entity, ball, ball_entity)) =
-//          if let Ok((_, paddle)) = paddles.get(contact.entity1) {
-//              if let Ok(ball) = balls.get(contact.entity2) {
-//                  Some((goal, contact.entity1, ball, contact.entity2))
-//              } else {
-//                  None
-//              }
-//          } else if let Ok(goal) = goals.get(contact.entity2) {
-//              if let Ok(ball) = balls.get(contact.entity1) {
-//                  Some((goal, contact.entity2, ball, contact.entity1))
-//              } else {
-//                  None
-//              }
-//          } else {
-//              None
-//          }
-//      {
-//      }
-    }
-}
+    // Spectators of a LAN match, top right
+    commands.spawn((
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 18.0,
+                    color: Color::GRAY,
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.),
+                right: Val::Px(10.),
+                ..default()
+            },
+            ..default()
+        },
+        SpectatorHud,
+        MatchEntity,
+    ));
 
-fn move_paddle(
-    time: Res<Time>,
-    q_windows: Query<&Window, With<PrimaryWindow>>,
-    buttons: Res<Input<MouseButton>>,
-    camera: Query<(&GlobalTransform, &Camera)>,
-    mut paddles: Query<(&Transform, &mut LinearVelocity, &mut Position, &Paddle)>,
-    balls: Query<&Transform, With<Ball>>,
-) {
-    for (paddle_transform, mut velocity, mut paddle_position, paddle) in paddles.iter_mut() {
-        if paddle.first_player {
-            if !buttons.pressed(MouseButton::Left) {
-                *velocity = LinearVelocity(Vec2::ZERO);
-                continue;
-            }
-            let Some(position) = q_windows.single().cursor_position() else {
-                    continue;
-                };
-            let Some((camera_transform, camera)) = camera.iter().next() else {
-                continue;
-                };
-            let Some(position) = camera.viewport_to_world_2d(camera_transform, position) else {
-                continue;
-                };
-            let to_target_position = position - paddle_transform.translation.xy();
-            let new_velocity = to_target_position.normalize_or_zero()
-                * PADDLE_SPEED.min(to_target_position.length() / time.delta_seconds());
-            velocity.0 = new_velocity;
-
-            // keeping paddle within bounds 
-            if paddle_position.x < P1_LEFT_BOUND && velocity.x < 0.0 {
-                velocity.x = 0.0;
-                paddle_position.x = LEFT_WALL;
-            }
-            if paddle_position.x > P1_RIGHT_BOUND && velocity.x > 0.0 {
-                paddle_position.x = RIGHT_WALL;
-                velocity.x = 0.0;
-            }
-            if paddle_position.y > P1_TOP_BOUND && velocity.y > 0.0 {
-                velocity.y = 0.0;
-                paddle_position.y = TOP_WALL;
-            }
-            if paddle_position.y < P1_BOTTOM_BOUND && velocity.y < 0.0 {
-                velocity.y = 0.0;
-                paddle_position.y = BOTTOM_WALL;
-            }
+    // Handicap buff status, just above the wall status
+    if config.handicap_interval > 0. {
+        commands.insert_resource(HandicapTimer(Timer::from_seconds(
+            config.handicap_interval,
+            TimerMode::Repeating,
+        )));
+    }
+    for first_player in [true, false] {
+        let mut style = Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(34.),
+            ..default()
+        };
+        if first_player {
+            style.left = Val::Px(10.);
         } else {
-            let Some(t) = balls.iter().next() else {
-                continue;
-            };
-            let to_target_position = Vec2::new(paddle_transform.translation.x, t.translation.y)
-                - paddle_transform.translation.xy();
-
-            let new_velocity = to_target_position.normalize_or_zero()
-                * PADDLE_SPEED_AI.min(to_target_position.length() / time.delta_seconds());
-            *velocity = LinearVelocity(new_velocity);
+            style.right = Val::Px(10.);
         }
+        commands.spawn((
+            TextBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::LIME_GREEN,
+                        ..default()
+                    },
+                ),
+                style,
+                ..default()
+            },
+            PaddleBuffHud { first_player },
+            MatchEntity,
+        ));
     }
+
+    // Dash cooldown, above the first player's buff status
+    commands.spawn((
+        TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 18.0,
+                    color: Color::GRAY,
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(58.),
+                left: Val::Px(10.),
+                ..default()
+            },
+            ..default()
+        },
+        DashHud,
+        MatchEntity,
+    ));
 }
 
-fn update_score(
-    score: Res<Score>, 
-    mut player_1_score: Query<&mut Text, (With<Player1ScoreMarker>, Without<Player2ScoreMarker>)>,
-    mut player_2_score: Query<&mut Text, (With<Player2ScoreMarker>, Without<Player1ScoreMarker>)>,
-) {
-    let mut p1_text = player_1_score.single_mut();
-    p1_text.sections[0].value = format!("{}", score.first_player);
+fn pause_physics(mut physics_loop: ResMut<PhysicsLoop>) {
+    physics_loop.pause();
+}
+
+fn resume_physics(mut physics_loop: ResMut<PhysicsLoop>) {
+    physics_loop.resume();
+}
 
-    let mut p2_text = player_2_score.single_mut();
-    p2_text.sections[0].value = format!("{}", score.second_player);
+// Time left over from the pause would otherwise be caught up in one burst of steps
+fn drop_paused_fixed_time(mut fixed_time: ResMut<FixedTime>) {
+    *fixed_time = FixedTime::new(fixed_time.period);
 }
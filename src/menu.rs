@@ -0,0 +1,978 @@
+use crate::arena::ArenaDefinition;
+use crate::audio::AudioSettings;
+use crate::config::{
+    config_file_path, save_config_file, GameConfig, Opponent, Settings, SETTINGS_FILE,
+};
+use crate::cosmetics::CosmeticsConfig;
+use crate::input::{ActiveGamepad, InputSource, KeyBindings, TouchScreen};
+use crate::net::{open_socket, resolve_address, NetClient, NetHost, NetStatus, NetStatusText};
+use crate::prelude::*;
+use crate::replay::{Replay, ReplayPlayback, REPLAY_FILE};
+use crate::score::{
+    PlayerStats, Tournament, TournamentMatchText, TOURNAMENT_MAX_PLAYERS, TOURNAMENT_MIN_PLAYERS,
+    TOURNAMENT_NAME_LENGTH,
+};
+use crate::ui::{UI_SCALE_MAX, UI_SCALE_MIN, UI_SCALE_STEP};
+
+#[derive(Component)]
+struct MenuScreen;
+
+#[derive(Component)]
+struct PauseScreen;
+
+// Sections of the main menu shown one at a time by their button
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+enum MenuPanel {
+    Options,
+    Stats,
+    Tournament,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuButton {
+    Play,
+    Tournament,
+    TournamentSize,
+    // Picks which entrant's name typing edits
+    TournamentName(usize),
+    StartTournament,
+    Options,
+    Stats,
+    Quit,
+    Mode,
+    Opponent,
+    WinningScore,
+    BestOf,
+    PowerUps,
+    Arena,
+    ReducedMotion,
+    Difficulty,
+    // Cycles the shape of the given player's paddle
+    PaddleShape(usize),
+    PaddleSkin(usize),
+    BallSkin(usize),
+    Colorblind,
+    ScreenShake,
+    BallTrail,
+    DisplayMode,
+    UiScale,
+    Controls,
+    MasterVolume,
+    SfxVolume,
+    MusicVolume,
+    Mute,
+    Resume,
+    QuitToMenu,
+    HostGame,
+    JoinGame,
+    WatchGame,
+    WatchReplay,
+    // Typing on the menu edits the address shown here
+    JoinAddress,
+}
+
+impl MenuButton {
+    // Browsers can't open UDP sockets, and closing the tab is how a web game is quit
+    pub fn desktop_only(&self) -> bool {
+        matches!(
+            self,
+            Self::HostGame | Self::JoinGame | Self::WatchGame | Self::JoinAddress | Self::Quit
+        )
+    }
+}
+
+// On-screen pause for players without a keyboard or gamepad, shown once a touch is seen
+#[derive(Component)]
+pub struct PauseButton;
+
+// Seconds the demo's game over screen stays up before going back to the menu
+const DEMO_GAME_OVER_SECONDS: f32 = 5.;
+
+// Title screen shown before the first match, options picked here edit GameConfig directly
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(GameState::Menu),
+            (despawn_match, spawn_menu, stop_demo, stop_tournament),
+        )
+        .add_systems(OnExit(GameState::Menu), despawn_menu)
+        .add_systems(OnEnter(GameState::Paused), spawn_pause_screen)
+        .add_systems(OnExit(GameState::Paused), despawn_pause_screen)
+        .add_systems(
+            Update,
+            (
+                press_menu_buttons
+                    .run_if(in_state(GameState::Menu).or_else(in_state(GameState::Paused))),
+                update_menu_labels
+                    .run_if(in_state(GameState::Menu).or_else(in_state(GameState::Paused)))
+                    .run_if(
+                        resource_changed::<GameConfig>()
+                            .or_else(resource_changed::<AudioSettings>())
+                            .or_else(resource_changed::<CosmeticsConfig>()),
+                    ),
+                toggle_pause
+                    .run_if(in_state(GameState::Playing).or_else(in_state(GameState::Paused)))
+                    .run_if(not(resource_exists::<DemoMatch>()))
+                    .run_if(not(resource_exists::<ReplayPlayback>()))
+                    .run_if(not(resource_exists::<NetClient>())),
+                // Only spawned when pausing is allowed
+                press_pause_button.run_if(in_state(GameState::Playing)),
+                (detect_touch_screen, fit_buttons_to_touch).chain(),
+                start_demo_when_idle
+                    .run_if(in_state(GameState::Menu))
+                    .run_if(not(resource_exists::<NetHost>()))
+                    .run_if(not(resource_exists::<NetClient>())),
+                end_demo
+                    .run_if(in_state(GameState::Playing).or_else(in_state(GameState::GameOver)))
+                    .run_if(
+                        resource_exists::<DemoMatch>().or_else(resource_exists::<ReplayPlayback>()),
+                    ),
+                navigate_menu_with_gamepad
+                    .run_if(in_state(GameState::Menu).or_else(in_state(GameState::Paused))),
+                (show_tournament_name_buttons, edit_tournament_name)
+                    .run_if(in_state(GameState::Menu)),
+                update_tournament_match_text.run_if(
+                    resource_exists::<Tournament>().and_then(resource_changed::<Tournament>()),
+                ),
+                // A replay borrows the settings it was recorded with, they aren't the player's
+                save_settings
+                    .run_if(
+                        resource_changed::<GameConfig>()
+                            .or_else(resource_changed::<AudioSettings>())
+                            .or_else(resource_changed::<CosmeticsConfig>()),
+                    )
+                    .run_if(not(resource_exists::<ReplayPlayback>())),
+            ),
+        );
+        #[cfg(target_arch = "wasm32")]
+        app.add_systems(
+            Update,
+            pause_on_focus_loss
+                .run_if(in_state(GameState::Playing))
+                .run_if(not(resource_exists::<DemoMatch>()))
+                .run_if(not(resource_exists::<ReplayPlayback>())),
+        );
+    }
+}
+
+pub const MENU_BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const MENU_BUTTON_HOVER_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+// Roughly a fingertip, menu buttons grow to at least this tall on a touchscreen
+pub const TOUCH_BUTTON_SIZE: f32 = 64.;
+
+fn spawn_menu_button(
+    builder: &mut ChildBuilder,
+    button: MenuButton,
+    config: &GameConfig,
+    audio: &AudioSettings,
+    cosmetics: &CosmeticsConfig,
+) {
+    builder
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(320.),
+                    padding: UiRect::all(Val::Px(10.)),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: MENU_BUTTON_COLOR.into(),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                menu_label(button, config, audio, cosmetics),
+                TextStyle {
+                    font_size: 28.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn menu_label(
+    button: MenuButton,
+    config: &GameConfig,
+    audio: &AudioSettings,
+    cosmetics: &CosmeticsConfig,
+) -> String {
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let percent = |level: f32| (level * 100.).round();
+    match button {
+        MenuButton::Play => match config.opponent {
+            Opponent::Ai => "Play vs AI".to_string(),
+            Opponent::Human => "Play 2 players".to_string(),
+        },
+        MenuButton::Opponent => format!("Opponent: {:?}", config.opponent),
+        MenuButton::Tournament => "Tournament".to_string(),
+        MenuButton::TournamentSize => format!("Players: {}", config.tournament_names.len()),
+        MenuButton::TournamentName(index) => {
+            let name = config
+                .tournament_names
+                .get(index)
+                .map_or("", String::as_str);
+            let cursor = if config.editing_name == Some(index) {
+                "_"
+            } else {
+                ""
+            };
+            format!("{}. {name}{cursor}", index + 1)
+        }
+        MenuButton::StartTournament => "Start tournament".to_string(),
+        MenuButton::Options => "Options".to_string(),
+        MenuButton::Stats => "Stats".to_string(),
+        MenuButton::Quit => "Quit".to_string(),
+        MenuButton::Mode => format!("Mode: {:?}", config.mode),
+        MenuButton::WinningScore => format!("First to {}", config.winning_score),
+        MenuButton::BestOf if config.best_of == 1 => "Single game".to_string(),
+        MenuButton::BestOf => format!("Best of {} games", config.best_of),
+        MenuButton::PowerUps => format!("Power-ups: {}", on_off(config.power_ups)),
+        MenuButton::Arena => format!("Arena: {}", config.arena),
+        MenuButton::ReducedMotion => format!("Reduced motion: {}", on_off(config.reduced_motion)),
+        MenuButton::Difficulty => format!("AI difficulty: {:?}", config.ai_difficulty),
+        MenuButton::PaddleShape(index) => {
+            format!("P{} paddle: {:?}", index + 1, config.paddle_shapes[*index])
+        }
+        MenuButton::PaddleSkin(index) => {
+            format!(
+                "P{} paddle skin: {:?}",
+                index + 1,
+                cosmetics.paddle_skins[*index]
+            )
+        }
+        MenuButton::BallSkin(index) => {
+            format!(
+                "P{} ball skin: {:?}",
+                index + 1,
+                cosmetics.ball_skins[*index]
+            )
+        }
+        MenuButton::Colorblind => format!("Colorblind mode: {}", on_off(config.colorblind)),
+        MenuButton::ScreenShake if config.screen_shake == 0. => "Screen shake: off".to_string(),
+        MenuButton::ScreenShake => format!("Screen shake: {}%", percent(config.screen_shake)),
+        MenuButton::BallTrail => format!("Ball trail: {}", on_off(config.ball_trail)),
+        MenuButton::DisplayMode => format!("Display: {:?}", config.display_mode),
+        MenuButton::UiScale => format!("UI scale: {}%", percent(config.ui_scale)),
+        MenuButton::Controls => {
+            format!(
+                "Controls: {}",
+                InputSource::scheme_name(&config.input_priority)
+            )
+        }
+        MenuButton::MasterVolume => format!("Volume: {}%", percent(audio.master)),
+        MenuButton::SfxVolume => format!("Sound effects: {}%", percent(audio.sfx)),
+        MenuButton::MusicVolume => format!("Music: {}%", percent(audio.music)),
+        MenuButton::Mute => format!("Mute: {}", on_off(audio.muted)),
+        MenuButton::Resume => "Resume".to_string(),
+        MenuButton::QuitToMenu => "Quit to menu".to_string(),
+        MenuButton::HostGame => format!("Host LAN game on port {}", config.net_port),
+        MenuButton::JoinGame => "Join LAN game".to_string(),
+        MenuButton::WatchGame => "Watch LAN game".to_string(),
+        MenuButton::WatchReplay => "Watch last replay".to_string(),
+        MenuButton::JoinAddress => format!("Address: {}_", config.join_address),
+    }
+}
+
+fn spawn_menu(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    audio: Res<AudioSettings>,
+    cosmetics: Res<CosmeticsConfig>,
+    stats: Res<PlayerStats>,
+    net_status: Res<NetStatus>,
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.),
+                    ..default()
+                },
+                ..default()
+            },
+            MenuScreen,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                "Air Oki",
+                TextStyle {
+                    font_size: 80.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+            for button in [
+                MenuButton::Play,
+                MenuButton::Tournament,
+                MenuButton::HostGame,
+                MenuButton::JoinGame,
+                MenuButton::WatchGame,
+                MenuButton::JoinAddress,
+                MenuButton::WatchReplay,
+                MenuButton::Options,
+                MenuButton::Stats,
+                MenuButton::Quit,
+            ] {
+                if cfg!(target_arch = "wasm32") && button.desktop_only() {
+                    continue;
+                }
+                spawn_menu_button(builder, button, &config, &audio, &cosmetics);
+            }
+            builder.spawn((
+                TextBundle::from_section(
+                    net_status.0.clone(),
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::GRAY,
+                        ..default()
+                    },
+                ),
+                NetStatusText,
+            ));
+            builder
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            row_gap: Val::Px(8.),
+                            ..default()
+                        },
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    MenuPanel::Options,
+                ))
+                .with_children(|builder| {
+                    for button in [
+                        MenuButton::Mode,
+                        MenuButton::Opponent,
+                        MenuButton::WinningScore,
+                        MenuButton::BestOf,
+                        MenuButton::PowerUps,
+                        MenuButton::Arena,
+                        MenuButton::ReducedMotion,
+                        MenuButton::Difficulty,
+                        MenuButton::PaddleShape(0),
+                        MenuButton::PaddleShape(1),
+                        MenuButton::PaddleSkin(0),
+                        MenuButton::PaddleSkin(1),
+                        MenuButton::BallSkin(0),
+                        MenuButton::BallSkin(1),
+                        MenuButton::Colorblind,
+                        MenuButton::ScreenShake,
+                        MenuButton::BallTrail,
+                        MenuButton::DisplayMode,
+                        MenuButton::UiScale,
+                        MenuButton::Controls,
+                        MenuButton::MasterVolume,
+                        MenuButton::SfxVolume,
+                        MenuButton::MusicVolume,
+                        MenuButton::Mute,
+                    ] {
+                        spawn_menu_button(builder, button, &config, &audio, &cosmetics);
+                    }
+                });
+            builder
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            row_gap: Val::Px(8.),
+                            ..default()
+                        },
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    MenuPanel::Tournament,
+                ))
+                .with_children(|builder| {
+                    let names = (0..TOURNAMENT_MAX_PLAYERS).map(MenuButton::TournamentName);
+                    for button in [MenuButton::TournamentSize]
+                        .into_iter()
+                        .chain(names)
+                        .chain([MenuButton::StartTournament])
+                    {
+                        spawn_menu_button(builder, button, &config, &audio, &cosmetics);
+                    }
+                });
+            builder.spawn((
+                TextBundle {
+                    text: Text::from_section(
+                        stats.summary(),
+                        TextStyle {
+                            font_size: 24.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                MenuPanel::Stats,
+            ));
+        });
+}
+
+fn save_settings(
+    config: Res<GameConfig>,
+    audio: Res<AudioSettings>,
+    cosmetics: Res<CosmeticsConfig>,
+    mut settings: ResMut<Settings>,
+) {
+    let current = Settings::from_config(&config, &audio, &cosmetics);
+    if current == *settings {
+        return;
+    }
+    *settings = current;
+    if let Err(error) = save_config_file(SETTINGS_FILE, &*settings) {
+        warn!("Could not save settings: {error}");
+    }
+}
+
+fn despawn_menu(mut commands: Commands, screens: Query<Entity, With<MenuScreen>>) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// D-pad moves a highlight over the visible buttons top to bottom, South presses it
+fn navigate_menu_with_gamepad(
+    gamepad: Res<ActiveGamepad>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut focused: Local<usize>,
+    mut buttons: Query<
+        (
+            &mut Interaction,
+            &mut BackgroundColor,
+            &ComputedVisibility,
+            &GlobalTransform,
+        ),
+        With<MenuButton>,
+    >,
+) {
+    let pressed = |button_type| gamepad.just_pressed(&gamepad_buttons, button_type);
+    let (up, down, confirm) = (
+        pressed(GamepadButtonType::DPadUp),
+        pressed(GamepadButtonType::DPadDown),
+        pressed(GamepadButtonType::South),
+    );
+    if !(up || down || confirm) {
+        return;
+    }
+
+    let mut visible = buttons
+        .iter_mut()
+        .filter(|(_, _, visibility, _)| visibility.is_visible())
+        .collect::<Vec<_>>();
+    if visible.is_empty() {
+        return;
+    }
+    // UI y grows downward
+    visible.sort_by(|(_, _, _, a), (_, _, _, b)| a.translation().y.total_cmp(&b.translation().y));
+    let count = visible.len();
+    if up {
+        *focused = (*focused + count - 1) % count;
+    } else if down {
+        *focused = (*focused + 1) % count;
+    }
+    *focused = (*focused).min(count - 1);
+
+    for (i, (interaction, color, _, _)) in visible.iter_mut().enumerate() {
+        let focus = i == *focused;
+        color.0 = if focus {
+            MENU_BUTTON_HOVER_COLOR
+        } else {
+            MENU_BUTTON_COLOR
+        };
+        if focus && confirm {
+            // press_menu_buttons reacts to the change like it does for a click
+            **interaction = Interaction::Pressed;
+        }
+    }
+}
+
+fn any_input_pressed(
+    keys: &Input<KeyCode>,
+    mouse_buttons: &Input<MouseButton>,
+    touches: &Touches,
+    gamepad_buttons: &Input<GamepadButton>,
+) -> bool {
+    keys.get_just_pressed().next().is_some()
+        || mouse_buttons.get_just_pressed().next().is_some()
+        || touches.any_just_pressed()
+        || gamepad_buttons.get_just_pressed().next().is_some()
+}
+
+fn start_demo_when_idle(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut idle: Local<f32>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let moved = cursor_moved.iter().count() > 0;
+    if moved || any_input_pressed(&keys, &mouse_buttons, &touches, &gamepad_buttons) {
+        *idle = 0.;
+        return;
+    }
+    *idle += time.delta_seconds();
+    if config.attract_delay > 0. && *idle >= config.attract_delay {
+        *idle = 0.;
+        commands.insert_resource(DemoMatch);
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn end_demo(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut game_over_for: Local<f32>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if *state.get() == GameState::GameOver {
+        *game_over_for += time.delta_seconds();
+    }
+    if *game_over_for >= DEMO_GAME_OVER_SECONDS
+        || any_input_pressed(&keys, &mouse_buttons, &touches, &gamepad_buttons)
+    {
+        *game_over_for = 0.;
+        next_state.set(GameState::Menu);
+    }
+}
+
+fn stop_demo(mut commands: Commands) {
+    commands.remove_resource::<DemoMatch>();
+}
+
+fn stop_tournament(mut commands: Commands) {
+    commands.remove_resource::<Tournament>();
+}
+
+// One name button per entrant, the rest stay out of the layout
+fn show_tournament_name_buttons(
+    config: Res<GameConfig>,
+    mut buttons: Query<(&MenuButton, &mut Style, &mut Visibility)>,
+) {
+    for (button, mut style, mut visibility) in buttons.iter_mut() {
+        let MenuButton::TournamentName(index) = button else {
+            continue;
+        };
+        let (display, shown) = if *index < config.tournament_names.len() {
+            (Display::Flex, Visibility::Inherited)
+        } else {
+            (Display::None, Visibility::Hidden)
+        };
+        if style.display != display {
+            style.display = display;
+        }
+        if *visibility != shown {
+            *visibility = shown;
+        }
+    }
+}
+
+fn edit_tournament_name(
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut config: ResMut<GameConfig>,
+) {
+    let Some(index) = config.editing_name else {
+        characters.clear();
+        return;
+    };
+    let typed = characters
+        .iter()
+        .map(|event| event.char)
+        .filter(|c| c.is_alphanumeric() || " -_.'".contains(*c))
+        .collect::<String>();
+    let Some(name) = config.tournament_names.get_mut(index) else {
+        return;
+    };
+    if !typed.is_empty() {
+        name.push_str(&typed);
+        *name = name.chars().take(TOURNAMENT_NAME_LENGTH).collect();
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        name.pop();
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        config.editing_name = None;
+    }
+}
+
+fn update_tournament_match_text(
+    tournament: Res<Tournament>,
+    mut texts: Query<&mut Text, With<TournamentMatchText>>,
+) {
+    let label = match tournament.next_names() {
+        Some([left, right]) => format!("{left} vs {right}"),
+        None => String::new(),
+    };
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = label.clone();
+    }
+}
+
+fn despawn_match(mut commands: Commands, entities: Query<Entity, With<MatchEntity>>) {
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn toggle_pause(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    gamepad: Res<ActiveGamepad>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keys.just_pressed(bindings.pause)
+        && !gamepad.just_pressed(&gamepad_buttons, GamepadButtonType::Start)
+    {
+        return;
+    }
+    next_state.set(if *state.get() == GameState::Paused {
+        GameState::Playing
+    } else {
+        GameState::Paused
+    });
+}
+
+fn spawn_pause_screen(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    audio: Res<AudioSettings>,
+    cosmetics: Res<CosmeticsConfig>,
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.7).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            PauseScreen,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                "Paused",
+                TextStyle {
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+            for button in [MenuButton::Resume, MenuButton::Mute, MenuButton::QuitToMenu] {
+                spawn_menu_button(builder, button, &config, &audio, &cosmetics);
+            }
+        });
+}
+
+fn despawn_pause_screen(mut commands: Commands, screens: Query<Entity, With<PauseScreen>>) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn detect_touch_screen(touches: Res<Touches>, mut touch_screen: ResMut<TouchScreen>) {
+    if !touch_screen.0 && touches.any_just_pressed() {
+        touch_screen.0 = true;
+    }
+}
+
+fn press_pause_button(
+    buttons: Query<&Interaction, (Changed<Interaction>, With<PauseButton>)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if buttons
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        next_state.set(GameState::Paused);
+    }
+}
+
+// Runs for buttons spawned after the first touch too, menus are rebuilt on every visit
+fn fit_buttons_to_touch(
+    touch_screen: Res<TouchScreen>,
+    mut menu_buttons: Query<(Ref<MenuButton>, &mut Style)>,
+    mut pause_buttons: Query<&mut Visibility, With<PauseButton>>,
+) {
+    if !touch_screen.0 {
+        return;
+    }
+    for (button, mut style) in menu_buttons.iter_mut() {
+        if touch_screen.is_changed() || button.is_added() {
+            style.min_height = Val::Px(TOUCH_BUTTON_SIZE);
+            style.align_items = AlignItems::Center;
+            style.margin = UiRect::vertical(Val::Px(4.));
+        }
+    }
+    if touch_screen.is_changed() {
+        for mut visibility in pause_buttons.iter_mut() {
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
+
+fn press_menu_buttons(
+    mut commands: Commands,
+    mut buttons: Query<(&Interaction, &MenuButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut panels: Query<(&mut Visibility, &MenuPanel)>,
+    mut config: ResMut<GameConfig>,
+    mut audio: ResMut<AudioSettings>,
+    mut cosmetics: ResMut<CosmeticsConfig>,
+    mut net_status: ResMut<NetStatus>,
+    hosting: Option<Res<NetHost>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, button, mut color) in buttons.iter_mut() {
+        *color = match interaction {
+            Interaction::Hovered | Interaction::Pressed => MENU_BUTTON_HOVER_COLOR.into(),
+            Interaction::None => MENU_BUTTON_COLOR.into(),
+        };
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        // Any other button finishes the name being typed
+        if config.editing_name.is_some() && !matches!(button, MenuButton::TournamentName(_)) {
+            config.editing_name = None;
+        }
+        match button {
+            MenuButton::Play => {
+                // A local match drops any LAN game being set up
+                commands.remove_resource::<NetHost>();
+                commands.remove_resource::<NetClient>();
+                net_status.0.clear();
+                next_state.set(GameState::Playing);
+            }
+            MenuButton::Options | MenuButton::Stats | MenuButton::Tournament => {
+                let shown = match button {
+                    MenuButton::Options => MenuPanel::Options,
+                    MenuButton::Tournament => MenuPanel::Tournament,
+                    _ => MenuPanel::Stats,
+                };
+                // Opening one panel closes the others
+                for (mut visibility, panel) in panels.iter_mut() {
+                    *visibility = if *panel == shown && *visibility == Visibility::Hidden {
+                        Visibility::Inherited
+                    } else {
+                        Visibility::Hidden
+                    };
+                }
+            }
+            MenuButton::TournamentSize => {
+                let count = if config.tournament_names.len() >= TOURNAMENT_MAX_PLAYERS {
+                    TOURNAMENT_MIN_PLAYERS
+                } else {
+                    config.tournament_names.len() + 1
+                };
+                config.tournament_names.truncate(count);
+                while config.tournament_names.len() < count {
+                    let name = format!("Player {}", config.tournament_names.len() + 1);
+                    config.tournament_names.push(name);
+                }
+            }
+            MenuButton::TournamentName(index) => config.editing_name = Some(*index),
+            MenuButton::StartTournament => {
+                commands.remove_resource::<NetHost>();
+                commands.remove_resource::<NetClient>();
+                net_status.0.clear();
+                commands.insert_resource(Tournament::new(config.tournament_names.clone()));
+                config.opponent = Opponent::Human;
+                next_state.set(GameState::Playing);
+            }
+            MenuButton::Quit => exit.send(AppExit),
+            MenuButton::Mode => config.mode = config.mode.next(),
+            MenuButton::Opponent => {
+                config.opponent = match config.opponent {
+                    Opponent::Ai => Opponent::Human,
+                    Opponent::Human => Opponent::Ai,
+                };
+            }
+            MenuButton::BestOf => {
+                config.best_of = if config.best_of >= 7 {
+                    1
+                } else {
+                    config.best_of + 2
+                };
+            }
+            MenuButton::WinningScore => {
+                config.winning_score = match config.winning_score {
+                    0..=4 => 5,
+                    5..=9 => 10,
+                    10..=14 => 15,
+                    15..=20 => 21,
+                    _ => 3,
+                };
+            }
+            MenuButton::PowerUps => config.power_ups = !config.power_ups,
+            MenuButton::Arena => {
+                // An arena picked with --arena outside the built-in ones goes back to the first
+                let built_in = ArenaDefinition::BUILT_IN;
+                let next = built_in
+                    .iter()
+                    .position(|name| *name == config.arena)
+                    .map_or(0, |i| i + 1);
+                config.arena = built_in[next % built_in.len()].to_string();
+            }
+            MenuButton::ReducedMotion => config.reduced_motion = !config.reduced_motion,
+            MenuButton::Difficulty => config.ai_difficulty = config.ai_difficulty.next(),
+            MenuButton::PaddleShape(index) => {
+                config.paddle_shapes[*index] = config.paddle_shapes[*index].next()
+            }
+            MenuButton::PaddleSkin(index) => {
+                cosmetics.paddle_skins[*index] = cosmetics.paddle_skins[*index].next()
+            }
+            MenuButton::BallSkin(index) => {
+                cosmetics.ball_skins[*index] = cosmetics.ball_skins[*index].next()
+            }
+            MenuButton::Colorblind => config.colorblind = !config.colorblind,
+            MenuButton::ScreenShake => {
+                // Quarter steps, wrapping from full back to off
+                config.screen_shake = if config.screen_shake >= 0.875 {
+                    0.
+                } else {
+                    ((config.screen_shake + 0.25) / 0.25).round() * 0.25
+                };
+            }
+            MenuButton::BallTrail => config.ball_trail = !config.ball_trail,
+            MenuButton::DisplayMode => config.display_mode = config.display_mode.next(),
+            MenuButton::UiScale => {
+                config.ui_scale = if config.ui_scale >= UI_SCALE_MAX - UI_SCALE_STEP / 2. {
+                    UI_SCALE_MIN
+                } else {
+                    ((config.ui_scale + UI_SCALE_STEP) / UI_SCALE_STEP).round() * UI_SCALE_STEP
+                };
+            }
+            MenuButton::Controls => {
+                config.input_priority = match InputSource::scheme_name(&config.input_priority) {
+                    "Auto" => vec![InputSource::Mouse],
+                    "Mouse" => vec![InputSource::Keyboard],
+                    _ => InputSource::DEFAULT_PRIORITY.to_vec(),
+                };
+            }
+            MenuButton::MasterVolume => audio.master = AudioSettings::next_level(audio.master),
+            MenuButton::SfxVolume => audio.sfx = AudioSettings::next_level(audio.sfx),
+            MenuButton::MusicVolume => audio.music = AudioSettings::next_level(audio.music),
+            MenuButton::Mute => audio.muted = !audio.muted,
+            MenuButton::Resume => next_state.set(GameState::Playing),
+            MenuButton::QuitToMenu => next_state.set(GameState::Menu),
+            // The client plays the right paddle, a mode without one has nothing for it to do
+            MenuButton::HostGame if hosting.is_none() && config.mode.lone_player() => {
+                net_status.0 = format!("{:?} can't be played over LAN", config.mode);
+            }
+            MenuButton::HostGame if hosting.is_none() => {
+                commands.remove_resource::<NetClient>();
+                net_status.0 = match open_socket(config.net_port) {
+                    Ok(socket) => {
+                        commands.insert_resource(NetHost {
+                            socket,
+                            peer: None,
+                            remote_target: None,
+                            silence: 0.,
+                            spectators: vec![],
+                        });
+                        format!("Waiting for a player on port {}", config.net_port)
+                    }
+                    Err(error) => format!("Could not host: {error}"),
+                };
+            }
+            MenuButton::WatchReplay => {
+                let loaded = config_file_path(REPLAY_FILE)
+                    .ok_or_else(|| "no config folder on this platform".to_string())
+                    .and_then(|path| Replay::load(&path.to_string_lossy()));
+                match loaded {
+                    Ok(replay) => {
+                        commands.remove_resource::<NetHost>();
+                        commands.remove_resource::<NetClient>();
+                        net_status.0.clear();
+                        let previous = config.clone();
+                        replay.rules.apply(&mut config);
+                        commands.insert_resource(ReplayPlayback::new(replay, previous));
+                        next_state.set(GameState::Playing);
+                    }
+                    Err(error) => net_status.0 = format!("Could not load the replay: {error}"),
+                }
+            }
+            MenuButton::JoinGame | MenuButton::WatchGame => {
+                commands.remove_resource::<NetHost>();
+                let spectating = *button == MenuButton::WatchGame;
+                let joined = resolve_address(&config.join_address)
+                    .and_then(|host| open_socket(0).map(|socket| (socket, host)));
+                net_status.0 = match joined {
+                    Ok((socket, host)) => {
+                        commands.insert_resource(NetClient {
+                            socket,
+                            host,
+                            silence: 0.,
+                            spectating,
+                        });
+                        format!("Joining {host}...")
+                    }
+                    Err(error) => format!("Could not join {}: {error}", config.join_address),
+                };
+            }
+            MenuButton::HostGame | MenuButton::JoinAddress => {}
+        }
+    }
+}
+
+fn update_menu_labels(
+    config: Res<GameConfig>,
+    audio: Res<AudioSettings>,
+    cosmetics: Res<CosmeticsConfig>,
+    buttons: Query<(&MenuButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (button, children) in buttons.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = menu_label(*button, &config, &audio, &cosmetics);
+            }
+        }
+    }
+}
+
+// Browsers stop drawing hidden tabs, the match waits instead of jumping ahead when shown again
+#[cfg(target_arch = "wasm32")]
+fn pause_on_focus_loss(
+    mut focus_events: EventReader<bevy::window::WindowFocused>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if focus_events.iter().any(|event| !event.focused) {
+        next_state.set(GameState::Paused);
+    }
+}
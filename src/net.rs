@@ -0,0 +1,696 @@
+use crate::ball::{Ball, BallAssets, BallKind};
+use crate::config::GameConfig;
+use crate::cosmetics::CosmeticsConfig;
+use crate::input::PlayerInput;
+use crate::paddle::{
+    steer_paddle, Paddle, PaddleBounds, PaddleController, PaddleSize, PADDLE_SPEED,
+};
+use crate::prelude::*;
+use crate::replay::ReplayRecorder;
+use crate::score::Score;
+
+// Hash of the simulation after the latest tick, peers and replays compare it to catch desyncs
+#[derive(Resource, Default)]
+pub struct SimulationChecksum {
+    pub tick: u64,
+    pub value: u64,
+}
+
+pub const DEFAULT_NET_PORT: u16 = 7777;
+// A peer not heard from for this many seconds is considered gone
+const NET_TIMEOUT_SECONDS: f32 = 5.;
+// Seconds between join requests while waiting for the host to answer
+const NET_HELLO_INTERVAL: f32 = 0.5;
+// Fits a snapshot of a few hundred balls
+const NET_BUFFER_SIZE: usize = 16 * 1024;
+
+// Hosting a LAN match, this side runs the simulation and the peer steers the second paddle
+#[derive(Resource)]
+pub struct NetHost {
+    pub socket: UdpSocket,
+    pub peer: Option<SocketAddr>,
+    // Where the peer wants its paddle, None while it isn't pushing any input
+    pub remote_target: Option<Vec2>,
+    pub silence: f32,
+    // Read-only clients and how long each has been silent
+    pub spectators: Vec<(SocketAddr, f32)>,
+}
+
+// Joined a LAN match, this side shows the host's snapshots and sends its paddle input
+#[derive(Resource)]
+pub struct NetClient {
+    pub socket: UdpSocket,
+    pub host: SocketAddr,
+    pub silence: f32,
+    // Only watches, sends no paddle input
+    pub spectating: bool,
+}
+
+// Spectators of the current LAN match, as counted by the host
+#[derive(Resource, Default)]
+struct SpectatorCount(pub usize);
+
+#[derive(Component)]
+pub struct SpectatorHud;
+
+// Last connection news, shown under the menu buttons
+#[derive(Resource, Default)]
+pub struct NetStatus(pub String);
+
+#[derive(Component)]
+pub struct NetStatusText;
+
+// Stand-in for a ball simulated by the LAN host, keyed by the host's entity bits
+#[derive(Component)]
+struct RemoteBall(pub u64);
+
+// LAN play over UDP, the host runs the match and streams a snapshot to its one client every frame
+pub struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetStatus>()
+            .init_resource::<SpectatorCount>()
+            .init_resource::<SimulationChecksum>()
+            .add_systems(OnEnter(GameState::Menu), close_network)
+            .add_systems(
+                FixedUpdate,
+                move_network_paddles.in_set(StepSet::MovePaddles),
+            )
+            .add_systems(
+                FixedUpdate,
+                update_simulation_checksum
+                    .after(PhysicsSet::Sync)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    edit_join_address
+                        .run_if(in_state(GameState::Menu))
+                        .run_if(|config: Res<GameConfig>| config.editing_name.is_none()),
+                    update_net_status.run_if(resource_changed::<NetStatus>()),
+                    update_spectator_hud,
+                    accept_client
+                        .run_if(in_state(GameState::Menu))
+                        .run_if(resource_exists::<NetHost>()),
+                    join_host
+                        .run_if(in_state(GameState::Menu))
+                        .run_if(resource_exists::<NetClient>()),
+                    exchange_with_client
+                        .run_if(not(in_state(GameState::Menu)))
+                        .run_if(resource_exists::<NetHost>()),
+                    exchange_with_host
+                        .run_if(not(in_state(GameState::Menu)))
+                        .run_if(resource_exists::<NetClient>()),
+                ),
+            );
+    }
+}
+
+// Everything a LAN client draws, sent by the host as one line of text
+#[derive(Debug)]
+pub struct Snapshot {
+    pub score: (usize, usize),
+    pub spectators: usize,
+    // Set while the host shows its game over screen
+    pub outcome: Option<MatchOutcome>,
+    // First player flag, position, height scale and tilt
+    pub paddles: Vec<(bool, Vec2, f32, f32)>,
+    // Host entity bits, position, radius and kind
+    pub balls: Vec<(u64, Vec2, f32, BallKind)>,
+}
+
+impl Snapshot {
+    pub fn to_message(&self) -> String {
+        let outcome = match &self.outcome {
+            None => "-".to_string(),
+            Some(MatchOutcome::Winner { first_player: true }) => "1".to_string(),
+            Some(MatchOutcome::Winner {
+                first_player: false,
+            }) => "2".to_string(),
+            Some(MatchOutcome::Draw) => "0".to_string(),
+            // A four player seat by name, a survival run by its time and returns
+            Some(MatchOutcome::SeatWinner { side }) => side.label().to_string(),
+            Some(MatchOutcome::SurvivalOver { time, returns }) => format!("{time}/{returns}"),
+        };
+        let mut message = format!(
+            "S {} {} {outcome} {}",
+            self.score.0, self.score.1, self.spectators
+        );
+        for (first_player, position, scale, tilt) in &self.paddles {
+            let flag = u8::from(*first_player);
+            message += &format!(" P {flag} {} {} {scale} {tilt}", position.x, position.y);
+        }
+        for (id, position, radius, kind) in &self.balls {
+            let kind = match kind {
+                BallKind::Normal => "n",
+                BallKind::Point => "p",
+                BallKind::Golden => "g",
+            };
+            message += &format!(" B {id} {} {} {radius} {kind}", position.x, position.y);
+        }
+        message
+    }
+
+    pub fn parse(message: &str) -> Option<Self> {
+        let mut tokens = message.split_whitespace();
+        if tokens.next()? != "S" {
+            return None;
+        }
+        let score = (tokens.next()?.parse().ok()?, tokens.next()?.parse().ok()?);
+        let outcome = match tokens.next()? {
+            "-" => None,
+            "1" => Some(MatchOutcome::Winner { first_player: true }),
+            "2" => Some(MatchOutcome::Winner {
+                first_player: false,
+            }),
+            "0" => Some(MatchOutcome::Draw),
+            token => match token.split_once('/') {
+                Some((time, returns)) => Some(MatchOutcome::SurvivalOver {
+                    time: time.parse::<f32>().ok().filter(|time| time.is_finite())?,
+                    returns: returns.parse().ok()?,
+                }),
+                None => {
+                    let side = Side::SEATS.into_iter().find(|side| side.label() == token)?;
+                    Some(MatchOutcome::SeatWinner { side })
+                }
+            },
+        };
+        let spectators = tokens.next()?.parse().ok()?;
+        let mut snapshot = Self {
+            score,
+            spectators,
+            outcome,
+            paddles: vec![],
+            balls: vec![],
+        };
+        while let Some(tag) = tokens.next() {
+            match tag {
+                "P" => {
+                    let first_player = tokens.next()? == "1";
+                    let position = next_vec2(&mut tokens)?;
+                    let (scale, tilt) = (next_finite(&mut tokens)?, next_finite(&mut tokens)?);
+                    snapshot.paddles.push((first_player, position, scale, tilt));
+                }
+                "B" => {
+                    let id = tokens.next()?.parse().ok()?;
+                    let position = next_vec2(&mut tokens)?;
+                    let radius = next_finite(&mut tokens)?;
+                    let kind = match tokens.next()? {
+                        "n" => BallKind::Normal,
+                        "p" => BallKind::Point,
+                        "g" => BallKind::Golden,
+                        _ => return None,
+                    };
+                    snapshot.balls.push((id, position, radius, kind));
+                }
+                _ => return None,
+            }
+        }
+        Some(snapshot)
+    }
+}
+
+fn next_finite<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<f32> {
+    tokens
+        .next()?
+        .parse::<f32>()
+        .ok()
+        .filter(|value| value.is_finite())
+}
+
+fn next_vec2<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<Vec2> {
+    Some(Vec2::new(next_finite(tokens)?, next_finite(tokens)?))
+}
+
+pub fn open_socket(port: u16) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+pub fn resolve_address(address: &str) -> io::Result<SocketAddr> {
+    address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address found"))
+}
+
+fn send_message(socket: &UdpSocket, to: SocketAddr, message: &str) {
+    if let Err(error) = socket.send_to(message.as_bytes(), to) {
+        warn!("Could not send to {to}: {error}");
+    }
+}
+
+// Everything that arrived since the last frame, the socket never blocks
+fn receive_messages(socket: &UdpSocket) -> Vec<(SocketAddr, String)> {
+    let mut buffer = vec![0; NET_BUFFER_SIZE];
+    let mut messages = vec![];
+    while let Ok((length, from)) = socket.recv_from(&mut buffer) {
+        messages.push((
+            from,
+            String::from_utf8_lossy(&buffer[..length]).into_owned(),
+        ));
+    }
+    messages
+}
+
+fn close_network(
+    mut commands: Commands,
+    host: Option<Res<NetHost>>,
+    client: Option<Res<NetClient>>,
+    mut spectator_count: ResMut<SpectatorCount>,
+) {
+    spectator_count.0 = 0;
+    if let Some(host) = host {
+        let spectators = host.spectators.iter().map(|(address, _)| *address);
+        for peer in host.peer.into_iter().chain(spectators) {
+            send_message(&host.socket, peer, "BYE");
+        }
+        commands.remove_resource::<NetHost>();
+    }
+    if let Some(client) = client {
+        send_message(&client.socket, client.host, "BYE");
+        commands.remove_resource::<NetClient>();
+    }
+}
+
+fn edit_join_address(
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut config: ResMut<GameConfig>,
+) {
+    let typed = characters
+        .iter()
+        .map(|event| event.char)
+        .filter(|c| c.is_ascii_alphanumeric() || ".:-[]".contains(*c))
+        .collect::<String>();
+    if !typed.is_empty() {
+        config.join_address.push_str(&typed);
+        // Long enough for any host name and port
+        config.join_address.truncate(64);
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        config.join_address.pop();
+    }
+}
+
+fn update_spectator_hud(
+    spectators: Res<SpectatorCount>,
+    mut texts: Query<&mut Text, With<SpectatorHud>>,
+) {
+    for mut text in texts.iter_mut() {
+        let label = match spectators.0 {
+            0 => String::new(),
+            1 => "1 spectator".to_string(),
+            count => format!("{count} spectators"),
+        };
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+}
+
+fn update_net_status(net_status: Res<NetStatus>, mut texts: Query<&mut Text, With<NetStatusText>>) {
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = net_status.0.clone();
+    }
+}
+
+fn accept_client(
+    mut host: ResMut<NetHost>,
+    mut net_status: ResMut<NetStatus>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (from, message) in receive_messages(&host.socket) {
+        if message == "HELLO" {
+            send_message(&host.socket, from, "WELCOME");
+            host.peer = Some(from);
+            host.silence = 0.;
+            net_status.0.clear();
+            next_state.set(GameState::Playing);
+        }
+    }
+}
+
+fn join_host(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut client: ResMut<NetClient>,
+    mut net_status: ResMut<NetStatus>,
+    mut until_hello: Local<f32>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    client.silence += time.delta_seconds();
+    if client.silence > NET_TIMEOUT_SECONDS {
+        net_status.0 = format!("No answer from {}", client.host);
+        commands.remove_resource::<NetClient>();
+        return;
+    }
+    *until_hello -= time.delta_seconds();
+    if *until_hello <= 0. {
+        let request = if client.spectating { "WATCH" } else { "HELLO" };
+        send_message(&client.socket, client.host, request);
+        *until_hello = NET_HELLO_INTERVAL;
+    }
+    for (from, message) in receive_messages(&client.socket) {
+        if from == client.host && message == "WELCOME" {
+            client.silence = 0.;
+            net_status.0.clear();
+            next_state.set(GameState::Playing);
+        }
+    }
+}
+
+fn exchange_with_client(
+    time: Res<Time>,
+    mut host: ResMut<NetHost>,
+    mut net_status: ResMut<NetStatus>,
+    mut spectator_count: ResMut<SpectatorCount>,
+    state: Res<State<GameState>>,
+    outcome: Option<Res<MatchOutcome>>,
+    score: Res<Score>,
+    paddles: Query<(&Position, &Rotation, &Paddle, &PaddleSize)>,
+    balls: Query<(Entity, &Position, &Ball)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(peer) = host.peer else {
+        return;
+    };
+    host.silence += time.delta_seconds();
+    for (_, silence) in host.spectators.iter_mut() {
+        *silence += time.delta_seconds();
+    }
+    for (from, message) in receive_messages(&host.socket) {
+        if from != peer {
+            // Anyone else may only watch, and keeps asking to stay
+            if message != "WATCH" {
+                continue;
+            }
+            match host
+                .spectators
+                .iter_mut()
+                .find(|(address, _)| *address == from)
+            {
+                Some((_, silence)) => *silence = 0.,
+                None => {
+                    info!("Spectator {from} joined");
+                    send_message(&host.socket, from, "WELCOME");
+                    host.spectators.push((from, 0.));
+                }
+            }
+            continue;
+        }
+        host.silence = 0.;
+        let mut tokens = message.split_whitespace();
+        match tokens.next() {
+            // The welcome got lost, the client is still asking
+            Some("HELLO") => send_message(&host.socket, peer, "WELCOME"),
+            Some("I") => {
+                host.remote_target = next_vec2(&mut tokens);
+            }
+            Some("BYE") => {
+                net_status.0 = "The other player left".to_string();
+                next_state.set(GameState::Menu);
+            }
+            _ => {}
+        }
+    }
+    if host.silence > NET_TIMEOUT_SECONDS {
+        net_status.0 = format!("Lost connection to {peer}");
+        next_state.set(GameState::Menu);
+        return;
+    }
+    host.spectators
+        .retain(|(_, silence)| *silence <= NET_TIMEOUT_SECONDS);
+    if spectator_count.0 != host.spectators.len() {
+        spectator_count.0 = host.spectators.len();
+    }
+
+    let snapshot = Snapshot {
+        score: (score.first_player(), score.second_player()),
+        spectators: host.spectators.len(),
+        outcome: outcome
+            .filter(|_| *state.get() == GameState::GameOver)
+            .map(|outcome| MatchOutcome::clone(&outcome)),
+        paddles: paddles
+            .iter()
+            .map(|(position, rotation, paddle, size)| {
+                (
+                    paddle.first_player,
+                    position.0,
+                    size.scale + size.bonus,
+                    rotation.as_radians(),
+                )
+            })
+            .collect(),
+        balls: balls
+            .iter()
+            .map(|(entity, position, ball)| {
+                (entity.to_bits(), position.0, ball.get_radius(), ball.kind)
+            })
+            .collect(),
+    };
+    let message = snapshot.to_message();
+    let spectators = host.spectators.iter().map(|(address, _)| *address);
+    for to in std::iter::once(peer).chain(spectators) {
+        send_message(&host.socket, to, &message);
+    }
+}
+
+fn exchange_with_host(
+    mut commands: Commands,
+    input: PlayerInput,
+    mut client: ResMut<NetClient>,
+    mut net_status: ResMut<NetStatus>,
+    mut spectator_count: ResMut<SpectatorCount>,
+    state: Res<State<GameState>>,
+    mut score: ResMut<Score>,
+    ball_assets: Res<BallAssets>,
+    cosmetics: Res<CosmeticsConfig>,
+    mut paddles: Query<(
+        &mut Position,
+        &mut Rotation,
+        &mut LinearVelocity,
+        &mut PaddleSize,
+        &Paddle,
+    )>,
+    mut remote_balls: Query<(Entity, &RemoteBall, &mut Transform)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    client.silence += input.time.delta_seconds();
+    if client.silence > NET_TIMEOUT_SECONDS {
+        net_status.0 = format!("Lost connection to {}", client.host);
+        next_state.set(GameState::Menu);
+        return;
+    }
+
+    // The client always plays the second paddle
+    let own_paddle = paddles
+        .iter()
+        .find(|(.., paddle)| !paddle.first_player)
+        .map(|(position, ..)| position.0);
+    let target = own_paddle
+        .filter(|_| *state.get() == GameState::Playing)
+        .and_then(|position| input.target(position));
+    let message = match target {
+        _ if client.spectating => "WATCH".to_string(),
+        Some(target) => format!("I {} {}", target.x, target.y),
+        None => "I -".to_string(),
+    };
+    send_message(&client.socket, client.host, &message);
+
+    let mut latest = None;
+    for (from, message) in receive_messages(&client.socket) {
+        if from != client.host {
+            continue;
+        }
+        client.silence = 0.;
+        if message == "BYE" {
+            net_status.0 = "The host left".to_string();
+            next_state.set(GameState::Menu);
+            return;
+        }
+        latest = Snapshot::parse(&message).or(latest);
+    }
+    let Some(snapshot) = latest else {
+        return;
+    };
+
+    if spectator_count.0 != snapshot.spectators {
+        spectator_count.0 = snapshot.spectators;
+    }
+    if (score.first_player(), score.second_player()) != snapshot.score {
+        score.set(Side::Left, snapshot.score.0);
+        score.set(Side::Right, snapshot.score.1);
+    }
+    for (mut position, mut rotation, mut velocity, mut size, paddle) in paddles.iter_mut() {
+        let remote = snapshot
+            .paddles
+            .iter()
+            .find(|(first_player, ..)| *first_player == paddle.first_player);
+        let Some((_, remote_position, scale, tilt)) = remote else {
+            continue;
+        };
+        position.0 = *remote_position;
+        *rotation = Rotation::from_radians(*tilt);
+        velocity.0 = Vec2::ZERO;
+        if size.scale != *scale {
+            size.scale = *scale;
+        }
+    }
+    for (entity, remote, mut transform) in remote_balls.iter_mut() {
+        match snapshot.balls.iter().find(|(id, ..)| *id == remote.0) {
+            Some((_, position, ..)) => {
+                transform.translation = position.extend(transform.translation.z)
+            }
+            None => commands.entity(entity).despawn_recursive(),
+        }
+    }
+    for (id, position, radius, kind) in &snapshot.balls {
+        if remote_balls.iter().any(|(_, remote, _)| remote.0 == *id) {
+            continue;
+        }
+        // The host's returns aren't sent, so every ball keeps this player's first skin
+        let skin = cosmetics.ball_skins[0];
+        commands.spawn((
+            SpriteBundle {
+                texture: skin.texture(&ball_assets),
+                sprite: Sprite {
+                    color: skin.color(*kind),
+                    custom_size: Some(Vec2::ONE * (radius * 2.)),
+                    ..default()
+                },
+                transform: Transform::from_translation(position.extend(3.)),
+                ..default()
+            },
+            RemoteBall(*id),
+            MatchEntity,
+        ));
+    }
+
+    match (snapshot.outcome, state.get()) {
+        (Some(outcome), GameState::Playing) => {
+            commands.insert_resource(outcome);
+            next_state.set(GameState::GameOver);
+        }
+        (None, GameState::GameOver) => next_state.set(GameState::Playing),
+        _ => {}
+    }
+}
+
+fn move_network_paddles(
+    fixed_time: Res<FixedTime>,
+    host: Option<Res<NetHost>>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    mut paddles: Query<(
+        &mut LinearVelocity,
+        &mut Position,
+        &PaddleBounds,
+        &Paddle,
+        &PaddleController,
+    )>,
+) {
+    let target = host.and_then(|host| host.remote_target);
+    for (mut velocity, mut paddle_position, bounds, paddle, controller) in paddles.iter_mut() {
+        if *controller != PaddleController::Network {
+            continue;
+        }
+        ReplayRecorder::record_target(&mut recorder, paddle, target);
+        let Some(position) = target else {
+            *velocity = LinearVelocity(Vec2::ZERO);
+            continue;
+        };
+        steer_paddle(
+            bounds,
+            position,
+            PADDLE_SPEED,
+            fixed_time.period.as_secs_f32(),
+            &mut paddle_position.0,
+            &mut velocity.0,
+        );
+    }
+}
+
+// FNV-1a, unlike the std hashers its output is fixed across platforms and releases
+struct StateHasher(pub u64);
+
+// Starts from the FNV-1a offset basis
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl StateHasher {
+    pub fn write_u64(&mut self, value: u64) {
+        for byte in value.to_le_bytes() {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn write_vec2(&mut self, value: Vec2) {
+        self.write_u64(value.x.to_bits() as u64);
+        self.write_u64(value.y.to_bits() as u64);
+    }
+}
+
+// Balls and paddles are sorted by their state rather than by entity so the ECS iteration
+// order, and entity ids that differ between peers, never change the result
+pub fn simulation_checksum(
+    mut balls: Vec<(Vec2, Vec2, usize)>,
+    mut paddles: Vec<(Side, Vec2)>,
+    score: &Score,
+) -> u64 {
+    let bits = |v: Vec2| (v.x.to_bits(), v.y.to_bits());
+    balls.sort_by_key(|(position, velocity, points)| (bits(*position), bits(*velocity), *points));
+    // Co-op has two paddles on the left, their positions keep them apart
+    paddles.sort_by_key(|(side, position)| {
+        (
+            Side::SEATS.iter().position(|seat| seat == side),
+            bits(*position),
+        )
+    });
+
+    let mut hasher = StateHasher::default();
+    hasher.write_u64(balls.len() as u64);
+    for (position, velocity, points) in balls {
+        hasher.write_vec2(position);
+        hasher.write_vec2(velocity);
+        hasher.write_u64(points as u64);
+    }
+    for (_, position) in paddles {
+        hasher.write_vec2(position);
+    }
+    hasher.write_u64(score.first_player() as u64);
+    hasher.write_u64(score.second_player() as u64);
+    // Only four player matches score on the other seats, two player checksums stay as they were
+    for side in [Side::Top, Side::Bottom] {
+        if let Some(points) = score.0.get(&side) {
+            hasher.write_u64(*points as u64);
+        }
+    }
+    hasher.0
+}
+
+pub fn update_simulation_checksum(
+    balls: Query<(&Position, &LinearVelocity, &Ball)>,
+    paddles: Query<(&Position, &Paddle)>,
+    score: Res<Score>,
+    mut checksum: ResMut<SimulationChecksum>,
+) {
+    checksum.value = simulation_checksum(
+        balls
+            .iter()
+            .map(|(position, velocity, ball)| (position.0, velocity.0, ball.points))
+            .collect(),
+        paddles
+            .iter()
+            .map(|(position, paddle)| (paddle.side.clone(), position.0))
+            .collect(),
+        &score,
+    );
+    checksum.tick += 1;
+}
@@ -0,0 +1,585 @@
+use crate::ball::MAX_BOUNCE_ANGLE;
+use crate::config::{GameConfig, Opponent};
+use crate::cosmetics::CosmeticsConfig;
+use crate::net::{NetClient, NetHost};
+use crate::power_ups::apply_paddle_effects;
+use crate::prelude::*;
+use crate::ui::FadeOut;
+
+// Paddle sizes and bounds, and what happens to them outside of player input
+pub struct PaddlePlugin;
+
+impl Plugin for PaddlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnExit(GameState::GameOver), level_paddles)
+            .add_systems(OnExit(GameState::Intermission), level_paddles)
+            .add_systems(
+                FixedUpdate,
+                clamp_tilted_paddles
+                    .in_set(StepSet::PaddleEffects)
+                    .after(apply_paddle_effects),
+            )
+            .add_systems(
+                Update,
+                (
+                    shrink_scoring_paddle.in_set(GoalSet::React),
+                    spawn_paddle_afterimages.in_set(SimulationSet),
+                    rebuild_paddle_colliders,
+                    update_charge_meters,
+                ),
+            );
+    }
+}
+
+pub const PADDLE_SIZE: Vec2 = Vec2 { x: 15., y: 60. };
+
+pub const PADDLE_SPEED: f32 = 5000.;
+
+// Paddles tilt either way up to this many radians, shaping where returns go
+pub const MAX_PADDLE_TILT: f32 = PI / 6.;
+// Radians per second while a tilt key is held, and per mouse wheel notch
+pub const PADDLE_TILT_SPEED: f32 = 2.;
+pub const PADDLE_TILT_STEP: f32 = PI / 36.;
+
+// Fast paddles leave fading copies of themselves behind
+const AFTERIMAGE_SPEED_THRESHOLD: f32 = 1500.;
+const AFTERIMAGE_INTERVAL: f32 = 0.03;
+const AFTERIMAGE_LIFETIME: f32 = 0.25;
+const MAX_AFTERIMAGES: usize = 32;
+
+pub const SMASH_CHARGE_TIME: f32 = 1.;
+// How long a let go charge waits for the ball to arrive
+pub const SMASH_WINDOW: f32 = 0.2;
+// Extra return speed of a full charge
+pub const SMASH_IMPULSE: f32 = 900.;
+const CHARGE_METER_WIDTH: f32 = 4.;
+
+// How far the arc bulges and the notch prongs stick out of a shaped paddle's face
+const SHAPE_DEPTH: f32 = 8.;
+const ARC_SLICES: usize = 4;
+// Points along the arc collider's curve
+const ARC_POINTS: usize = 8;
+// Return angles off the arc, over a flat paddle's
+const ARC_BOUNCE_SCALE: f32 = 1.25;
+// Share of the paddle's length open between the notch prongs
+const NOTCH_WIDTH: f32 = 0.5;
+// Return angles of balls caught in the notch, over a flat paddle's
+const NOTCH_BOUNCE_SCALE: f32 = 0.25;
+
+// Where a paddle is put back between the games of a match
+#[derive(Component)]
+pub struct PaddleStart(pub Vec2);
+
+#[derive(Component, Debug)]
+pub struct Paddle {
+    pub first_player: bool,
+    pub side: Side,
+}
+
+// Who steers a paddle, every controller has its own movement system
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaddleController {
+    // The first player's input sources, in priority order
+    Player,
+    // The second keyboard layout of a hotseat match
+    HotseatPlayer,
+    // A LAN client steering toward the targets it sends
+    Network,
+    Ai,
+    // Steers toward the targets saved in the replay being watched
+    Replay,
+}
+
+impl PaddleController {
+    pub fn for_paddle(first_player: bool, config: &GameConfig, demo: bool, hosting: bool) -> Self {
+        // The AI plays both sides of a demo match
+        if demo {
+            PaddleController::Ai
+        } else if first_player {
+            PaddleController::Player
+        } else if hosting {
+            PaddleController::Network
+        } else if config.opponent == Opponent::Human || config.mode == GameMode::Coop {
+            // A co-op partner is always the second player
+            PaddleController::HotseatPlayer
+        } else {
+            PaddleController::Ai
+        }
+    }
+}
+
+// Height multiplier over PADDLE_SIZE, the sprite and collider are rebuilt when it changes
+#[derive(Component, Clone, Copy, Debug)]
+pub struct PaddleSize {
+    pub scale: f32,
+    // Temporary growth from status effects, kept apart so the goal shrink floor ignores it
+    pub bonus: f32,
+}
+
+impl Default for PaddleSize {
+    fn default() -> Self {
+        Self {
+            scale: 1.,
+            bonus: 0.,
+        }
+    }
+}
+
+impl PaddleSize {
+    pub fn extent(&self) -> Vec2 {
+        Vec2::new(PADDLE_SIZE.x, PADDLE_SIZE.y * (self.scale + self.bonus))
+    }
+}
+
+// Past min/max the paddle is stopped and snapped onto the matching wall
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct PaddleBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub min_wall: Vec2,
+    pub max_wall: Vec2,
+    // Where the paddle starts, and where the AI falls back to
+    pub home: Vec2,
+}
+
+impl PaddleBounds {
+    pub fn mirrored(&self) -> Self {
+        Self {
+            min: Vec2::new(-self.max.x, self.min.y),
+            max: Vec2::new(-self.min.x, self.max.y),
+            min_wall: Vec2::new(-self.max_wall.x, self.min_wall.y),
+            max_wall: Vec2::new(-self.min_wall.x, self.max_wall.y),
+            home: Vec2::new(-self.home.x, self.home.y),
+        }
+    }
+
+    // The walls moved in, for a tilted paddle that sticks out further. An axis the paddle can't
+    // move along, like the depth of the top and bottom seats, is left alone
+    pub fn shrunk(&self, by: Vec2) -> Self {
+        let by = Vec2::new(
+            if self.min.x < self.max.x { by.x } else { 0. },
+            if self.min.y < self.max.y { by.y } else { 0. },
+        );
+        Self {
+            min: self.min + by,
+            max: self.max - by,
+            min_wall: self.min_wall + by,
+            max_wall: self.max_wall - by,
+            home: self.home,
+        }
+    }
+
+    pub fn clamp(&self, position: &mut Vec2, velocity: &mut Vec2) {
+        if position.x < self.min.x && velocity.x < 0.0 {
+            velocity.x = 0.0;
+            position.x = self.min_wall.x;
+        }
+        if position.x > self.max.x && velocity.x > 0.0 {
+            velocity.x = 0.0;
+            position.x = self.max_wall.x;
+        }
+        if position.y > self.max.y && velocity.y > 0.0 {
+            velocity.y = 0.0;
+            position.y = self.max_wall.y;
+        }
+        if position.y < self.min.y && velocity.y < 0.0 {
+            velocity.y = 0.0;
+            position.y = self.min_wall.y;
+        }
+    }
+}
+
+#[derive(Component)]
+struct PaddleAfterimage;
+
+// How a paddle's face is built, picked per player. The body is the same flat box for all of
+// them, the others add to its front
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PaddleShape {
+    #[default]
+    Flat,
+    // Bulges toward the court, returns off its ends leave at wider angles
+    Arc,
+    // A prong at each end, a ball caught between them goes back nearly straight
+    Notched,
+}
+
+impl PaddleShape {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "flat" => Some(Self::Flat),
+            "arc" => Some(Self::Arc),
+            "notched" => Some(Self::Notched),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Flat => Self::Arc,
+            Self::Arc => Self::Notched,
+            Self::Notched => Self::Flat,
+        }
+    }
+
+    // What's added in front of a paddle of this extent, as centers and sizes with x toward the
+    // court and y along the paddle. The arc is drawn as slices, each shorter than the one behind
+    pub fn face_parts(&self, extent: Vec2) -> Vec<(Vec2, Vec2)> {
+        let front = extent.x / 2.;
+        match self {
+            Self::Flat => vec![],
+            Self::Arc => {
+                let depth = SHAPE_DEPTH / ARC_SLICES as f32;
+                (0..ARC_SLICES)
+                    .map(|slice| {
+                        let middle = slice as f32 + 0.5;
+                        let length = extent.y * (1. - middle / ARC_SLICES as f32).sqrt();
+                        (
+                            Vec2::new(front + depth * middle, 0.),
+                            Vec2::new(depth, length),
+                        )
+                    })
+                    .collect()
+            }
+            Self::Notched => {
+                let prong = extent.y * (1. - NOTCH_WIDTH) / 2.;
+                [1., -1.]
+                    .map(|end| {
+                        let y = end * (extent.y - prong) / 2.;
+                        (
+                            Vec2::new(front + SHAPE_DEPTH / 2., y),
+                            Vec2::new(SHAPE_DEPTH, prong),
+                        )
+                    })
+                    .to_vec()
+            }
+        }
+    }
+
+    // The catch assist margin grows every part of the collider alike
+    pub fn collider(&self, side: &Side, extent: Vec2, margin: f32) -> Collider {
+        let body = extent + Vec2::splat(margin * 2.);
+        match self {
+            Self::Flat => {
+                let size = side.oriented(body);
+                Collider::cuboid(size.x, size.y)
+            }
+            // The hull of the back corners and the curve the slices step along
+            Self::Arc => {
+                let back = -body.x / 2.;
+                let mut points = vec![Vec2::new(back, body.y / 2.), Vec2::new(back, -body.y / 2.)];
+                points.extend((0..=ARC_POINTS).map(|point| {
+                    let along = point as f32 / ARC_POINTS as f32 * 2. - 1.;
+                    let bulge = SHAPE_DEPTH * (1. - along * along);
+                    Vec2::new(body.x / 2. + bulge, along * body.y / 2.)
+                }));
+                let points = points
+                    .into_iter()
+                    .map(|point| side.face_point(point))
+                    .collect();
+                Collider::convex_hull(points).unwrap_or_else(|| {
+                    let size = side.oriented(body);
+                    Collider::cuboid(size.x, size.y)
+                })
+            }
+            Self::Notched => {
+                let mut shapes = vec![(Vec2::ZERO, body)];
+                shapes.extend(
+                    self.face_parts(extent)
+                        .into_iter()
+                        .map(|(center, size)| (center, size + Vec2::splat(margin * 2.))),
+                );
+                Collider::compound(
+                    shapes
+                        .into_iter()
+                        .map(|(center, size)| {
+                            let size = side.oriented(size);
+                            (
+                                Position(side.face_point(center)),
+                                Rotation::default(),
+                                Collider::cuboid(size.x, size.y),
+                            )
+                        })
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    // Return angle for a ball met this far along the face, -1 at the bottom end and 1 at the top
+    pub fn bounce_angle(&self, offset: f32) -> f32 {
+        match self {
+            Self::Flat => offset * MAX_BOUNCE_ANGLE,
+            Self::Arc => offset * MAX_BOUNCE_ANGLE * ARC_BOUNCE_SCALE,
+            Self::Notched if offset.abs() < NOTCH_WIDTH => {
+                offset * MAX_BOUNCE_ANGLE * NOTCH_BOUNCE_SCALE
+            }
+            Self::Notched => offset * MAX_BOUNCE_ANGLE,
+        }
+    }
+}
+
+// A piece of a shaped paddle's face, indexing PaddleShape::face_parts
+#[derive(Component)]
+struct PaddlePart(pub usize);
+
+// The first player's smash, charged by holding the right mouse button. Let go of just before a
+// hit, the return gets an extra push
+#[derive(Component, Default, Debug)]
+pub struct Charge {
+    // 0 to 1, full after SMASH_CHARGE_TIME held
+    pub level: f32,
+    // The charge let go of, and the seconds left for a ball to meet it
+    pub released: f32,
+    pub window: f32,
+}
+
+#[derive(Component)]
+struct ChargeMeter;
+
+pub fn spawn_paddle(
+    commands: &mut Commands,
+    paddle: Paddle,
+    controller: PaddleController,
+    bounds: PaddleBounds,
+    shape: PaddleShape,
+    cosmetics: &CosmeticsConfig,
+    colorblind: bool,
+) {
+    let transform = Transform::from_translation(bounds.home.extend(5.));
+    let color = cosmetics.paddle_skin(&paddle).color(&paddle, colorblind);
+    let size = paddle.side.oriented(PADDLE_SIZE);
+    // Paddles on the top and bottom goals cross the net
+    let masks = if paddle.side.horizontal() {
+        vec![Layer::Ball, Layer::Wall]
+    } else {
+        vec![Layer::Ball, Layer::Wall, Layer::Net]
+    };
+    let charges = paddle.first_player
+        && matches!(
+            controller,
+            PaddleController::Player | PaddleController::Replay
+        );
+    let mut entity = commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(size),
+                ..default()
+            },
+            transform,
+            ..default()
+        },
+        PreviousPosition(transform.translation.xy()),
+        RigidBody::Kinematic,
+        shape.collider(&paddle.side, PADDLE_SIZE, 0.),
+        CollisionLayers::new([Layer::Paddle], masks),
+        Restitution::new(0.8),
+        (paddle, controller, bounds, shape),
+        PaddleSize::default(),
+        PaddleStart(transform.translation.xy()),
+        MatchEntity,
+    ));
+    // Placed and sized with the collider in rebuild_paddle_colliders
+    entity.with_children(|builder| {
+        for part in 0..shape.face_parts(PADDLE_SIZE).len() {
+            builder.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::ZERO),
+                        ..default()
+                    },
+                    ..default()
+                },
+                PaddlePart(part),
+            ));
+        }
+    });
+    // The smash meter sits behind the paddle and stays empty until the charge starts
+    if charges {
+        entity.insert(Charge::default()).with_children(|builder| {
+            builder.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::WHITE.with_a(0.7),
+                        custom_size: Some(Vec2::ZERO),
+                        ..default()
+                    },
+                    ..default()
+                },
+                ChargeMeter,
+            ));
+        });
+    }
+}
+
+fn update_charge_meters(
+    paddles: Query<(&Charge, &PaddleSize, &Paddle, &Children)>,
+    mut meters: Query<(&mut Sprite, &mut Transform), With<ChargeMeter>>,
+) {
+    for (charge, size, paddle, children) in paddles.iter() {
+        for child in children.iter() {
+            let Ok((mut sprite, mut transform)) = meters.get_mut(*child) else {
+                continue;
+            };
+            let length = size.extent().y * charge.level;
+            sprite.custom_size = Some(paddle.side.oriented(Vec2::new(CHARGE_METER_WIDTH, length)));
+            sprite.color = if charge.level >= 1. {
+                Color::GOLD
+            } else {
+                Color::WHITE.with_a(0.7)
+            };
+            let behind = -paddle.side.inward() * (PADDLE_SIZE.x / 2. + CHARGE_METER_WIDTH * 2.);
+            transform.translation = behind.extend(0.1);
+        }
+    }
+}
+
+// The catch assist only grows the collider, the sprite keeps its size so near-misses still count as hits
+fn rebuild_paddle_colliders(
+    config: Res<GameConfig>,
+    host: Option<Res<NetHost>>,
+    client: Option<Res<NetClient>>,
+    mut paddles: Query<(
+        Ref<PaddleSize>,
+        &Paddle,
+        &PaddleShape,
+        &mut Collider,
+        &mut Sprite,
+        Option<&Children>,
+    )>,
+    mut parts: Query<(&PaddlePart, &mut Sprite, &mut Transform), Without<Paddle>>,
+) {
+    let margin = config.catch_assist_margin(host.is_some() || client.is_some());
+    for (size, paddle, shape, mut collider, mut sprite, children) in paddles.iter_mut() {
+        if !config.is_changed() && !size.is_changed() {
+            continue;
+        }
+        let margin = if paddle.first_player { margin } else { 0. };
+        sprite.custom_size = Some(paddle.side.oriented(size.extent()));
+        *collider = shape.collider(&paddle.side, size.extent(), margin);
+        let face_parts = shape.face_parts(size.extent());
+        for child in children.iter().flat_map(|children| children.iter()) {
+            let Ok((part, mut sprite, mut transform)) = parts.get_mut(*child) else {
+                continue;
+            };
+            let Some((center, part_size)) = face_parts.get(part.0) else {
+                continue;
+            };
+            sprite.custom_size = Some(paddle.side.oriented(*part_size));
+            transform.translation = paddle.side.face_point(*center).extend(0.);
+        }
+    }
+}
+
+// Runs after every other paddle movement, so whatever steered the paddle it stays in its box
+fn clamp_tilted_paddles(
+    mut paddles: Query<(
+        &mut Position,
+        &mut LinearVelocity,
+        &Rotation,
+        &PaddleBounds,
+        &PaddleSize,
+        &Paddle,
+    )>,
+) {
+    for (mut position, mut velocity, rotation, bounds, size, paddle) in paddles.iter_mut() {
+        if rotation.sin() == 0. {
+            continue;
+        }
+        let half = paddle.side.oriented(size.extent()) / 2.;
+        let (sin, cos) = (rotation.sin().abs(), rotation.cos().abs());
+        let rotated = Vec2::new(cos * half.x + sin * half.y, sin * half.x + cos * half.y);
+        bounds
+            .shrunk((rotated - half).max(Vec2::ZERO))
+            .clamp(&mut position.0, &mut velocity.0);
+    }
+}
+
+// Every game starts with level paddles
+fn level_paddles(mut paddles: Query<&mut Rotation, With<Paddle>>) {
+    for mut rotation in paddles.iter_mut() {
+        *rotation = Rotation::default();
+    }
+}
+
+fn shrink_scoring_paddle(
+    mut goal_scored: EventReader<GoalScored>,
+    config: Res<GameConfig>,
+    mut paddles: Query<(&Paddle, &mut PaddleSize)>,
+) {
+    if config.shrink_per_goal <= 0. || config.mode.lone_player() {
+        goal_scored.clear();
+        return;
+    }
+    for goal in goal_scored.iter() {
+        for (paddle, mut size) in paddles.iter_mut() {
+            if Some(&paddle.side) == goal.scorer.as_ref() {
+                size.scale = (size.scale - config.shrink_per_goal).max(config.shrink_floor);
+            }
+        }
+    }
+}
+
+fn spawn_paddle_afterimages(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut since_last: Local<f32>,
+    paddles: Query<(&Transform, &LinearVelocity, &Sprite), With<Paddle>>,
+    afterimages: Query<(), With<PaddleAfterimage>>,
+) {
+    if config.reduced_motion {
+        return;
+    }
+    *since_last += time.delta_seconds();
+    if *since_last < AFTERIMAGE_INTERVAL {
+        return;
+    }
+    *since_last = 0.;
+
+    let mut count = afterimages.iter().count();
+    for (transform, velocity, sprite) in paddles.iter() {
+        if velocity.length() < AFTERIMAGE_SPEED_THRESHOLD || count >= MAX_AFTERIMAGES {
+            continue;
+        }
+        let start_alpha = 0.4;
+        let mut afterimage_transform = *transform;
+        afterimage_transform.translation.z -= 1.;
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: sprite.color.with_a(start_alpha),
+                    custom_size: sprite.custom_size,
+                    ..default()
+                },
+                transform: afterimage_transform,
+                ..default()
+            },
+            FadeOut {
+                timer: Timer::from_seconds(AFTERIMAGE_LIFETIME, TimerMode::Once),
+                start_alpha,
+            },
+            PaddleAfterimage,
+            MatchEntity,
+        ));
+        count += 1;
+    }
+}
+
+pub fn steer_paddle(
+    bounds: &PaddleBounds,
+    target: Vec2,
+    max_speed: f32,
+    delta_seconds: f32,
+    position: &mut Vec2,
+    velocity: &mut Vec2,
+) {
+    let to_target_position = target - *position;
+    *velocity = to_target_position.normalize_or_zero()
+        * max_speed.min(to_target_position.length() / delta_seconds);
+
+    // keeping paddle within bounds
+    bounds.clamp(position, velocity);
+}
@@ -0,0 +1,670 @@
+use crate::arena::{Court, Wall};
+use crate::ball::{
+    serve_direction, spawn_ball_at, Ball, BallAssets, BallKind, BallSpawnConfig, BallSprite,
+    LastTouchedBy, PointBallCount, BALL_RADIUS,
+};
+use crate::config::{GameConfig, Opponent};
+use crate::cosmetics::CosmeticsConfig;
+use crate::input::{key_name, ActiveGamepad, KeyBindings};
+use crate::paddle::{Paddle, PaddleBounds, PaddleSize};
+use crate::prelude::*;
+use crate::replay::{ReplayPlayback, ReplayRecorder};
+use crate::score::{Score, RALLY_BONUS_POINTS};
+
+// Power-ups on the court, the status effects they give, the handicap and defensive walls
+pub struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnExit(GameState::GameOver), clear_status_effects)
+            .add_systems(OnExit(GameState::Intermission), clear_status_effects)
+            .add_systems(
+                FixedUpdate,
+                apply_paddle_effects.in_set(StepSet::PaddleEffects),
+            )
+            .add_systems(
+                Update,
+                (
+                    earn_defensive_walls.in_set(HitSet::React),
+                    collect_power_ups.in_set(HitSet::Collect),
+                    (
+                        (
+                            raise_defensive_walls,
+                            expire_defensive_walls,
+                            update_defensive_wall_hud,
+                        ),
+                        (
+                            grant_handicap,
+                            tick_status_effects,
+                            (grow_paddles, resize_oversized_balls, update_paddle_buff_hud),
+                        )
+                            .chain(),
+                        power_up_spawner,
+                    )
+                        .in_set(SimulationSet),
+                ),
+            );
+    }
+}
+
+const DEFENSIVE_WALL_DURATION: f32 = 4.;
+const DEFENSIVE_WALL_WIDTH: f32 = 8.;
+// The AI raises its wall when a ball heading for its goal gets this close
+const DEFENSIVE_WALL_AI_DISTANCE: f32 = 200.;
+const HANDICAP_DURATION: f32 = 5.;
+// Extra paddle height, as a fraction of PADDLE_SIZE, while the handicap buff lasts
+const HANDICAP_GROWTH: f32 = 0.35;
+const POWER_UP_SIZE: f32 = 24.;
+// Power-ups stay out of this fraction of their spawn area on each side of the serve spot
+const POWER_UP_SERVE_CLEARANCE: f32 = 0.2;
+// Seconds a timed power-up lasts
+const POWER_UP_DURATION: f32 = 8.;
+const FREEZE_DURATION: f32 = 1.5;
+const REVERSE_DURATION: f32 = 5.;
+// Radius multiplier while the big ball effect lasts
+const OVERSIZED_BALL_SCALE: f32 = 1.5;
+const BIG_PADDLE_GROWTH: f32 = 0.5;
+
+// Angle between the ball that hit a multiball pickup and each of its two copies
+const MULTIBALL_SPREAD: f32 = PI / 9.;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EffectKind {
+    // Handicap growth for the trailing player
+    CatchUp,
+    BigPaddle,
+    // Returns from the paddle leave faster
+    FastShots,
+    Frozen,
+    // The paddle moves opposite to where it is steered
+    ReversedControls,
+    OversizedBall,
+}
+
+impl EffectKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::CatchUp => "Catch-up boost",
+            Self::BigPaddle => "Big paddle",
+            Self::FastShots => "Fast shots",
+            Self::Frozen => "Frozen",
+            Self::ReversedControls => "Reversed",
+            Self::OversizedBall => "Big ball",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Self::CatchUp | Self::BigPaddle => Color::LIME_GREEN,
+            Self::FastShots => Color::ORANGE_RED,
+            Self::Frozen => Color::ALICE_BLUE,
+            Self::ReversedControls => Color::VIOLET,
+            Self::OversizedBall => Color::WHITE,
+        }
+    }
+
+    // Extra paddle height as a fraction of PADDLE_SIZE
+    pub fn paddle_growth(&self) -> f32 {
+        match self {
+            Self::CatchUp => HANDICAP_GROWTH,
+            Self::BigPaddle => BIG_PADDLE_GROWTH,
+            _ => 0.,
+        }
+    }
+}
+
+// A timed mutation of its parent paddle or ball, despawned when the timer runs out
+#[derive(Component)]
+pub struct StatusEffect {
+    pub kind: EffectKind,
+    pub timer: Timer,
+}
+
+fn add_status_effect(commands: &mut Commands, target: Entity, kind: EffectKind, seconds: f32) {
+    let effect = StatusEffect {
+        kind,
+        timer: Timer::from_seconds(seconds, TimerMode::Once),
+    };
+    commands.entity(target).with_children(|builder| {
+        builder.spawn(effect);
+    });
+}
+
+pub fn has_effect(
+    children: Option<&Children>,
+    effects: &Query<&StatusEffect>,
+    kind: EffectKind,
+) -> bool {
+    children.is_some_and(|children| {
+        children
+            .iter()
+            .any(|child| effects.get(*child).is_ok_and(|effect| effect.kind == kind))
+    })
+}
+
+#[derive(Resource)]
+pub struct HandicapTimer(pub Timer);
+
+// Indexed by player like MatchStats
+#[derive(Resource, Default)]
+pub struct DefensiveWallCharges {
+    pub ready: [bool; 2],
+    pub streak: [usize; 2],
+}
+
+// Temporary barrier in front of a goal
+#[derive(Component)]
+pub struct DefensiveWall {
+    pub first_player: bool,
+    pub timer: Timer,
+}
+
+#[derive(Component)]
+pub struct DefensiveWallHud {
+    pub first_player: bool,
+}
+
+#[derive(Component)]
+pub struct PaddleBuffHud {
+    pub first_player: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum PowerUpKind {
+    DefensiveWall,
+    PointBall,
+    BigPaddle,
+    FastShots,
+    Multiball,
+    // Freezes the other player's paddle
+    Freeze,
+    // Reverses the other player's controls
+    Reverse,
+    BigBall,
+}
+
+impl PowerUpKind {
+    pub const ALL: [Self; 8] = [
+        Self::DefensiveWall,
+        Self::PointBall,
+        Self::BigPaddle,
+        Self::FastShots,
+        Self::Multiball,
+        Self::Freeze,
+        Self::Reverse,
+        Self::BigBall,
+    ];
+
+    pub fn color(&self) -> Color {
+        match self {
+            Self::DefensiveWall => Color::CYAN,
+            Self::PointBall => Color::GOLD,
+            Self::BigPaddle => Color::LIME_GREEN,
+            Self::FastShots => Color::ORANGE_RED,
+            Self::Multiball => Color::FUCHSIA,
+            Self::Freeze => EffectKind::Frozen.color(),
+            Self::Reverse => EffectKind::ReversedControls.color(),
+            Self::BigBall => Color::SILVER,
+        }
+    }
+}
+
+// Pickup lying in the arena, collected by whoever last touched the ball that hits it
+#[derive(Component)]
+struct PowerUp {
+    pub kind: PowerUpKind,
+}
+
+// Indexed by player, elapsed seconds of each player's last pickup
+#[derive(Resource, Default)]
+pub struct PowerUpCooldowns {
+    pub last_pickup: [Option<f32>; 2],
+}
+
+fn earn_defensive_walls(
+    mut paddle_hit: EventReader<PaddleHit>,
+    mut goal_scored: EventReader<GoalScored>,
+    mut charges: ResMut<DefensiveWallCharges>,
+    config: Res<GameConfig>,
+) {
+    if !goal_scored.is_empty() {
+        goal_scored.clear();
+        charges.streak = [0; 2];
+    }
+    if config.defensive_wall_streak == 0 {
+        paddle_hit.clear();
+        return;
+    }
+    for hit in paddle_hit.iter() {
+        let player = if hit.side == Side::Left { 0 } else { 1 };
+        charges.streak[player] += 1;
+        if charges.streak[player] >= config.defensive_wall_streak {
+            charges.streak[player] = 0;
+            charges.ready[player] = true;
+        }
+    }
+}
+
+fn raise_defensive_walls(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    gamepad: Res<ActiveGamepad>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    config: Res<GameConfig>,
+    mut charges: ResMut<DefensiveWallCharges>,
+    balls: Query<(&Position, &LinearVelocity), With<Ball>>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+    playback: Option<Res<ReplayPlayback>>,
+    court: Res<Court>,
+) {
+    let presses = match playback.as_ref().and_then(|playback| playback.frame()) {
+        Some(frame) => frame.wall_presses,
+        None => [
+            keys.just_pressed(bindings.defensive_wall)
+                || gamepad.just_pressed(&gamepad_buttons, GamepadButtonType::South),
+            keys.just_pressed(bindings.p2_defensive_wall),
+        ],
+    };
+    if let Some(mut recorder) = recorder {
+        recorder.frame.wall_presses = presses;
+    }
+    for first_player in [true, false] {
+        let player = if first_player { 0 } else { 1 };
+        if !charges.ready[player] {
+            continue;
+        }
+        // Both co-op players guard the left goal
+        let coop = config.mode == GameMode::Coop;
+        let goal_x = if first_player || coop {
+            -court.half_size.x
+        } else {
+            court.half_size.x
+        };
+        let activate = if first_player || coop || config.opponent == Opponent::Human {
+            presses[player]
+        } else {
+            balls.iter().any(|(position, velocity)| {
+                velocity.x.signum() == goal_x.signum()
+                    && (goal_x - position.x).abs() < DEFENSIVE_WALL_AI_DISTANCE
+            })
+        };
+        if !activate {
+            continue;
+        }
+
+        charges.ready[player] = false;
+        let x = goal_x - goal_x.signum() * DEFENSIVE_WALL_WIDTH;
+        let size = Vec2::new(DEFENSIVE_WALL_WIDTH, court.half_size.y * 2.);
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::CYAN.with_a(0.5),
+                    custom_size: Some(size),
+                    ..default()
+                },
+                transform: Transform::from_xyz(x, 0., 3.),
+                ..default()
+            },
+            RigidBody::Static,
+            Collider::cuboid(size.x, size.y),
+            CollisionLayers::new([Layer::Wall], [Layer::Ball]),
+            Restitution::new(0.8),
+            Friction::ZERO,
+            Wall,
+            DefensiveWall {
+                first_player,
+                timer: Timer::from_seconds(DEFENSIVE_WALL_DURATION, TimerMode::Once),
+            },
+            MatchEntity,
+        ));
+    }
+}
+
+fn expire_defensive_walls(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut walls: Query<(Entity, &mut DefensiveWall)>,
+) {
+    for (entity, mut wall) in walls.iter_mut() {
+        wall.timer.tick(time.delta());
+        if wall.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn update_defensive_wall_hud(
+    config: Res<GameConfig>,
+    bindings: Res<KeyBindings>,
+    charges: Res<DefensiveWallCharges>,
+    walls: Query<&DefensiveWall>,
+    mut hud: Query<(&mut Text, &DefensiveWallHud)>,
+) {
+    for (mut text, hud) in hud.iter_mut() {
+        let player = if hud.first_player { 0 } else { 1 };
+        let active = walls
+            .iter()
+            .find(|wall| wall.first_player == hud.first_player);
+        text.sections[0].value = if config.defensive_wall_streak == 0 {
+            String::new()
+        } else if let Some(wall) = active {
+            format!("Wall up {:.1}s", wall.timer.remaining_secs())
+        } else if charges.ready[player] {
+            if hud.first_player {
+                format!("Wall ready ({})", key_name(bindings.defensive_wall))
+            } else if config.opponent == Opponent::Human {
+                format!("Wall ready ({})", key_name(bindings.p2_defensive_wall))
+            } else {
+                "Wall ready".to_string()
+            }
+        } else {
+            format!(
+                "Wall {}/{}",
+                charges.streak[player], config.defensive_wall_streak
+            )
+        };
+    }
+}
+
+// Catch-up help, never given while the score is level
+fn grant_handicap(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    score: Res<Score>,
+    timer: Option<ResMut<HandicapTimer>>,
+    paddles: Query<(Entity, &Paddle)>,
+) {
+    let Some(mut timer) = timer else {
+        return;
+    };
+    if config.mode.lone_player() || !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let trailing_first_player = match score.first_player().cmp(&score.second_player()) {
+        Ordering::Less => true,
+        Ordering::Greater => false,
+        Ordering::Equal => return,
+    };
+    for (entity, paddle) in paddles.iter() {
+        if paddle.first_player == trailing_first_player {
+            add_status_effect(
+                &mut commands,
+                entity,
+                EffectKind::CatchUp,
+                HANDICAP_DURATION,
+            );
+        }
+    }
+}
+
+fn tick_status_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut effects: Query<(Entity, &mut StatusEffect)>,
+) {
+    for (entity, mut effect) in effects.iter_mut() {
+        if effect.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// The biggest growth wins, picking up the same effect twice doesn't stack
+fn grow_paddles(
+    mut paddles: Query<(&mut PaddleSize, Option<&Children>), With<Paddle>>,
+    effects: Query<&StatusEffect>,
+) {
+    for (mut size, children) in paddles.iter_mut() {
+        let bonus = children
+            .into_iter()
+            .flat_map(|children| children.iter())
+            .filter_map(|child| effects.get(*child).ok())
+            .map(|effect| effect.kind.paddle_growth())
+            .fold(0., f32::max);
+        if size.bonus != bonus {
+            size.bonus = bonus;
+        }
+    }
+}
+
+// Runs after every paddle controller, whoever steers the paddle
+pub fn apply_paddle_effects(
+    mut paddles: Query<(
+        &mut LinearVelocity,
+        &mut Position,
+        &PaddleBounds,
+        Option<&Children>,
+    )>,
+    effects: Query<&StatusEffect>,
+) {
+    for (mut velocity, mut position, bounds, children) in paddles.iter_mut() {
+        if has_effect(children, &effects, EffectKind::Frozen) {
+            velocity.0 = Vec2::ZERO;
+        } else if has_effect(children, &effects, EffectKind::ReversedControls) {
+            velocity.0 = -velocity.0;
+            bounds.clamp(&mut position.0, &mut velocity.0);
+        }
+    }
+}
+
+fn resize_oversized_balls(
+    mut balls: Query<(&Ball, &mut Collider, &Children)>,
+    effects: Query<&StatusEffect>,
+    mut ball_sprites: Query<&mut Sprite, With<BallSprite>>,
+) {
+    for (ball, mut collider, children) in balls.iter_mut() {
+        let mut radius = ball.get_radius();
+        if has_effect(Some(children), &effects, EffectKind::OversizedBall) {
+            radius *= OVERSIZED_BALL_SCALE;
+        }
+        let size = Some(Vec2::ONE * (radius * 2.));
+        for child in children.iter() {
+            if let Ok(mut sprite) = ball_sprites.get_mut(*child) {
+                if sprite.custom_size != size {
+                    sprite.custom_size = size;
+                    *collider = Collider::ball(radius);
+                }
+            }
+        }
+    }
+}
+
+// One colored entry per active effect, with its time left
+fn update_paddle_buff_hud(
+    paddles: Query<(&Paddle, Option<&Children>)>,
+    effects: Query<&StatusEffect>,
+    mut hud: Query<(&mut Text, &PaddleBuffHud)>,
+) {
+    for (mut text, hud) in hud.iter_mut() {
+        let Some((_, children)) = paddles
+            .iter()
+            .find(|(paddle, _)| paddle.first_player == hud.first_player)
+        else {
+            continue;
+        };
+        // The same effect picked up twice shows once, with the longest time left
+        let mut active: Vec<(EffectKind, f32)> = vec![];
+        let paddle_effects = children
+            .into_iter()
+            .flat_map(|children| children.iter())
+            .filter_map(|child| effects.get(*child).ok());
+        for effect in paddle_effects {
+            let remaining = effect.timer.remaining_secs();
+            match active.iter_mut().find(|(kind, _)| *kind == effect.kind) {
+                Some((_, longest)) => *longest = longest.max(remaining),
+                None => active.push((effect.kind, remaining)),
+            }
+        }
+        let sections = active.into_iter().map(|(kind, remaining)| {
+            TextSection::new(
+                format!("{} {remaining:.1}s  ", kind.label()),
+                TextStyle {
+                    font_size: 18.0,
+                    color: kind.color(),
+                    ..default()
+                },
+            )
+        });
+        text.sections = sections.collect();
+    }
+}
+
+fn clear_status_effects(mut commands: Commands, effects: Query<Entity, With<StatusEffect>>) {
+    for entity in effects.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn power_up_spawner(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut since_last: Local<f32>,
+    mut rng: ResMut<GameRng>,
+    power_ups: Query<(), With<PowerUp>>,
+    court: Res<Court>,
+) {
+    if !config.power_ups {
+        return;
+    }
+    *since_last += time.delta_seconds();
+    if *since_last < config.power_up_interval || power_ups.iter().count() >= config.max_power_ups {
+        return;
+    }
+    *since_last = 0.;
+
+    // Away from the serve spot and the goal lines
+    let area = court.half_size * 0.7;
+    let x = rng.0.gen_range(area.x * POWER_UP_SERVE_CLEARANCE..area.x)
+        * if rng.0.gen::<bool>() { 1. } else { -1. };
+    let y = rng.0.gen_range(-area.y..area.y);
+    let kind = PowerUpKind::ALL[rng.0.gen_range(0..PowerUpKind::ALL.len())];
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: kind.color(),
+                custom_size: Some(Vec2::splat(POWER_UP_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_xyz(x, y, 2.),
+            ..default()
+        },
+        RigidBody::Static,
+        Collider::ball(POWER_UP_SIZE / 2.),
+        Sensor,
+        CollisionLayers::new([Layer::PowerUp], [Layer::Ball]),
+        PowerUp { kind },
+        MatchEntity,
+    ));
+}
+
+fn collect_power_ups(
+    mut commands: Commands,
+    mut collision_started: EventReader<CollisionStarted>,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    balls: Query<(Entity, &LastTouchedBy, &Position, &LinearVelocity), With<Ball>>,
+    power_ups: Query<(&PowerUp, &Position), Without<Ball>>,
+    mut cooldowns: ResMut<PowerUpCooldowns>,
+    mut wall_charges: ResMut<DefensiveWallCharges>,
+    mut point_ball_count: ResMut<PointBallCount>,
+    mut rng: ResMut<GameRng>,
+    ball_assets: Res<BallAssets>,
+    cosmetics: Res<CosmeticsConfig>,
+    paddles: Query<(Entity, &Paddle)>,
+    spawn_config: Res<BallSpawnConfig>,
+) {
+    let now = time.elapsed_seconds();
+    let mut ball_count = balls.iter().count();
+    for CollisionStarted(entity1, entity2) in collision_started.iter() {
+        let (ball, power_up_entity) = if balls.contains(*entity1) {
+            (*entity1, *entity2)
+        } else {
+            (*entity2, *entity1)
+        };
+        let (Ok((_, last_touched, ball_position, ball_velocity)), Ok((power_up, position))) =
+            (balls.get(ball), power_ups.get(power_up_entity))
+        else {
+            continue;
+        };
+        let Some(side) = &last_touched.0 else {
+            continue;
+        };
+        let player = if *side == Side::Left { 0 } else { 1 };
+        if cooldowns.last_pickup[player].is_some_and(|last| now - last < config.pickup_cooldown) {
+            continue;
+        }
+        cooldowns.last_pickup[player] = Some(now);
+
+        match power_up.kind {
+            PowerUpKind::DefensiveWall => wall_charges.ready[player] = true,
+            PowerUpKind::PointBall => {
+                let direction = serve_direction(side.opposite(), &mut rng.0);
+                spawn_ball_at(
+                    &mut commands,
+                    &ball_assets,
+                    &cosmetics,
+                    position.0,
+                    direction,
+                    RALLY_BONUS_POINTS,
+                    BallKind::Point,
+                );
+                point_ball_count.0 = point_ball_count.0.saturating_add(1);
+            }
+            PowerUpKind::Multiball => {
+                // The copies already move, so they skip the serve push, and start beside the
+                // ball rather than inside it
+                let beside = ball_velocity.perp().normalize_or_zero() * BALL_RADIUS * 2.2;
+                for angle in [MULTIBALL_SPREAD, -MULTIBALL_SPREAD] {
+                    if ball_count >= spawn_config.max_balls {
+                        break;
+                    }
+                    let copy = spawn_ball_at(
+                        &mut commands,
+                        &ball_assets,
+                        &cosmetics,
+                        ball_position.0 + beside * angle.signum(),
+                        Vec2::ZERO,
+                        0,
+                        BallKind::Normal,
+                    );
+                    let velocity = Vec2::from_angle(angle).rotate(ball_velocity.0);
+                    commands
+                        .entity(copy)
+                        .insert((LinearVelocity(velocity), LastTouchedBy(Some(side.clone()))));
+                    ball_count += 1;
+                }
+            }
+            PowerUpKind::BigPaddle
+            | PowerUpKind::FastShots
+            | PowerUpKind::Freeze
+            | PowerUpKind::Reverse => {
+                // Buffs go to the collector, debuffs to the other player
+                let (kind, seconds, own) = match power_up.kind {
+                    PowerUpKind::BigPaddle => (EffectKind::BigPaddle, POWER_UP_DURATION, true),
+                    PowerUpKind::FastShots => (EffectKind::FastShots, POWER_UP_DURATION, true),
+                    PowerUpKind::Freeze => (EffectKind::Frozen, FREEZE_DURATION, false),
+                    _ => (EffectKind::ReversedControls, REVERSE_DURATION, false),
+                };
+                for (entity, paddle) in paddles.iter() {
+                    if (paddle.side == *side) == own {
+                        add_status_effect(&mut commands, entity, kind, seconds);
+                    }
+                }
+            }
+            PowerUpKind::BigBall => {
+                for (entity, ..) in balls.iter() {
+                    add_status_effect(
+                        &mut commands,
+                        entity,
+                        EffectKind::OversizedBall,
+                        POWER_UP_DURATION,
+                    );
+                }
+            }
+        }
+        commands.entity(power_up_entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,281 @@
+// Shared by every module: the engine, physics and std items the game uses and the few types all
+// of it touches. Everything else is imported from the module that owns it
+pub use std::cmp::Ordering;
+pub use std::collections::{HashMap, VecDeque};
+pub use std::f32::consts::{PI, TAU};
+pub use std::io;
+pub use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+pub use std::path::PathBuf;
+pub use std::time::Duration;
+
+pub use bevy::app::AppExit;
+pub use bevy::asset::{AssetLoader, LoadContext, LoadState, LoadedAsset};
+pub use bevy::audio::{AudioSinkPlayback, Volume};
+pub use bevy::ecs::system::{CommandQueue, EntityCommands, SystemParam};
+pub use bevy::input::mouse::MouseWheel;
+pub use bevy::math::Vec3Swizzles;
+pub use bevy::prelude::*;
+pub use bevy::reflect::{TypePath, TypeUuid};
+pub use bevy::render::camera::RenderTarget;
+pub use bevy::render::view::RenderLayers;
+pub use bevy::time::{Stopwatch, TimeUpdateStrategy};
+pub use bevy::transform::TransformSystem;
+pub use bevy::utils::BoxedFuture;
+pub use bevy::window::{
+    ExitCondition, PrimaryWindow, WindowMode, WindowRef, WindowResized, WindowResolution,
+};
+
+pub use bevy_xpbd_2d::prelude::*;
+
+pub use rand::rngs::StdRng;
+pub use rand::{Rng, SeedableRng};
+
+pub use serde::de::DeserializeOwned;
+pub use serde::{Deserialize, Serialize};
+
+use crate::ball::BallKind;
+
+pub const WINDOW_SIZE: Vec2 = Vec2 { x: 1280., y: 720. };
+// Paddle movement and physics step by this much in FixedUpdate, whatever the frame rate
+pub const FIXED_TIMESTEP: Duration = Duration::from_nanos(16_666_667);
+
+// Gameplay systems only run in Playing, physics is frozen while Paused and on GameOver
+#[derive(States, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum GameState {
+    // Waits for the assets setup started loading, then goes to the menu
+    #[default]
+    Loading,
+    Menu,
+    Playing,
+    Paused,
+    // Between two games of a best-of match, the court is reset and the next game starts by itself
+    Intermission,
+    GameOver,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum GameMode {
+    #[default]
+    Versus,
+    // Defend alone against an accelerating stream of balls, the right side is a wall
+    Survival,
+    // Most points when the match clock runs out wins
+    Timed,
+    // Like versus, but scoring enough point balls also wins outright
+    PointBallRace,
+    // Break every brick on the right side before conceding too many goals
+    Breakout,
+    // A seat on every side, the AI takes the ones no player has
+    FourPlayer,
+    // Both players defend the left goal together against balls fired from the right
+    Coop,
+}
+
+impl GameMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Versus => Self::Survival,
+            Self::Survival => Self::Timed,
+            Self::Timed => Self::PointBallRace,
+            Self::PointBallRace => Self::Breakout,
+            Self::Breakout => Self::FourPlayer,
+            Self::FourPlayer => Self::Coop,
+            Self::Coop => Self::Versus,
+        }
+    }
+
+    // No paddle on the right, the right side bounces balls back
+    pub fn lone_player(self) -> bool {
+        matches!(self, Self::Survival | Self::Breakout | Self::Coop)
+    }
+
+    // Runs against a stream of balls, scored by time and returns until the lives are gone
+    pub fn endless(self) -> bool {
+        matches!(self, Self::Survival | Self::Coop)
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub enum MatchOutcome {
+    Winner { first_player: bool },
+    // Four player matches are won by a seat
+    SeatWinner { side: Side },
+    Draw,
+    SurvivalOver { time: f32, returns: usize },
+}
+
+// Match simulation, only run while playing and never on a LAN client
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SimulationSet;
+
+// Each fixed step before physics: delayed forces land, then paddles move, then status effects
+// adjust them
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum StepSet {
+    Forces,
+    MovePaddles,
+    PaddleEffects,
+}
+
+// A goal is found, everything reacts to it, it may end the match, then the next ball comes in
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum GoalSet {
+    Detect,
+    React,
+    Victory,
+    Countdown,
+    Serve,
+}
+
+// A paddle hit is found, everything reacts to it, then power-ups are picked up
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum HitSet {
+    Detect,
+    React,
+    Collect,
+}
+
+// Arena, HUD and everything spawned during play, all cleared when going back to the menu
+#[derive(Component)]
+pub struct MatchEntity;
+
+// Present while the AI plays itself as an attract screen, any input goes back to the menu
+#[derive(Resource)]
+pub struct DemoMatch;
+
+#[derive(Resource, Default)]
+pub struct DebugOverlays {
+    pub ball_inspector: bool,
+    pub ai_target: bool,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct GoalScored {
+    // Side of the goal the ball went into
+    pub side: Side,
+    // Seat the points go to, None for a four player goal nobody else touched
+    pub scorer: Option<Side>,
+    pub ball: Entity,
+    // Where the ball was when it went in
+    pub position: Vec2,
+    pub points: usize,
+    pub kind: BallKind,
+    // Score awarded, more than one for corner goals with goal zones on
+    pub value: usize,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct PaddleHit {
+    pub ball: Entity,
+    pub paddle: Entity,
+    pub side: Side,
+}
+
+// Where a ball or paddle was before the latest fixed step, its sprite is drawn between the two
+#[derive(Component)]
+pub struct PreviousPosition(pub Vec2);
+
+// The game's own camera, the scoreboard window has another
+#[derive(Component)]
+pub struct MainCamera;
+
+#[derive(Resource)]
+pub struct GameRng(pub StdRng);
+
+// What GameRng was seeded with at the start of the match, --seed plays the same randomness again
+#[derive(Resource)]
+pub struct MatchSeed(pub u64);
+
+impl GameRng {
+    pub fn new(seed: Option<u64>) -> Self {
+        Self(match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        })
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum Side {
+    Random,
+    Left,
+    Right,
+    // Only four player matches have goals on the top and bottom
+    Top,
+    Bottom,
+}
+
+impl Side {
+    // Every seat of a four player match
+    pub const SEATS: [Side; 4] = [Side::Left, Side::Right, Side::Top, Side::Bottom];
+
+    pub fn opposite(&self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::Top => Self::Bottom,
+            Self::Bottom => Self::Top,
+            Self::Random => Self::Random,
+        }
+    }
+
+    // Some(true) for the first player's seat, None for the seats only four player matches have
+    pub fn first_player(&self) -> Option<bool> {
+        match self {
+            Self::Left => Some(true),
+            Self::Right => Some(false),
+            _ => None,
+        }
+    }
+
+    // Paddles on the top and bottom goals lie across the court
+    pub fn horizontal(&self) -> bool {
+        matches!(self, Self::Top | Self::Bottom)
+    }
+
+    pub fn oriented(&self, size: Vec2) -> Vec2 {
+        if self.horizontal() {
+            Vec2::new(size.y, size.x)
+        } else {
+            size
+        }
+    }
+
+    // A point given with x toward the court and y along a left paddle, turned to this side
+    pub fn face_point(&self, point: Vec2) -> Vec2 {
+        let along = if self.horizontal() { Vec2::X } else { Vec2::Y };
+        self.inward() * point.x + along * point.y
+    }
+
+    // From the goal on this side into the court
+    pub fn inward(&self) -> Vec2 {
+        match self {
+            Self::Left => Vec2::X,
+            Self::Right => Vec2::NEG_X,
+            Self::Top => Vec2::NEG_Y,
+            Self::Bottom => Vec2::Y,
+            Self::Random => Vec2::ZERO,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Top => "Top",
+            Self::Bottom => "Bottom",
+            Self::Random => "Random",
+        }
+    }
+}
+
+#[derive(PhysicsLayer)]
+pub enum Layer {
+    Wall,
+    Net,
+    Paddle,
+    Ball,
+    PowerUp,
+    Obstacle,
+    Brick,
+}
@@ -0,0 +1,324 @@
+use crate::config::{read_shared_file, save_config_file, GameConfig, MatchRules};
+use crate::input::{KeyBindings, PaddleDash};
+use crate::net::SimulationChecksum;
+use crate::paddle::{
+    steer_paddle, Paddle, PaddleBounds, PaddleController, MAX_PADDLE_TILT, PADDLE_SPEED,
+};
+use crate::power_ups::apply_paddle_effects;
+use crate::prelude::*;
+
+pub const REPLAY_FILE: &str = "replay.ron";
+
+// Longest frame a replay may hold, a recorded match never stalls this long between frames
+const MAX_FRAME_NANOS: u64 = 1_000_000_000;
+// Paddle targets are kept in their box anyway, one further than this from the net is corrupt
+const MAX_TARGET_REACH: Vec2 = WINDOW_SIZE;
+
+// What the players did during one frame of a match
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct ReplayFrame {
+    pub delta_nanos: u64,
+    // Per fixed step of the frame, where each player steered their paddle, first player first,
+    // None when they let go
+    pub steps: Vec<[Option<[f32; 2]>; 2]>,
+    pub wall_presses: [bool; 2],
+    // The first player started a dash, replays saved before dashes have none
+    #[serde(default)]
+    pub dash: bool,
+    // The first player held their smash charge
+    #[serde(default)]
+    pub charging: bool,
+    // The first player's paddle tilt in radians, after this frame's input
+    #[serde(default)]
+    pub tilt: f32,
+}
+
+// A match's rules and seed plus every frame of input, enough to play it again
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Replay {
+    pub rules: MatchRules,
+    pub seed: u64,
+    pub controllers: [PaddleController; 2],
+    pub frames: Vec<ReplayFrame>,
+    // Simulation checksum after the last frame, a replay ending elsewhere has diverged
+    pub checksum: u64,
+}
+
+impl Replay {
+    pub fn new(config: &GameConfig, seed: u64, controllers: [PaddleController; 2]) -> Self {
+        Self {
+            rules: MatchRules::from_config(config),
+            seed,
+            controllers,
+            frames: vec![],
+            checksum: 0,
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        Self::parse(&read_shared_file(path)?)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let replay: Self = ron::from_str(contents).map_err(|e| e.to_string())?;
+        if replay.frames.is_empty() {
+            return Err("the replay has no frames".to_string());
+        }
+        replay.rules.validate()?;
+        for (index, frame) in replay.frames.iter().enumerate() {
+            frame
+                .validate()
+                .map_err(|error| format!("frame {index}: {error}"))?;
+        }
+        Ok(replay)
+    }
+}
+
+impl ReplayFrame {
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_FRAME_NANOS).contains(&self.delta_nanos) {
+            return Err(format!("{} ns is not a frame time", self.delta_nanos));
+        }
+        // The fixed clock carries less than a step over, a frame can't run more than one past
+        // its own time
+        let max_steps = self.delta_nanos / FIXED_TIMESTEP.as_nanos() as u64 + 1;
+        if self.steps.len() as u64 > max_steps {
+            return Err(format!(
+                "{} fixed steps in {} ns",
+                self.steps.len(),
+                self.delta_nanos
+            ));
+        }
+        let targets = self
+            .steps
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|&target| Vec2::from(target));
+        for target in targets {
+            if !target.is_finite() || target.abs().cmpgt(MAX_TARGET_REACH).any() {
+                return Err(format!("paddle target {target} is off the court"));
+            }
+        }
+        if !self.tilt.is_finite() || self.tilt.abs() > MAX_PADDLE_TILT {
+            return Err(format!("paddle tilt {} is past the limit", self.tilt));
+        }
+        Ok(())
+    }
+}
+
+// The match being played, the current frame fills in as the gameplay systems run
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    pub replay: Replay,
+    pub frame: ReplayFrame,
+    pub step: [Option<[f32; 2]>; 2],
+}
+
+impl ReplayRecorder {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            frame: ReplayFrame::default(),
+            step: [None; 2],
+        }
+    }
+
+    pub fn record_target(
+        recorder: &mut Option<ResMut<Self>>,
+        paddle: &Paddle,
+        target: Option<Vec2>,
+    ) {
+        if let Some(recorder) = recorder {
+            let player = if paddle.first_player { 0 } else { 1 };
+            recorder.step[player] = target.map(Vec2::into);
+        }
+    }
+}
+
+// Present while a saved match plays back, the recorded inputs stand in for the players
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    pub next: usize,
+    // Fixed steps already played of the next frame
+    pub step: usize,
+    // Settings from before the replay took them over, put back on the way out
+    pub config: GameConfig,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay, config: GameConfig) -> Self {
+        Self {
+            replay,
+            next: 0,
+            step: 0,
+            config,
+        }
+    }
+
+    pub fn frame(&self) -> Option<&ReplayFrame> {
+        self.replay.frames.get(self.next)
+    }
+
+    pub fn targets(&self) -> Option<[Option<[f32; 2]>; 2]> {
+        self.frame()?.steps.get(self.step).copied()
+    }
+}
+
+// Records local matches and plays saved ones back. Paddle targets are kept per fixed step and
+// frames are closed in Last, playback feeds each recorded frame time to the clock before the
+// frame runs so the same fixed steps happen
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Menu), stop_replay)
+            .add_systems(
+                Update,
+                save_replay
+                    .run_if(not(in_state(GameState::Menu)))
+                    .run_if(resource_exists::<ReplayRecorder>()),
+            )
+            .add_systems(
+                FixedUpdate,
+                move_replay_paddles.in_set(StepSet::MovePaddles),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    record_replay_step.run_if(resource_exists::<ReplayRecorder>()),
+                    advance_replay_step.run_if(resource_exists::<ReplayPlayback>()),
+                )
+                    .after(apply_paddle_effects)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Last,
+                (
+                    record_replay_frame
+                        .run_if(in_state(GameState::Playing))
+                        .run_if(resource_exists::<ReplayRecorder>()),
+                    advance_replay.run_if(resource_exists::<ReplayPlayback>()),
+                ),
+            );
+    }
+}
+
+pub fn record_replay_step(mut recorder: ResMut<ReplayRecorder>) {
+    let step = std::mem::take(&mut recorder.step);
+    recorder.frame.steps.push(step);
+}
+
+pub fn advance_replay_step(mut playback: ResMut<ReplayPlayback>) {
+    playback.step += 1;
+}
+
+pub fn record_replay_frame(
+    time: Res<Time>,
+    checksum: Res<SimulationChecksum>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    let mut frame = std::mem::take(&mut recorder.frame);
+    frame.delta_nanos = time.delta().as_nanos() as u64;
+    recorder.replay.frames.push(frame);
+    recorder.replay.checksum = checksum.value;
+}
+
+fn save_replay(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    recorder: Res<ReplayRecorder>,
+) {
+    if !keys.just_pressed(bindings.save_replay) {
+        return;
+    }
+    match save_config_file(REPLAY_FILE, &recorder.replay) {
+        Ok(()) => info!("Saved a replay of {} frames", recorder.replay.frames.len()),
+        Err(error) => warn!("Could not save the replay: {error}"),
+    }
+}
+
+pub fn advance_replay(
+    state: Res<State<GameState>>,
+    checksum: Res<SimulationChecksum>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut time_strategy: ResMut<TimeUpdateStrategy>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    match state.get() {
+        GameState::Playing => {
+            playback.next += 1;
+            playback.step = 0;
+        }
+        // The frame the replay was picked on, the match starts on the next one
+        GameState::Menu => {}
+        _ => return,
+    }
+    if let Some(frame) = playback.frame() {
+        *time_strategy =
+            TimeUpdateStrategy::ManualDuration(Duration::from_nanos(frame.delta_nanos));
+        return;
+    }
+    *time_strategy = TimeUpdateStrategy::Automatic;
+    if checksum.value != playback.replay.checksum {
+        warn!("The replay diverged from the recorded match");
+    }
+    // A replay saved mid-match stops where it was saved, a finished one shows its game over screen
+    if next_state.0.is_none() {
+        next_state.set(GameState::Menu);
+    }
+}
+
+fn stop_replay(
+    mut commands: Commands,
+    playback: Option<Res<ReplayPlayback>>,
+    mut config: ResMut<GameConfig>,
+    mut time_strategy: ResMut<TimeUpdateStrategy>,
+) {
+    let Some(playback) = playback else {
+        return;
+    };
+    *config = playback.config.clone();
+    *time_strategy = TimeUpdateStrategy::Automatic;
+    commands.remove_resource::<ReplayPlayback>();
+}
+
+pub fn move_replay_paddles(
+    fixed_time: Res<FixedTime>,
+    dash: Res<PaddleDash>,
+    playback: Option<Res<ReplayPlayback>>,
+    mut paddles: Query<(
+        &mut LinearVelocity,
+        &mut Position,
+        &PaddleBounds,
+        &Paddle,
+        &PaddleController,
+    )>,
+) {
+    let targets = playback.as_ref().and_then(|playback| playback.targets());
+    for (mut velocity, mut paddle_position, bounds, paddle, controller) in paddles.iter_mut() {
+        if *controller != PaddleController::Replay {
+            continue;
+        }
+        let player = if paddle.first_player { 0 } else { 1 };
+        let Some(position) = targets.and_then(|targets| targets[player]).map(Vec2::from) else {
+            *velocity = LinearVelocity(Vec2::ZERO);
+            continue;
+        };
+        // Only the first player dashes
+        let speed = if paddle.first_player {
+            dash.speed()
+        } else {
+            PADDLE_SPEED
+        };
+        steer_paddle(
+            bounds,
+            position,
+            speed,
+            fixed_time.period.as_secs_f32(),
+            &mut paddle_position.0,
+            &mut velocity.0,
+        );
+    }
+}
@@ -0,0 +1,1019 @@
+use crate::arena::{Brick, Court, Goal};
+use crate::ball::{
+    point_label, Ball, BallKind, BallSprite, BallTextMarker, ParkedBall, PendingServe,
+    PointBallCount, RallyCounter, ServeCountdown, ServeQueue,
+};
+use crate::config::{save_config_file, GameConfig, TieBreak};
+use crate::input::KeyBindings;
+use crate::net::NetClient;
+use crate::paddle::{Paddle, PaddleSize, PaddleStart};
+use crate::power_ups::{DefensiveWall, DefensiveWallCharges};
+use crate::prelude::*;
+use crate::replay::{ReplayPlayback, ReplayRecorder};
+use crate::ui::FadeOut;
+
+// Scoring goals and returns, deciding matches, tournaments, lifetime stats and the score HUD
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(GameState::GameOver),
+            (
+                record_tournament_result.run_if(resource_exists::<Tournament>()),
+                // Demo matches, replays, matches only watched over LAN and tournament matches
+                // aren't the player's own
+                record_lifetime_stats
+                    .run_if(not(resource_exists::<DemoMatch>()))
+                    .run_if(not(resource_exists::<ReplayPlayback>()))
+                    .run_if(not(resource_exists::<NetClient>()))
+                    .run_if(not(resource_exists::<Tournament>())),
+            )
+                .chain(),
+        )
+        .add_systems(OnEnter(GameState::Intermission), start_next_game)
+        .add_systems(
+            Update,
+            (
+                (apply_goals, record_goal_stats, start_goal_celebration).in_set(GoalSet::React),
+                check_victory.in_set(GoalSet::Victory),
+                tick_goal_celebration.in_set(GoalSet::Countdown),
+                (
+                    add_points_on_paddle_hits,
+                    count_survival_returns,
+                    record_paddle_hit_stats,
+                )
+                    .in_set(HitSet::React),
+                (tick_match_timer, tick_survival_run).in_set(SimulationSet),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                restart_match
+                    .run_if(in_state(GameState::GameOver))
+                    .run_if(not(resource_exists::<DemoMatch>()))
+                    .run_if(not(resource_exists::<ReplayPlayback>()))
+                    .run_if(not(resource_exists::<NetClient>())),
+                // Match resources only exist once the menu has been left
+                (
+                    update_score.run_if(resource_exists::<Score>().and_then(
+                        resource_changed::<Score>().or_else(resource_changed::<WinningScore>()),
+                    )),
+                    update_games_won.run_if(
+                        resource_exists::<GamesWon>().and_then(resource_changed::<GamesWon>()),
+                    ),
+                    update_seat_scores.run_if(resource_exists::<Score>()),
+                    animate_score_punches,
+                )
+                    .chain(),
+            ),
+        );
+    }
+}
+
+// How long the set score stays up between two games of a best-of match
+pub const INTERMISSION_SECONDS: f32 = 3.;
+pub const TOURNAMENT_MIN_PLAYERS: usize = 4;
+pub const TOURNAMENT_MAX_PLAYERS: usize = 8;
+pub const TOURNAMENT_NAME_LENGTH: usize = 16;
+
+pub const STATS_FILE: &str = "stats.ron";
+
+// After a goal the goal line flashes in the scorer's color, no serve comes out meanwhile
+const GOAL_CELEBRATION_SECONDS: f32 = 1.2;
+const GOAL_FLASH_WIDTH: f32 = 60.;
+// A score that goes up grows to this size and back, starting in the scorer's paddle color
+const SCORE_PUNCH_SCALE: f32 = 1.6;
+const SCORE_PUNCH_SECONDS: f32 = 0.6;
+
+pub const RALLY_BONUS_POINTS: usize = 3;
+
+// Best survival runs kept in the lifetime stats
+const SURVIVAL_HIGH_SCORES: usize = 5;
+
+// Points of every seat, keyed by the side its goal is on
+#[derive(Resource, Default)]
+pub struct Score(pub HashMap<Side, usize>);
+
+impl Score {
+    pub fn of(&self, side: &Side) -> usize {
+        self.0.get(side).copied().unwrap_or_default()
+    }
+
+    pub fn add(&mut self, side: Side, points: usize) {
+        *self.0.entry(side).or_default() += points;
+    }
+
+    pub fn set(&mut self, side: Side, points: usize) {
+        self.0.insert(side, points);
+    }
+
+    pub fn first_player(&self) -> usize {
+        self.of(&Side::Left)
+    }
+
+    pub fn second_player(&self) -> usize {
+        self.of(&Side::Right)
+    }
+}
+
+#[derive(Resource)]
+pub struct WinningScore(pub usize);
+
+// Games taken so far in a best-of match, Score counts the points of the current game
+#[derive(Resource, Default)]
+pub struct GamesWon {
+    pub first_player: usize,
+    pub second_player: usize,
+}
+
+impl GamesWon {
+    // True once the winner of this game has taken the match
+    pub fn record(&mut self, first_player: bool, best_of: usize) -> bool {
+        let games = if first_player {
+            &mut self.first_player
+        } else {
+            &mut self.second_player
+        };
+        *games += 1;
+        *games > best_of / 2
+    }
+}
+
+#[derive(Component)]
+pub struct GamesWonMarker;
+
+// Single elimination between local players, every match is a hotseat match, left player first
+#[derive(Resource, Clone, Debug)]
+pub struct Tournament {
+    pub names: Vec<String>,
+    // Entrant in each slot of each round, pairs of slots meet and the winner fills the next round.
+    // Round 0 is padded with byes up to a power of two, later rounds fill in as matches end
+    pub rounds: Vec<Vec<Option<usize>>>,
+    // Round and pair of the match that just ended, for the game over screen
+    pub last_match: Option<(usize, usize)>,
+}
+
+impl Tournament {
+    pub fn new(names: Vec<String>) -> Self {
+        let size = names.len().next_power_of_two();
+        let half = size / 2;
+        // Byes go to the second half of the draw so no pair is left without a player
+        let first_round = (0..half)
+            .flat_map(|pair| {
+                [
+                    Some(pair),
+                    Some(half + pair).filter(|entrant| *entrant < names.len()),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let mut rounds = vec![first_round];
+        while rounds[rounds.len() - 1].len() > 1 {
+            rounds.push(vec![None; rounds[rounds.len() - 1].len() / 2]);
+        }
+        for pair in 0..half {
+            if rounds[0][pair * 2 + 1].is_none() {
+                rounds[1][pair] = rounds[0][pair * 2];
+            }
+        }
+        Self {
+            names,
+            rounds,
+            last_match: None,
+        }
+    }
+
+    // Round and pair of the match being played, None once there is a champion
+    pub fn next_match(&self) -> Option<(usize, usize)> {
+        (0..self.rounds.len() - 1).find_map(|round| {
+            (0..self.rounds[round].len() / 2)
+                .find(|pair| {
+                    let slots = &self.rounds[round][pair * 2..pair * 2 + 2];
+                    slots.iter().all(Option::is_some) && self.rounds[round + 1][*pair].is_none()
+                })
+                .map(|pair| (round, pair))
+        })
+    }
+
+    // Names on the left and right paddles for the match being played
+    pub fn next_names(&self) -> Option<[&str; 2]> {
+        self.match_names(self.next_match()?)
+    }
+
+    pub fn match_names(&self, (round, pair): (usize, usize)) -> Option<[&str; 2]> {
+        let name =
+            |slot: usize| self.rounds[round][slot].map(|entrant| self.names[entrant].as_str());
+        Some([name(pair * 2)?, name(pair * 2 + 1)?])
+    }
+
+    pub fn record_winner(&mut self, first_player: bool) {
+        if let Some((round, pair)) = self.next_match() {
+            let slot = if first_player { pair * 2 } else { pair * 2 + 1 };
+            self.rounds[round + 1][pair] = self.rounds[round][slot];
+        }
+    }
+
+    pub fn champion(&self) -> Option<&str> {
+        let entrant = self.rounds[self.rounds.len() - 1][0]?;
+        Some(&self.names[entrant])
+    }
+}
+
+#[derive(Component)]
+pub struct TournamentMatchText;
+
+impl Default for WinningScore {
+    fn default() -> Self {
+        Self(10)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SurvivalRun {
+    pub time: Stopwatch,
+    pub returns: usize,
+    pub conceded: usize,
+}
+
+#[derive(Resource, Default)]
+pub struct MatchTimer {
+    pub elapsed: Stopwatch,
+    // Only timed matches have a limit
+    pub duration: Option<f32>,
+}
+
+impl MatchTimer {
+    pub fn new(config: &GameConfig) -> Self {
+        Self {
+            elapsed: Stopwatch::new(),
+            duration: (config.mode == GameMode::Timed).then_some(config.match_duration),
+        }
+    }
+
+    pub fn remaining(&self) -> Option<f32> {
+        self.duration
+            .map(|duration| (duration - self.elapsed.elapsed_secs()).max(0.))
+    }
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct PlayerMatchStats {
+    pub goals: usize,
+    pub paddle_hits: usize,
+    // Most hits the player made within a single rally
+    pub longest_rally: usize,
+    pub fastest_shot: f32,
+    pub point_balls: usize,
+    pub current_rally: usize,
+    // Match clock time of every goal the player scored
+    pub goal_times: Vec<f32>,
+}
+
+// Indexed by player, the first player comes first
+#[derive(Resource, Default)]
+pub struct MatchStats {
+    pub players: [PlayerMatchStats; 2],
+}
+
+impl MatchStats {
+    pub fn player_mut(&mut self, first_player: bool) -> &mut PlayerMatchStats {
+        &mut self.players[if first_player { 0 } else { 1 }]
+    }
+}
+
+#[derive(Component)]
+pub struct Player1ScoreMarker;
+#[derive(Component)]
+pub struct Player2ScoreMarker;
+// Score of a top or bottom seat, drawn on the court in front of its goal
+#[derive(Component)]
+pub struct SeatScoreText(pub Side);
+#[derive(Component)]
+pub struct WinningScoreMarker;
+
+// Lifetime record of the first player, saved after every match they play on this machine
+#[derive(Resource, Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct PlayerStats {
+    pub matches_played: usize,
+    pub matches_won: usize,
+    pub total_goals: usize,
+    pub longest_rally: usize,
+    pub fastest_ball: f32,
+    // Best first
+    pub survival_high_scores: Vec<SurvivalScore>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct SurvivalScore {
+    pub time: f32,
+    pub returns: usize,
+}
+
+impl SurvivalScore {
+    // One point per whole second survived and per return
+    pub fn points(&self) -> usize {
+        self.time as usize + self.returns
+    }
+}
+
+impl PlayerStats {
+    pub fn record(&mut self, outcome: &MatchOutcome, stats: &PlayerMatchStats) {
+        self.matches_played += 1;
+        if matches!(
+            outcome,
+            MatchOutcome::Winner { first_player: true }
+                | MatchOutcome::SeatWinner { side: Side::Left }
+        ) {
+            self.matches_won += 1;
+        }
+        self.total_goals += stats.goals;
+        self.longest_rally = self.longest_rally.max(stats.longest_rally);
+        self.fastest_ball = self.fastest_ball.max(stats.fastest_shot);
+        if let MatchOutcome::SurvivalOver { time, returns } = *outcome {
+            self.survival_high_scores
+                .push(SurvivalScore { time, returns });
+            // Stable, so an equal score doesn't push out an older one
+            self.survival_high_scores
+                .sort_by(|a, b| b.points().cmp(&a.points()));
+            self.survival_high_scores.truncate(SURVIVAL_HIGH_SCORES);
+        }
+    }
+
+    pub fn high_score_table(&self, current: Option<SurvivalScore>) -> String {
+        let mut marked = false;
+        let rows = self
+            .survival_high_scores
+            .iter()
+            .enumerate()
+            .map(|(rank, score)| {
+                let mark = if !marked && Some(*score) == current {
+                    marked = true;
+                    "  <"
+                } else {
+                    ""
+                };
+                format!(
+                    "{}. {} ({:.1}s, {} returns){mark}",
+                    rank + 1,
+                    score.points(),
+                    score.time,
+                    score.returns,
+                )
+            });
+        ["Survival high scores".to_string()]
+            .into_iter()
+            .chain(rows)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn summary(&self) -> String {
+        [
+            format!("Matches played: {}", self.matches_played),
+            format!("Matches won: {}", self.matches_won),
+            format!("Goals scored: {}", self.total_goals),
+            format!("Longest rally: {} hits", self.longest_rally),
+            format!("Fastest ball: {:.0}", self.fastest_ball),
+            String::new(),
+            self.high_score_table(None),
+        ]
+        .join("\n")
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct GoalCelebration(pub Option<Timer>);
+
+impl GoalCelebration {
+    pub fn running(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+// Score number growing and shrinking back, fading from the scorer's color to its usual one
+#[derive(Component)]
+struct ScorePunch {
+    pub timer: Timer,
+    pub flash: Color,
+    pub color: Color,
+}
+
+// The match just played is still the next one until its winner is written in
+fn record_tournament_result(outcome: Res<MatchOutcome>, mut tournament: ResMut<Tournament>) {
+    // A drawn match is played again
+    tournament.last_match = tournament.next_match();
+    if let MatchOutcome::Winner { first_player } = *outcome {
+        tournament.record_winner(first_player);
+    }
+}
+
+fn start_goal_celebration(
+    mut commands: Commands,
+    mut goal_scored: EventReader<GoalScored>,
+    mut celebration: ResMut<GoalCelebration>,
+    court: Res<Court>,
+    goals: Query<(&Goal, &Transform)>,
+    paddles: Query<(&Paddle, &Sprite)>,
+) {
+    for goal in goal_scored.iter() {
+        celebration.0 = Some(Timer::from_seconds(
+            GOAL_CELEBRATION_SECONDS,
+            TimerMode::Once,
+        ));
+        let color = paddles
+            .iter()
+            .find(|(paddle, _)| Some(&paddle.side) == goal.scorer.as_ref())
+            .map_or(Color::WHITE, |(_, sprite)| sprite.color);
+        // Drawn just inside the goal line the ball went through
+        let Some((_, goal_transform)) = goals.iter().find(|(line, _)| line.side == goal.side)
+        else {
+            continue;
+        };
+        let line = goal_transform.translation.xy();
+        let center = line + goal.side.inward() * GOAL_FLASH_WIDTH / 2.;
+        let size = if goal.side.horizontal() {
+            Vec2::new(court.half_size.x * 2., GOAL_FLASH_WIDTH)
+        } else {
+            Vec2::new(GOAL_FLASH_WIDTH, court.half_size.y * 2.)
+        };
+        let start_alpha = 0.5;
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: color.with_a(start_alpha),
+                    custom_size: Some(size),
+                    ..default()
+                },
+                transform: Transform::from_translation(center.extend(1.)),
+                ..default()
+            },
+            FadeOut {
+                timer: Timer::from_seconds(GOAL_CELEBRATION_SECONDS, TimerMode::Once),
+                start_alpha,
+            },
+            MatchEntity,
+        ));
+    }
+}
+
+fn tick_goal_celebration(time: Res<Time>, mut celebration: ResMut<GoalCelebration>) {
+    let Some(timer) = celebration.0.as_mut() else {
+        return;
+    };
+    if timer.tick(time.delta()).finished() {
+        celebration.0 = None;
+    }
+}
+
+pub fn apply_goals(
+    mut goal_scored: EventReader<GoalScored>,
+    mut score: ResMut<Score>,
+    config: Res<GameConfig>,
+) {
+    for goal in goal_scored.iter() {
+        // Survival and co-op scores come from returns, never from goals
+        if config.mode.endless() {
+            continue;
+        }
+        if let Some(scorer) = &goal.scorer {
+            score.add(scorer.clone(), goal.value);
+        }
+    }
+}
+
+fn add_points_on_paddle_hits(
+    mut paddle_hit: EventReader<PaddleHit>,
+    config: Res<GameConfig>,
+    mut point_ball_count: ResMut<PointBallCount>,
+    mut balls: Query<(&mut Ball, &mut Collider, &Children)>,
+    mut ball_texts: Query<&mut Text, With<BallTextMarker>>,
+    mut ball_sprites: Query<&mut Sprite, With<BallSprite>>,
+) {
+    if !config.points_per_hit {
+        paddle_hit.clear();
+        return;
+    }
+    for hit in paddle_hit.iter() {
+        let Ok((mut ball, mut collider, children)) = balls.get_mut(hit.ball) else {
+            continue;
+        };
+        if ball.points == 0 {
+            point_ball_count.0 = point_ball_count.0.saturating_add(1);
+        }
+        ball.points += 1;
+        // Point balls are smaller, and harder to return
+        let radius = ball.get_radius();
+        *collider = Collider::ball(radius);
+        for child in children.iter() {
+            if let Ok(mut text) = ball_texts.get_mut(*child) {
+                text.sections[0].value = point_label(ball.points);
+            }
+            if let Ok(mut sprite) = ball_sprites.get_mut(*child) {
+                sprite.custom_size = Some(Vec2::ONE * (radius * 2.));
+            }
+        }
+    }
+}
+
+fn record_paddle_hit_stats(
+    mut paddle_hit: EventReader<PaddleHit>,
+    balls: Query<&LinearVelocity, With<Ball>>,
+    mut stats: ResMut<MatchStats>,
+) {
+    for hit in paddle_hit.iter() {
+        let speed = balls.get(hit.ball).map(|v| v.length()).unwrap_or_default();
+        let Some(first_player) = hit.side.first_player() else {
+            continue;
+        };
+        let player = stats.player_mut(first_player);
+        player.paddle_hits += 1;
+        player.current_rally += 1;
+        player.longest_rally = player.longest_rally.max(player.current_rally);
+        player.fastest_shot = player.fastest_shot.max(speed);
+    }
+}
+
+fn record_goal_stats(
+    mut goal_scored: EventReader<GoalScored>,
+    mut stats: ResMut<MatchStats>,
+    timer: Res<MatchTimer>,
+) {
+    for goal in goal_scored.iter() {
+        // Only the two players keep stats, not the top and bottom seats of a four player match
+        if let Some(first_player) = goal.scorer.as_ref().and_then(Side::first_player) {
+            let scorer = stats.player_mut(first_player);
+            scorer.goals += 1;
+            scorer.goal_times.push(timer.elapsed.elapsed_secs());
+            if goal.points > 0 {
+                scorer.point_balls += 1;
+            }
+        }
+        for player in stats.players.iter_mut() {
+            player.current_rally = 0;
+        }
+    }
+}
+
+fn count_survival_returns(
+    mut paddle_hit: EventReader<PaddleHit>,
+    mut survival: ResMut<SurvivalRun>,
+    mut score: ResMut<Score>,
+    config: Res<GameConfig>,
+) {
+    if !config.mode.endless() {
+        paddle_hit.clear();
+        return;
+    }
+    // Both co-op paddles are on the left and share the returns
+    for hit in paddle_hit.iter() {
+        if hit.side == Side::Left {
+            survival.returns += 1;
+            score.set(Side::Left, survival.returns);
+        }
+    }
+}
+
+fn tick_survival_run(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut survival: ResMut<SurvivalRun>,
+    mut target: Query<&mut Text, With<WinningScoreMarker>>,
+) {
+    // Every mode keeps the clock, serves ramp up with it, but only endless runs show it
+    survival.time.tick(time.delta());
+    if !config.mode.endless() {
+        return;
+    }
+    if let Ok(mut text) = target.get_single_mut() {
+        text.sections[0].value = format!(
+            "Survived {:.1}s  Lives {}",
+            survival.time.elapsed_secs(),
+            config.lives.saturating_sub(survival.conceded),
+        );
+    }
+}
+
+fn check_victory(
+    mut commands: Commands,
+    mut goal_scored: EventReader<GoalScored>,
+    score: Res<Score>,
+    winning_score: Res<WinningScore>,
+    mut survival: ResMut<SurvivalRun>,
+    timer: Res<MatchTimer>,
+    stats: Res<MatchStats>,
+    config: Res<GameConfig>,
+    mut games: ResMut<GamesWon>,
+    bricks: Query<(), With<Brick>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let point_ball_race = config.mode == GameMode::PointBallRace;
+    let [p1, p2] = &stats.players;
+    let goals = goal_scored.iter().collect::<Vec<_>>();
+    // A golden ball decides the match whatever the score
+    let golden_winner = goals
+        .iter()
+        .find(|goal| goal.kind == BallKind::Golden)
+        .and_then(|goal| goal.scorer.clone())
+        .filter(|_| !config.mode.lone_player());
+    let outcome = match config.mode {
+        _ if golden_winner.is_some() => golden_winner.map(|side| match side.first_player() {
+            Some(first_player) if config.mode != GameMode::FourPlayer => {
+                MatchOutcome::Winner { first_player }
+            }
+            _ => MatchOutcome::SeatWinner { side },
+        }),
+        // First seat to the points to win takes the match
+        GameMode::FourPlayer => Side::SEATS
+            .into_iter()
+            .find(|side| score.of(side) >= winning_score.0)
+            .map(|side| MatchOutcome::SeatWinner { side }),
+        GameMode::Versus | GameMode::PointBallRace => {
+            if point_ball_race && p1.point_balls >= config.point_ball_target {
+                Some(MatchOutcome::Winner { first_player: true })
+            } else if point_ball_race && p2.point_balls >= config.point_ball_target {
+                Some(MatchOutcome::Winner {
+                    first_player: false,
+                })
+            } else {
+                // Point balls and golden balls win the whole match, points only win a game
+                let game_winner = if score.first_player() >= winning_score.0 {
+                    Some(true)
+                } else if score.second_player() >= winning_score.0 {
+                    Some(false)
+                } else {
+                    None
+                };
+                match game_winner {
+                    Some(first_player) if !games.record(first_player, config.best_of) => {
+                        next_state.set(GameState::Intermission);
+                        None
+                    }
+                    winner => winner.map(|first_player| MatchOutcome::Winner { first_player }),
+                }
+            }
+        }
+        // The run ends once the player has let in as many balls as they had lives
+        GameMode::Survival | GameMode::Coop => {
+            survival.conceded += goals.iter().filter(|goal| goal.side == Side::Left).count();
+            (survival.conceded >= config.lives).then(|| MatchOutcome::SurvivalOver {
+                time: survival.time.elapsed_secs(),
+                returns: survival.returns,
+            })
+        }
+        // Goals let in count for the right side, only bricks score for the player
+        GameMode::Breakout => {
+            if score.second_player() >= config.lives {
+                Some(MatchOutcome::Winner {
+                    first_player: false,
+                })
+            } else if bricks.is_empty() && score.first_player() > 0 {
+                Some(MatchOutcome::Winner { first_player: true })
+            } else {
+                None
+            }
+        }
+        // A sudden death tie keeps going until the scores differ
+        GameMode::Timed => {
+            if timer.remaining().is_some_and(|remaining| remaining <= 0.) {
+                match score.first_player().cmp(&score.second_player()) {
+                    Ordering::Greater => Some(MatchOutcome::Winner { first_player: true }),
+                    Ordering::Less => Some(MatchOutcome::Winner {
+                        first_player: false,
+                    }),
+                    Ordering::Equal => {
+                        break_tie(config.tie_break, &stats, timer.elapsed.elapsed_secs())
+                    }
+                }
+            } else {
+                None
+            }
+        }
+    };
+
+    if let Some(outcome) = outcome {
+        commands.insert_resource(outcome);
+        next_state.set(GameState::GameOver);
+    }
+}
+
+// None means the tie stands and the match goes on
+fn break_tie(rule: TieBreak, stats: &MatchStats, now: f32) -> Option<MatchOutcome> {
+    let [p1, p2] = &stats.players;
+    let compare = match rule {
+        TieBreak::SuddenDeath => return None,
+        TieBreak::Draw => Ordering::Equal,
+        TieBreak::LongestRally => p1.longest_rally.cmp(&p2.longest_rally),
+        TieBreak::RecentGoals => {
+            let recent = |player: &PlayerMatchStats| {
+                player
+                    .goal_times
+                    .iter()
+                    .filter(|time| **time >= now - 60.)
+                    .count()
+            };
+            // Goals scored by one player were conceded by the other, so more recent goals wins
+            recent(p1).cmp(&recent(p2))
+        }
+    };
+    Some(match compare {
+        Ordering::Greater => MatchOutcome::Winner { first_player: true },
+        Ordering::Less => MatchOutcome::Winner {
+            first_player: false,
+        },
+        Ordering::Equal => MatchOutcome::Draw,
+    })
+}
+
+fn tick_match_timer(
+    time: Res<Time>,
+    mut timer: ResMut<MatchTimer>,
+    mut target: Query<&mut Text, With<WinningScoreMarker>>,
+) {
+    timer.elapsed.tick(time.delta());
+    let Some(remaining) = timer.remaining() else {
+        return;
+    };
+    if let Ok(mut text) = target.get_single_mut() {
+        text.sections[0].value = if remaining > 0. {
+            let seconds = remaining.ceil() as u32;
+            format!("{}:{:02}", seconds / 60, seconds % 60)
+        } else {
+            "Sudden death".to_string()
+        };
+    }
+}
+
+pub fn record_lifetime_stats(
+    outcome: Res<MatchOutcome>,
+    stats: Res<MatchStats>,
+    mut lifetime: ResMut<PlayerStats>,
+    config: Res<GameConfig>,
+) {
+    // A co-op run is shared, it isn't the first player's own
+    if config.mode == GameMode::Coop {
+        return;
+    }
+    lifetime.record(&outcome, &stats.players[0]);
+    if let Err(error) = save_config_file(STATS_FILE, &*lifetime) {
+        warn!("Could not save stats: {error}");
+    }
+}
+
+// The next game starts from a fresh court, the match stats and timer carry on
+fn start_next_game(
+    mut commands: Commands,
+    leftovers: Query<
+        Entity,
+        Or<(
+            With<Ball>,
+            With<ParkedBall>,
+            With<PendingServe>,
+            With<DefensiveWall>,
+        )>,
+    >,
+    mut paddles: Query<(
+        &PaddleStart,
+        &mut Position,
+        &mut PreviousPosition,
+        &mut LinearVelocity,
+        &mut Transform,
+        &mut PaddleSize,
+    )>,
+    mut score: ResMut<Score>,
+    mut rally: ResMut<RallyCounter>,
+    mut point_ball_count: ResMut<PointBallCount>,
+    mut countdown: ResMut<ServeCountdown>,
+    mut celebration: ResMut<GoalCelebration>,
+) {
+    for entity in leftovers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (start, mut position, mut previous, mut velocity, mut transform, mut size) in
+        paddles.iter_mut()
+    {
+        position.0 = start.0;
+        previous.0 = start.0;
+        velocity.0 = Vec2::ZERO;
+        transform.translation = start.0.extend(transform.translation.z);
+        *size = PaddleSize::default();
+    }
+    *score = Score::default();
+    rally.0 = 0;
+    point_ball_count.0 = 0;
+    countdown.0 = None;
+    celebration.0 = None;
+}
+
+// Everything restart_match puts back to how a match starts
+#[derive(SystemParam)]
+struct MatchReset<'w, 's> {
+    pub balls: Query<'w, 's, Entity, Or<(With<Ball>, With<ParkedBall>)>>,
+    pub paddle_sizes: Query<'w, 's, &'static mut PaddleSize>,
+    pub defensive_walls: Query<'w, 's, Entity, With<DefensiveWall>>,
+    pub score: ResMut<'w, Score>,
+    pub survival: ResMut<'w, SurvivalRun>,
+    pub rally: ResMut<'w, RallyCounter>,
+    pub point_ball_count: ResMut<'w, PointBallCount>,
+    pub stats: ResMut<'w, MatchStats>,
+    pub timer: ResMut<'w, MatchTimer>,
+    pub wall_charges: ResMut<'w, DefensiveWallCharges>,
+    pub serves: ResMut<'w, ServeQueue>,
+}
+
+fn restart_match(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut reset: MatchReset,
+    config: Res<GameConfig>,
+    court: Res<Court>,
+    tournament: Option<Res<Tournament>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keys.just_pressed(bindings.restart) {
+        return;
+    }
+    // The champion's screen leads back to the menu, anything else starts the next match
+    if tournament.is_some_and(|tournament| tournament.champion().is_some()) {
+        next_state.set(GameState::Menu);
+        return;
+    }
+    for mut size in reset.paddle_sizes.iter_mut() {
+        *size = PaddleSize::default();
+    }
+    // Every match of a tournament replays the serve sequence from the start
+    *reset.serves = ServeQueue::from_config(&config, &court);
+    for entity in reset.balls.iter().chain(reset.defensive_walls.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    *reset.score = Score::default();
+    commands.insert_resource(GamesWon::default());
+    *reset.survival = SurvivalRun::default();
+    *reset.stats = MatchStats::default();
+    *reset.timer = MatchTimer::new(&config);
+    *reset.wall_charges = DefensiveWallCharges::default();
+    reset.rally.0 = 0;
+    reset.point_ball_count.0 = 0;
+    // The rematch starts from where this one ended, not from the match seed
+    commands.remove_resource::<ReplayRecorder>();
+    commands.remove_resource::<MatchSeed>();
+    next_state.set(GameState::Playing);
+}
+
+fn update_score(
+    mut commands: Commands,
+    score: Res<Score>,
+    winning_score: Res<WinningScore>,
+    config: Res<GameConfig>,
+    paddles: Query<(&Paddle, &Sprite)>,
+    mut player_1_score: Query<
+        (Entity, &mut Text),
+        (With<Player1ScoreMarker>, Without<Player2ScoreMarker>),
+    >,
+    mut player_2_score: Query<
+        (Entity, &mut Text),
+        (With<Player2ScoreMarker>, Without<Player1ScoreMarker>),
+    >,
+    mut target: Query<
+        &mut Text,
+        (
+            With<WinningScoreMarker>,
+            Without<Player1ScoreMarker>,
+            Without<Player2ScoreMarker>,
+        ),
+    >,
+) {
+    // Survival, timed, breakout and co-op matches have no target score to emphasize
+    let winning_score = match config.mode {
+        GameMode::Versus | GameMode::PointBallRace | GameMode::FourPlayer => winning_score.0,
+        GameMode::Survival | GameMode::Timed | GameMode::Breakout | GameMode::Coop => usize::MAX,
+    };
+
+    for (first_player, (entity, mut text), value) in [
+        (true, player_1_score.single_mut(), score.first_player()),
+        (false, player_2_score.single_mut(), score.second_player()),
+    ] {
+        // Only a score going up is worth noticing, a restart back to 0 isn't
+        let raised = text.sections[0]
+            .value
+            .parse::<usize>()
+            .is_ok_and(|shown| value > shown);
+        text.sections[0].value = format!("{}", value);
+        emphasize_match_point(&mut text, value, winning_score);
+        if raised {
+            let flash = paddles
+                .iter()
+                .find(|(paddle, _)| paddle.first_player == first_player)
+                .map_or(Color::WHITE, |(_, sprite)| sprite.color);
+            let color = text.sections[0].style.color;
+            text.sections[0].style.color = flash;
+            commands.entity(entity).insert(ScorePunch {
+                timer: Timer::from_seconds(SCORE_PUNCH_SECONDS, TimerMode::Once),
+                flash,
+                color,
+            });
+        }
+    }
+
+    if let Ok(mut target_text) = target.get_single_mut() {
+        match config.mode {
+            GameMode::Versus | GameMode::FourPlayer => {
+                target_text.sections[0].value = format!("First to {}", winning_score);
+            }
+            GameMode::PointBallRace => {
+                target_text.sections[0].value = format!(
+                    "First to {} or {} point balls",
+                    winning_score, config.point_ball_target
+                );
+            }
+            GameMode::Breakout => {
+                target_text.sections[0].value = format!("Clear the bricks, {} lives", config.lives);
+            }
+            GameMode::Survival | GameMode::Timed | GameMode::Coop => {}
+        }
+    }
+}
+
+// Runs every frame, the seat's paddle may not have been spawned yet when the score last changed
+fn update_seat_scores(
+    score: Res<Score>,
+    paddles: Query<(&Paddle, &Sprite)>,
+    mut texts: Query<(&SeatScoreText, &mut Text)>,
+) {
+    for (seat, mut text) in texts.iter_mut() {
+        let value = score.of(&seat.0).to_string();
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+        let color = paddles
+            .iter()
+            .find(|(paddle, _)| paddle.side == seat.0)
+            .map_or(Color::WHITE, |(_, sprite)| sprite.color)
+            .with_a(0.6);
+        if text.sections[0].style.color != color {
+            text.sections[0].style.color = color;
+        }
+    }
+}
+
+fn update_games_won(
+    config: Res<GameConfig>,
+    games: Res<GamesWon>,
+    mut texts: Query<&mut Text, With<GamesWonMarker>>,
+) {
+    let best_of =
+        matches!(config.mode, GameMode::Versus | GameMode::PointBallRace) && config.best_of > 1;
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = if best_of {
+            format!(
+                "Games {} - {}, best of {}",
+                games.first_player, games.second_player, config.best_of
+            )
+        } else {
+            String::new()
+        };
+    }
+}
+
+fn animate_score_punches(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut punches: Query<(Entity, &mut ScorePunch, &mut Text, &mut Transform)>,
+) {
+    for (entity, mut punch, mut text, mut transform) in punches.iter_mut() {
+        punch.timer.tick(time.delta());
+        if punch.timer.finished() {
+            transform.scale = Vec3::ONE;
+            text.sections[0].style.color = punch.color;
+            commands.entity(entity).remove::<ScorePunch>();
+            continue;
+        }
+        let t = punch.timer.percent();
+        // Up and back down in one arc
+        if !config.reduced_motion {
+            transform.scale = Vec3::splat(1. + (SCORE_PUNCH_SCALE - 1.) * (t * PI).sin());
+        }
+        let [r, g, b, a] = punch.flash.as_rgba_f32();
+        let [r2, g2, b2, a2] = punch.color.as_rgba_f32();
+        text.sections[0].style.color = Color::rgba(
+            r + (r2 - r) * t,
+            g + (g2 - g) * t,
+            b + (b2 - b) * t,
+            a + (a2 - a) * t,
+        );
+    }
+}
+
+fn emphasize_match_point(text: &mut Text, score: usize, winning_score: usize) {
+    let match_point = score + 1 == winning_score;
+    let style = &mut text.sections[0].style;
+    style.font_size = if match_point { 64.0 } else { 50.0 };
+    style.color = if match_point {
+        Color::GOLD
+    } else {
+        Color::WHITE
+    };
+}
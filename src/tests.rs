@@ -0,0 +1,773 @@
+use bevy::input::InputPlugin;
+
+use crate::arena::{spawn_arena, ArenaAssets, ArenaDefinition, Court, NET_WIDTH};
+use crate::audio::{AudioAssets, AudioSettings};
+use crate::ball::{
+    apply_delayed_external_forces, check_goals, launch_pending_serves, parse_serves,
+    remove_scored_balls, spawn_ball, spawn_ball_at, Ball, BallAssets, BallKind, BallSpawnConfig,
+    NextServe, ParkedBall, PointBallCount, ServeQueue, BALL_RADIUS, MAX_SERVES,
+};
+use crate::config::GameConfig;
+use crate::cosmetics::CosmeticsConfig;
+use crate::input::{PaddleDash, TouchScreen};
+use crate::net::{simulation_checksum, update_simulation_checksum, SimulationChecksum, Snapshot};
+use crate::paddle::{
+    steer_paddle, Paddle, PaddleBounds, PaddleController, PADDLE_SIZE, PADDLE_SPEED,
+};
+use crate::prelude::*;
+use crate::replay::{
+    advance_replay, advance_replay_step, move_replay_paddles, record_replay_frame,
+    record_replay_step, Replay, ReplayFrame, ReplayPlayback, ReplayRecorder,
+};
+use crate::score::{apply_goals, PlayerStats, Score, SurvivalRun};
+use crate::GameplayPlugin;
+
+// Mirrored runs may drift apart by this much from float rounding
+const MIRROR_TOLERANCE: f32 = 0.5;
+// One minute of play at FIXED_TIMESTEP
+const DETERMINISM_TICKS: usize = 3600;
+
+fn headless_app(config: GameConfig) -> App {
+    let mut app = App::new();
+    let physics = PhysicsPlugins::new(FixedUpdate);
+    // Every update is exactly one fixed step
+    app.add_plugins((MinimalPlugins, TransformPlugin, HierarchyPlugin, physics))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FIXED_TIMESTEP))
+        .insert_resource(FixedTime::new(FIXED_TIMESTEP))
+        .insert_resource(PhysicsTimestep::FixedOnce(FIXED_TIMESTEP.as_secs_f32()))
+        .insert_resource(Gravity::ZERO)
+        .insert_resource(GameRng::new(Some(0)))
+        .insert_resource(ServeQueue::from_config(&config, &Court::default()))
+        .init_resource::<Court>()
+        .init_resource::<TouchScreen>()
+        .init_resource::<PaddleDash>()
+        .init_resource::<CosmeticsConfig>()
+        .insert_resource(config.game_speed)
+        .insert_resource(config)
+        .init_resource::<Score>()
+        .init_resource::<PointBallCount>()
+        .init_resource::<SimulationChecksum>()
+        .add_event::<GoalScored>()
+        .add_systems(
+            Startup,
+            |mut commands: Commands, config: Res<GameConfig>| {
+                let controllers = [true, false].map(|first_player| {
+                    PaddleController::for_paddle(first_player, &config, false, false)
+                });
+                let (arena, cosmetics) = (ArenaDefinition::default(), CosmeticsConfig::default());
+                let shapes = config.paddle_shapes;
+                spawn_arena(
+                    &mut commands,
+                    &config,
+                    &cosmetics,
+                    &arena,
+                    controllers,
+                    shapes,
+                );
+            },
+        )
+        .add_systems(
+            FixedUpdate,
+            apply_delayed_external_forces.before(PhysicsSet::Prepare),
+        )
+        .add_systems(
+            FixedUpdate,
+            update_simulation_checksum.after(PhysicsSet::Sync),
+        )
+        .add_systems(
+            Update,
+            (check_goals, (apply_goals, remove_scored_balls)).chain(),
+        );
+    app.finish();
+    app.cleanup();
+    app
+}
+
+fn headless_spawn_ball(app: &mut App, position: Vec2, direction: Vec2) {
+    let ball_assets = BallAssets {
+        point_ball: Handle::default(),
+    };
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, &app.world);
+    let cosmetics = CosmeticsConfig::default();
+    let kind = BallKind::Normal;
+    spawn_ball_at(
+        &mut commands,
+        &ball_assets,
+        &cosmetics,
+        position,
+        direction,
+        0,
+        kind,
+    );
+    queue.apply(&mut app.world);
+}
+
+fn headless_ball_positions(app: &mut App) -> Vec<Vec2> {
+    let mut balls = app
+        .world
+        .query_filtered::<(Entity, &Position), With<Ball>>();
+    let mut positions = balls.iter(&app.world).collect::<Vec<_>>();
+    positions.sort_by_key(|(entity, _)| *entity);
+    positions
+        .into_iter()
+        .map(|(_, position)| position.0)
+        .collect()
+}
+
+fn mirrored(v: Vec2) -> Vec2 {
+    Vec2::new(-v.x, v.y)
+}
+
+fn headless_checksums(app: &mut App, steps: usize) -> Vec<u64> {
+    (0..steps)
+        .map(|_| {
+            app.update();
+            app.world.resource::<SimulationChecksum>().value
+        })
+        .collect()
+}
+
+// Runs the serving and scoring game loop headless, for checks that need a match going on its own
+fn headless_match_app(config: GameConfig) -> App {
+    let spawn_config = BallSpawnConfig::for_config(&config);
+    let mut app = headless_app(config);
+    app.insert_resource(spawn_config)
+        .insert_resource(BallAssets {
+            point_ball: Handle::default(),
+        })
+        .insert_resource(AudioAssets {
+            paddle_hits: Vec::new(),
+            wall_bounces: Vec::new(),
+            serve_charge: Handle::default(),
+            golden_ball: Handle::default(),
+            goal: Handle::default(),
+            menu_click: Handle::default(),
+        })
+        .init_resource::<AudioSettings>()
+        .init_resource::<SurvivalRun>()
+        .init_resource::<NextServe>()
+        .add_systems(
+            Update,
+            (spawn_ball, launch_pending_serves)
+                .chain()
+                .after(remove_scored_balls),
+        );
+    app
+}
+
+// The game's gameplay plugins headless, with the AI on both paddles the match starts on the first
+// update and plays itself. Being a demo match, nothing is saved to the player's stats
+fn gameplay_app(config: GameConfig) -> App {
+    let mut app = App::new();
+    let physics = PhysicsPlugins::new(FixedUpdate);
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        HierarchyPlugin,
+        AssetPlugin::default(),
+        InputPlugin,
+        physics,
+    ))
+    .insert_resource(TimeUpdateStrategy::ManualDuration(FIXED_TIMESTEP))
+    .insert_resource(FixedTime::new(FIXED_TIMESTEP))
+    .insert_resource(PhysicsTimestep::FixedOnce(FIXED_TIMESTEP.as_secs_f32()))
+    .insert_resource(Gravity::ZERO)
+    .insert_resource(config.game_speed)
+    .insert_resource(config.key_bindings.clone())
+    .insert_resource(config)
+    .insert_resource(DemoMatch)
+    .insert_resource(BallAssets {
+        point_ball: Handle::default(),
+    })
+    .insert_resource(ArenaAssets(vec![]))
+    .insert_resource(AudioAssets {
+        paddle_hits: Vec::new(),
+        wall_bounces: Vec::new(),
+        serve_charge: Handle::default(),
+        golden_ball: Handle::default(),
+        goal: Handle::default(),
+        menu_click: Handle::default(),
+    })
+    .init_resource::<AudioSettings>()
+    .init_resource::<CosmeticsConfig>()
+    .init_resource::<PlayerStats>()
+    .init_resource::<SimulationChecksum>()
+    .add_state::<GameState>()
+    .insert_resource(State::new(GameState::Menu))
+    .insert_resource(NextState(Some(GameState::Playing)))
+    .add_plugins(GameplayPlugin)
+    .add_systems(
+        FixedUpdate,
+        update_simulation_checksum.after(PhysicsSet::Sync),
+    );
+    app.finish();
+    app.cleanup();
+    app
+}
+
+// The same scenario played on both sides of the net must produce mirrored outcomes
+#[test]
+fn mirror_match() {
+    let arena = ArenaDefinition::default();
+    let (left, right) = (
+        arena.paddle_bounds(&Side::Left),
+        arena.paddle_bounds(&Side::Right),
+    );
+    for x in [-700., -640., -630., -400., -330., -300.] {
+        for y in [-450., 0., 450.] {
+            for velocity in [
+                Vec2::new(-100., -100.),
+                Vec2::new(100., 100.),
+                Vec2::new(-100., 100.),
+            ] {
+                let (mut left_position, mut left_velocity) = (Vec2::new(x, y), velocity);
+                left.clamp(&mut left_position, &mut left_velocity);
+                let (mut right_position, mut right_velocity) =
+                    (mirrored(Vec2::new(x, y)), mirrored(velocity));
+                right.clamp(&mut right_position, &mut right_velocity);
+
+                assert!(
+                    mirrored(left_position) == right_position
+                        && mirrored(left_velocity) == right_velocity,
+                    "paddle bounds differ at ({x}, {y}) moving {velocity}: left {left_position} {left_velocity}, right {right_position} {right_velocity}"
+                );
+            }
+        }
+    }
+
+    let mut left_app = headless_app(GameConfig::default());
+    let mut right_app = headless_app(GameConfig::default());
+    let (position, direction) = (Vec2::new(-50., 40.), Vec2::new(-0.8, 0.35));
+    headless_spawn_ball(&mut left_app, position, direction);
+    headless_spawn_ball(&mut right_app, mirrored(position), mirrored(direction));
+
+    for step in 0..600 {
+        left_app.update();
+        right_app.update();
+
+        let left_balls = headless_ball_positions(&mut left_app);
+        let right_balls = headless_ball_positions(&mut right_app);
+        assert_eq!(
+            left_balls.len(),
+            right_balls.len(),
+            "step {step}: the runs hold different numbers of balls"
+        );
+        for (left_ball, right_ball) in left_balls.into_iter().zip(right_balls) {
+            assert!(
+                mirrored(left_ball).distance(right_ball) <= MIRROR_TOLERANCE,
+                "step {step}: ball at {left_ball} is not mirrored by {right_ball}"
+            );
+        }
+    }
+
+    let left_score = left_app.world.resource::<Score>();
+    let right_score = right_app.world.resource::<Score>();
+    assert!(
+        left_score.first_player() + left_score.second_player() > 0,
+        "no goal was scored"
+    );
+    assert_eq!(
+        (left_score.first_player(), left_score.second_player()),
+        (right_score.second_player(), right_score.first_player()),
+        "scores are not swapped"
+    );
+}
+
+// Both paddles are driven flat out at the net, neither may ever poke across it
+#[test]
+fn paddles_stay_behind_net() {
+    let mut app = headless_app(GameConfig::default());
+    app.add_systems(
+        FixedUpdate,
+        (|fixed_time: Res<FixedTime>,
+          mut paddles: Query<(&mut Position, &mut LinearVelocity, &Paddle, &PaddleBounds)>| {
+            for (mut position, mut velocity, paddle, bounds) in paddles.iter_mut() {
+                let target_x = if paddle.side == Side::Left { WINDOW_SIZE.x } else { -WINDOW_SIZE.x };
+                steer_paddle(
+                    bounds,
+                    Vec2::new(target_x, 0.),
+                    PADDLE_SPEED,
+                    fixed_time.period.as_secs_f32(),
+                    &mut position.0,
+                    &mut velocity.0,
+                );
+            }
+        })
+        .before(PhysicsSet::Prepare),
+    );
+
+    let limit = NET_WIDTH / 2. + PADDLE_SIZE.x / 2.;
+    for step in 0..600 {
+        app.update();
+        let mut paddles = app.world.query::<(&Position, &Paddle)>();
+        for (position, paddle) in paddles.iter(&app.world) {
+            let crossed = match paddle.side {
+                Side::Left => position.x > -limit,
+                _ => position.x < limit,
+            };
+            assert!(
+                !crossed,
+                "step {step}: {:?} paddle reached x {} past the net",
+                paddle.side, position.x
+            );
+        }
+    }
+}
+
+// Two identical runs hash identically every tick, and nudging one ball is caught
+#[test]
+fn checksum_determinism() {
+    let scenario = [
+        (Vec2::new(-50., 40.), Vec2::new(-0.8, 0.35)),
+        (Vec2::new(120., -200.), Vec2::new(0.6, 0.8)),
+    ];
+    let run = |nudge: Vec2| {
+        let mut app = headless_app(GameConfig::default());
+        for (i, (position, direction)) in scenario.into_iter().enumerate() {
+            let position = if i == 0 { position + nudge } else { position };
+            headless_spawn_ball(&mut app, position, direction);
+        }
+        headless_checksums(&mut app, 300)
+    };
+
+    let first = run(Vec2::ZERO);
+    let second = run(Vec2::ZERO);
+    let diverged = first.iter().zip(&second).position(|(a, b)| a != b);
+    assert_eq!(diverged, None, "identical runs diverged");
+    assert_ne!(
+        first,
+        run(Vec2::new(0.01, 0.)),
+        "a nudged ball went unnoticed"
+    );
+
+    let balls = vec![
+        (Vec2::new(1., 2.), Vec2::new(3., 4.), 1),
+        (Vec2::new(-5., 6.), Vec2::ZERO, 0),
+    ];
+    let mut reversed = balls.clone();
+    reversed.reverse();
+    let paddles = vec![
+        (Side::Left, Vec2::new(-600., 0.)),
+        (Side::Right, Vec2::new(600., 0.)),
+    ];
+    let mut swapped = paddles.clone();
+    swapped.reverse();
+    assert_eq!(
+        simulation_checksum(balls, paddles, &Score::default()),
+        simulation_checksum(reversed, swapped, &Score::default()),
+        "the checksum depends on iteration order"
+    );
+}
+
+// Plays the same match twice side by side through the game's own gameplay plugins, a tick where
+// the checksums disagree points at randomness or ordering outside the seed
+fn first_divergence(config: GameConfig, add_systems: impl Fn(&mut App)) -> Option<usize> {
+    let mut main = gameplay_app(config.clone());
+    let mut shadow = gameplay_app(config);
+    add_systems(&mut main);
+    add_systems(&mut shadow);
+    (0..DETERMINISM_TICKS).find(|_| {
+        main.update();
+        shadow.update();
+        let expected = main.world.resource::<SimulationChecksum>().value;
+        expected != shadow.world.resource::<SimulationChecksum>().value
+    })
+}
+
+#[test]
+fn seeded_matches_stay_in_step() {
+    let config = GameConfig {
+        seed: Some(0),
+        power_ups: true,
+        ..default()
+    };
+    assert_eq!(first_divergence(config, |_| {}), None);
+}
+
+// The check catches gameplay that draws from the thread's RNG instead of GameRng
+#[test]
+fn unseeded_randomness_falls_out_of_step() {
+    fn jitter(mut balls: Query<&mut LinearVelocity, With<Ball>>) {
+        for mut velocity in balls.iter_mut() {
+            velocity.0 *= 1. + rand::random::<f32>() * 0.01;
+        }
+    }
+    let config = GameConfig {
+        seed: Some(0),
+        ..default()
+    };
+    let diverged = first_divergence(config, |app| {
+        app.add_systems(FixedUpdate, jitter.in_set(StepSet::Forces));
+    });
+    assert!(diverged.is_some(), "the unseeded call went unnoticed");
+}
+
+// With reset in place a scored ball is parked out of play, then the next serve takes it back
+#[test]
+fn reset_balls_on_goal() {
+    let mut app = headless_match_app(GameConfig {
+        reset_balls_on_goal: true,
+        ..default()
+    });
+    headless_spawn_ball(&mut app, Vec2::new(-300., 300.), Vec2::new(-1., 0.));
+    let mut balls = app.world.query_filtered::<Entity, With<Ball>>();
+    let ball = balls.single(&app.world);
+
+    let mut parked = false;
+    for step in 0..600 {
+        app.update();
+        assert!(
+            app.world.get_entity(ball).is_some(),
+            "step {step}: the ball was despawned"
+        );
+        if app.world.get::<ParkedBall>(ball).is_some() {
+            parked = true;
+            assert!(app.world.get::<Ball>(ball).is_none());
+            assert_eq!(app.world.get::<Visibility>(ball), Some(&Visibility::Hidden));
+            continue;
+        }
+        if !parked {
+            continue;
+        }
+        // Served again through the usual serve, from the serve spot with nothing left over
+        let position = app.world.get::<Position>(ball).unwrap().0;
+        let previous = app.world.get::<PreviousPosition>(ball).unwrap().0;
+        assert!(app.world.get::<Ball>(ball).is_some());
+        assert_eq!(
+            app.world.get::<Visibility>(ball),
+            Some(&Visibility::Inherited)
+        );
+        assert_eq!(position.x, 0., "served from {position}");
+        assert_eq!(previous, position);
+        assert_eq!(app.world.get::<AngularVelocity>(ball).unwrap().0, 0.);
+        return;
+    }
+    panic!("the ball was never parked and served again, parked: {parked}");
+}
+
+// Drill serves go top-left, bottom-left, top-right, bottom-right and then start over
+#[test]
+fn corner_drill_order() {
+    let config = GameConfig {
+        corner_drill: true,
+        ..default()
+    };
+    let mut serves = ServeQueue::from_config(&config, &Court::default());
+    let mut rng = GameRng::new(Some(0));
+    let expected = [(-1., 1.), (-1., -1.), (1., 1.), (1., -1.)];
+    for serve in 0..expected.len() * 2 {
+        let direction = serves.next(Side::Random, &mut rng);
+        let (x, y) = expected[serve % expected.len()];
+        assert!(
+            direction.x.signum() == x && direction.y.signum() == y,
+            "serve {serve} went {direction}, expected toward ({x}, {y})"
+        );
+    }
+}
+
+#[test]
+fn tampered_serve_files_are_rejected() {
+    let valid = "# warm up\nleft 20\nright -35.5\n";
+    assert_eq!(parse_serves(valid).map(|serves| serves.len()), Ok(2));
+
+    let too_many = "left 10\n".repeat(MAX_SERVES + 1);
+    let tampered = [
+        "left NaN",
+        "right inf",
+        "left -inf",
+        "right 90",
+        "up 10",
+        "left 20 40",
+        "left",
+        too_many.as_str(),
+    ];
+    for contents in tampered {
+        let preview = contents.lines().next().unwrap_or_default();
+        assert!(parse_serves(contents).is_err(), "{preview:?} was accepted");
+    }
+}
+
+#[test]
+fn tampered_arena_files_are_rejected() {
+    let arena = |half_size: &str, area_max: &str, obstacle: &str| {
+        format!(
+            "(half_size: {half_size}, net_width: 5.0, goal_depth: 5.0, \
+             paddle_start: (-100.0, 0.0), paddle_area_min: (-120.0, -80.0), \
+             paddle_area_max: {area_max}, \
+             obstacles: [(shape: {obstacle}, position: (-60.0, 40.0))])"
+        )
+    };
+    let circle = "Circle(radius: 10.0)";
+    // Smaller than the window, power-ups still find room to spawn in it
+    let small = arena("(120.0, 80.0)", "(-80.0, 80.0)", circle);
+    assert!(ArenaDefinition::parse(&small).is_ok());
+
+    let tampered = [
+        arena("(NaN, 80.0)", "(-80.0, 80.0)", circle),
+        arena("(120.0, 0.0)", "(-80.0, 80.0)", circle),
+        arena("(-120.0, 80.0)", "(-80.0, 80.0)", circle),
+        arena("(120.0, inf)", "(-80.0, 80.0)", circle),
+        arena("(120.0, 80.0)", "(-160.0, 80.0)", circle),
+        arena("(120.0, 80.0)", "(-80.0, -100.0)", circle),
+        arena("(120.0, 80.0)", "(-80.0, NaN)", circle),
+        arena("(120.0, 80.0)", "(-80.0, 80.0)", "Circle(radius: -10.0)"),
+        arena(
+            "(120.0, 80.0)",
+            "(-80.0, 80.0)",
+            "Block(width: NaN, height: 10.0)",
+        ),
+    ];
+    for contents in tampered {
+        assert!(
+            ArenaDefinition::parse(&contents).is_err(),
+            "{contents:?} was accepted"
+        );
+    }
+}
+
+#[test]
+fn lan_snapshots_survive_the_wire() {
+    let snapshot = Snapshot {
+        score: (3, 7),
+        spectators: 2,
+        outcome: Some(MatchOutcome::Winner {
+            first_player: false,
+        }),
+        paddles: vec![
+            (true, Vec2::new(-280.5, 12.25), 0.8, 0.5),
+            (false, Vec2::new(280., -1. / 3.), 1.3, -0.125),
+        ],
+        balls: vec![(42, Vec2::new(0.1, -99.9), BALL_RADIUS, BallKind::Golden)],
+    };
+    let message = snapshot.to_message();
+    let parsed = Snapshot::parse(&message).expect("the snapshot did not parse");
+    assert_eq!(parsed.to_message(), message);
+    // Every way a match can end reaches the client as it is, none turns into a draw
+    let outcomes = [
+        MatchOutcome::Winner { first_player: true },
+        MatchOutcome::Draw,
+        MatchOutcome::SeatWinner { side: Side::Top },
+        MatchOutcome::SurvivalOver {
+            time: 61.25,
+            returns: 14,
+        },
+    ];
+    for outcome in outcomes {
+        let message = Snapshot {
+            outcome: Some(outcome.clone()),
+            paddles: vec![],
+            balls: vec![],
+            ..snapshot
+        }
+        .to_message();
+        let parsed = Snapshot::parse(&message).expect("the snapshot did not parse");
+        assert_eq!(
+            format!("{:?}", parsed.outcome),
+            format!("{:?}", Some(outcome))
+        );
+    }
+    for tampered in [
+        "S 1 2 - 0 P 1 NaN 0 1",
+        "S 1 2 x 0",
+        "S 1 2 Random 0",
+        "S 1 2 NaN/3 0",
+        "S 1 2 12/-1 0",
+        "S 1 2 - 0 B 1 0 0 10 q",
+        "S 1 2 - 0 P 1 0",
+        "S 1 2 -",
+    ] {
+        assert!(
+            Snapshot::parse(tampered).is_none(),
+            "{tampered:?} was accepted"
+        );
+    }
+}
+
+#[test]
+fn arena_files_load_clear_of_serves_and_paddles() {
+    for name in ArenaDefinition::BUILT_IN {
+        let path = format!(
+            "{}/assets/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            ArenaDefinition::path(name)
+        );
+        let contents = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{path}: {e}"));
+        let arena = ArenaDefinition::parse(&contents).unwrap_or_else(|e| panic!("{path}: {e}"));
+        // The open arena file is the built-in fallback written out, the two must not drift apart
+        if name == ArenaDefinition::BUILT_IN[0] {
+            assert!(
+                arena == ArenaDefinition::default(),
+                "{path} differs from the built-in open arena"
+            );
+        }
+
+        let paddle_areas = [Side::Left, Side::Right].map(|side| {
+            let bounds = arena.paddle_bounds(&side);
+            (
+                bounds.min_wall - PADDLE_SIZE / 2.,
+                bounds.max_wall + PADDLE_SIZE / 2.,
+            )
+        });
+        for obstacle in arena.obstacles() {
+            let (position, extent) = (obstacle.position(), obstacle.shape.extent());
+            // The serve spot and its fallbacks sit on the net line
+            assert!(
+                position.x.abs() - extent.x >= BALL_RADIUS * 2. + arena.net_width,
+                "{name} has an obstacle on the serve line at {position}"
+            );
+            let (min, max) = (position - extent, position + extent);
+            let overlaps = |(area_min, area_max): &(Vec2, Vec2)| {
+                min.cmplt(*area_max).all() && max.cmpgt(*area_min).all()
+            };
+            assert!(
+                !paddle_areas.iter().any(overlaps),
+                "{name} has an obstacle in reach of a paddle at {position}"
+            );
+        }
+    }
+}
+
+// A match steered by a scripted player, saved and loaded back, plays out identically
+#[test]
+fn replays_play_back_the_recorded_match() {
+    // Stands in for the player's input, weaving up and down and pushing into the net
+    let script = |fixed_time: Res<FixedTime>,
+                  mut steps: Local<u32>,
+                  mut recorder: Option<ResMut<ReplayRecorder>>,
+                  mut paddles: Query<(
+        &mut LinearVelocity,
+        &mut Position,
+        &PaddleBounds,
+        &Paddle,
+        &PaddleController,
+    )>| {
+        *steps += 1;
+        for (mut velocity, mut position, bounds, paddle, controller) in paddles.iter_mut() {
+            if *controller != PaddleController::Player {
+                continue;
+            }
+            // Every so often the player lets go
+            let target = (*steps % 50 >= 10)
+                .then(|| position.0 + Vec2::new(300., (*steps as f32 * 0.05).sin() * 200.));
+            ReplayRecorder::record_target(&mut recorder, paddle, target);
+            if let Some(target) = target {
+                let delta = fixed_time.period.as_secs_f32();
+                steer_paddle(
+                    bounds,
+                    target,
+                    PADDLE_SPEED,
+                    delta,
+                    &mut position.0,
+                    &mut velocity.0,
+                );
+            } else {
+                velocity.0 = Vec2::ZERO;
+            }
+        }
+    };
+    // Both runs are already in a match, as the replay plugin's systems expect
+    let app = || {
+        let mut app = headless_app(GameConfig::default());
+        app.add_state::<GameState>()
+            .insert_resource(State::new(GameState::Playing))
+            .add_systems(
+                FixedUpdate,
+                (
+                    (script, move_replay_paddles),
+                    record_replay_step.run_if(resource_exists::<ReplayRecorder>()),
+                    advance_replay_step.run_if(resource_exists::<ReplayPlayback>()),
+                )
+                    .chain()
+                    .before(PhysicsSet::Prepare),
+            )
+            .add_systems(
+                Last,
+                (
+                    record_replay_frame.run_if(resource_exists::<ReplayRecorder>()),
+                    advance_replay.run_if(resource_exists::<ReplayPlayback>()),
+                ),
+            );
+        headless_spawn_ball(&mut app, Vec2::new(-100., 0.), Vec2::new(-1., 0.2));
+        // Spawns the paddles before anything is recorded
+        app.update();
+        app
+    };
+    let steps = 240;
+
+    let mut recording = app();
+    let controllers = [PaddleController::Player, PaddleController::Ai];
+    let replay = Replay::new(&GameConfig::default(), 0, controllers);
+    recording.insert_resource(ReplayRecorder::new(replay));
+    let recorded = headless_checksums(&mut recording, steps);
+    let replay = recording
+        .world
+        .remove_resource::<ReplayRecorder>()
+        .unwrap()
+        .replay;
+    let saved = ron::to_string(&replay).unwrap();
+    let replay = Replay::parse(&saved).unwrap();
+    assert_eq!(replay.frames.len(), steps);
+
+    let mut playing = app();
+    let mut paddles = playing.world.query::<&mut PaddleController>();
+    for mut controller in paddles.iter_mut(&mut playing.world) {
+        if *controller == PaddleController::Player {
+            *controller = PaddleController::Replay;
+        }
+    }
+    let checksum = replay.checksum;
+    playing.insert_resource(ReplayPlayback::new(replay, GameConfig::default()));
+    let played = headless_checksums(&mut playing, steps);
+    let diverged = recorded.iter().zip(&played).position(|(a, b)| a != b);
+    assert_eq!(diverged, None, "the replay diverged");
+    assert_eq!(played.last(), Some(&checksum));
+    // Playback ran out of frames and leaves the replay for the menu
+    assert_eq!(playing.world.resource::<ReplayPlayback>().next, steps);
+    let next_state = playing.world.resource::<NextState<GameState>>();
+    assert_eq!(next_state.0, Some(GameState::Menu));
+}
+
+#[test]
+fn tampered_replays_are_rejected() {
+    let controllers = [PaddleController::Player, PaddleController::Ai];
+    let mut valid = Replay::new(&GameConfig::default(), 0, controllers);
+    valid.frames.push(ReplayFrame {
+        delta_nanos: FIXED_TIMESTEP.as_nanos() as u64,
+        steps: vec![[Some([-500., 120.]), None]],
+        ..default()
+    });
+    assert!(Replay::parse(&ron::to_string(&valid).unwrap()).is_ok());
+
+    let tampered: [(&str, fn(&mut Replay)); 10] = [
+        ("NaN target", |replay| {
+            replay.frames[0].steps[0][0] = Some([f32::NAN, 0.])
+        }),
+        ("far target", |replay| {
+            replay.frames[0].steps[0][1] = Some([0., 1e9])
+        }),
+        ("NaN tilt", |replay| replay.frames[0].tilt = f32::NAN),
+        ("steep tilt", |replay| replay.frames[0].tilt = -3.),
+        ("empty frame time", |replay| {
+            replay.frames[0].delta_nanos = 0
+        }),
+        ("huge frame time", |replay| {
+            replay.frames[0].delta_nanos = u64::MAX
+        }),
+        ("too many steps", |replay| {
+            let step = replay.frames[0].steps[0];
+            replay.frames[0].steps = vec![step; 1000];
+        }),
+        ("no winning score", |replay| replay.rules.winning_score = 0),
+        ("even best of", |replay| replay.rules.best_of = 2),
+        ("NaN catch assist", |replay| {
+            replay.rules.catch_assist = f32::NAN
+        }),
+    ];
+    for (name, tamper) in tampered {
+        let mut replay = valid.clone();
+        tamper(&mut replay);
+        let contents = ron::to_string(&replay).unwrap();
+        assert!(Replay::parse(&contents).is_err(), "{name} was accepted");
+    }
+}
@@ -0,0 +1,923 @@
+use crate::arena::{Brick, Court, Obstacle, Wall};
+use crate::audio::{AudioAssets, AudioSettings, SfxRng, SoundEffect};
+use crate::ball::{Ball, BALL_RADIUS};
+use crate::config::{GameConfig, GameSpeed};
+use crate::input::{key_name, KeyBindings};
+use crate::paddle::Paddle;
+use crate::prelude::*;
+use crate::score::{
+    record_lifetime_stats, GamesWon, MatchStats, MatchTimer, PlayerStats, Score, SurvivalRun,
+    SurvivalScore, Tournament, INTERMISSION_SECONDS,
+};
+
+// Game over and intermission screens, the scoreboard window, particles, the camera and display
+// options
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScreenShake>()
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                spawn_game_over_screen.after(record_lifetime_stats),
+            )
+            .add_systems(OnExit(GameState::GameOver), despawn_game_over_screen)
+            .add_systems(OnEnter(GameState::Intermission), spawn_intermission_screen)
+            .add_systems(OnExit(GameState::Intermission), despawn_intermission_screen)
+            .add_systems(
+                Update,
+                (
+                    reveal_game_over_screen.run_if(in_state(GameState::GameOver)),
+                    tick_intermission.run_if(in_state(GameState::Intermission)),
+                    fade_out,
+                    (spawn_collision_particles, move_particles).chain(),
+                    fit_camera_to_court,
+                    (add_shake_trauma, shake_camera).chain(),
+                    cycle_display_mode,
+                    apply_display_mode.run_if(resource_changed::<GameConfig>()),
+                    apply_ui_scale.run_if(resource_changed::<GameConfig>()),
+                    update_scoreboard_window.run_if(resource_exists::<MatchTimer>()),
+                    toggle_controls_help,
+                ),
+            );
+    }
+}
+
+const GAME_OVER_REVEAL_INTERVAL: f32 = 0.4;
+
+// Camera offset at full trauma and full shake intensity, trauma drains at SHAKE_DECAY per second
+const SHAKE_MAX_OFFSET: f32 = 24.;
+const SHAKE_DECAY: f32 = 1.5;
+const SHAKE_GOAL_TRAUMA: f32 = 0.6;
+// Returns faster than this fraction of the top ball speed shake the camera, harder ones more
+const SHAKE_HARD_HIT_SPEED: f32 = 0.6;
+
+// Text and menus are drawn between these sizes, each press of the option adds a step
+pub const UI_SCALE_MIN: f32 = 0.75;
+pub const UI_SCALE_MAX: f32 = 2.;
+pub const UI_SCALE_STEP: f32 = 0.25;
+
+// Sparks thrown from where a ball bounces, goals throw GOAL_PARTICLES faster ones
+const HIT_PARTICLES: usize = 10;
+const GOAL_PARTICLES: usize = 48;
+const PARTICLE_SPEED: f32 = 220.;
+const PARTICLE_SIZE: f32 = 5.;
+const PARTICLE_LIFETIME: f32 = 0.4;
+// Fraction of their speed particles keep after one second
+const PARTICLE_DRAG: f32 = 0.05;
+
+#[derive(Component)]
+struct IntermissionScreen {
+    pub timer: Timer,
+}
+
+#[derive(Component)]
+struct GameOverScreen;
+
+// Game over rows appear one by one, in step order
+#[derive(Component)]
+struct RevealStep(pub usize);
+
+#[derive(Component)]
+struct GameOverReveal {
+    pub timer: Timer,
+    pub next: usize,
+}
+
+#[derive(Component)]
+struct ControlsHelpOverlay;
+
+#[derive(Component)]
+pub struct FadeOut {
+    pub timer: Timer,
+    pub start_alpha: f32,
+}
+
+// Cosmetic spark, faded out by FadeOut
+#[derive(Component)]
+struct Particle {
+    pub velocity: Vec2,
+}
+
+// Builds up on goals and hard hits and drains over time, the camera shakes with its square
+#[derive(Resource, Default)]
+struct ScreenShake {
+    pub trauma: f32,
+}
+
+// The scoreboard window only renders this layer, and the game camera never does
+const SCOREBOARD_LAYER: u8 = 1;
+
+#[derive(Component)]
+struct ScoreboardScoreText;
+
+#[derive(Component)]
+struct ScoreboardClockText;
+
+pub fn spawn_scoreboard_window(commands: &mut Commands) {
+    let window = commands
+        .spawn(Window {
+            title: "Air Oki scoreboard".to_string(),
+            resolution: WindowResolution::new(640., 360.),
+            ..default()
+        })
+        .id();
+    let layer = RenderLayers::layer(SCOREBOARD_LAYER);
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                target: RenderTarget::Window(WindowRef::Entity(window)),
+                ..default()
+            },
+            ..default()
+        },
+        // The game UI renders on every camera, keep it out of this window
+        UiCameraConfig { show_ui: false },
+        layer,
+    ));
+
+    let labels = [("Player 1", -200.), ("Player 2", 200.)];
+    for (label, x) in labels {
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    label,
+                    TextStyle {
+                        font_size: 36.0,
+                        color: Color::GRAY,
+                        ..default()
+                    },
+                ),
+                transform: Transform::from_xyz(x, 110., 0.),
+                ..default()
+            },
+            layer,
+        ));
+    }
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 140.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            ..default()
+        },
+        layer,
+        ScoreboardScoreText,
+    ));
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 40.0,
+                    color: Color::GRAY,
+                    ..default()
+                },
+            ),
+            transform: Transform::from_xyz(0., -120., 0.),
+            ..default()
+        },
+        layer,
+        ScoreboardClockText,
+    ));
+}
+
+fn update_scoreboard_window(
+    config: Res<GameConfig>,
+    score: Res<Score>,
+    timer: Res<MatchTimer>,
+    survival: Res<SurvivalRun>,
+    mut scores: Query<&mut Text, (With<ScoreboardScoreText>, Without<ScoreboardClockText>)>,
+    mut clocks: Query<&mut Text, With<ScoreboardClockText>>,
+) {
+    for mut text in scores.iter_mut() {
+        text.sections[0].value = if config.mode.endless() {
+            format!("{} returns", survival.returns)
+        } else {
+            format!("{}  -  {}", score.first_player(), score.second_player())
+        };
+    }
+    for mut text in clocks.iter_mut() {
+        let seconds = match config.mode {
+            GameMode::Survival | GameMode::Coop => survival.time.elapsed_secs(),
+            _ => timer.remaining().unwrap_or(timer.elapsed.elapsed_secs()),
+        };
+        text.sections[0].value = format!("{}:{:02}", seconds as u32 / 60, seconds as u32 % 60);
+    }
+}
+
+fn spawn_game_over_screen(
+    mut commands: Commands,
+    outcome: Res<MatchOutcome>,
+    bindings: Res<KeyBindings>,
+    stats: Res<MatchStats>,
+    config: Res<GameConfig>,
+    seed: Option<Res<MatchSeed>>,
+    games: Res<GamesWon>,
+    tournament: Option<Res<Tournament>>,
+    lifetime: Res<PlayerStats>,
+) {
+    let hidden = if config.reduced_motion {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+
+    let names = tournament
+        .as_ref()
+        .and_then(|tournament| tournament.match_names(tournament.last_match?))
+        .unwrap_or(["Player 1", "Player 2"]);
+    let champion = tournament
+        .as_ref()
+        .and_then(|tournament| tournament.champion());
+    let headline = match *outcome {
+        MatchOutcome::Winner { .. } if champion.is_some() => {
+            format!("{} is the champion!", champion.unwrap_or_default())
+        }
+        MatchOutcome::Winner { first_player: true } if config.mode == GameMode::Breakout => {
+            "All bricks cleared!".to_string()
+        }
+        MatchOutcome::Winner {
+            first_player: false,
+        } if config.mode == GameMode::Breakout => "Out of lives".to_string(),
+        MatchOutcome::Winner { first_player } => {
+            format!("{} wins!", if first_player { names[0] } else { names[1] })
+        }
+        MatchOutcome::SeatWinner { ref side } => format!("{} player wins!", side.label()),
+        MatchOutcome::Draw => "Draw!".to_string(),
+        MatchOutcome::SurvivalOver { time, returns } => {
+            let points = SurvivalScore { time, returns }.points();
+            let run = if config.mode == GameMode::Coop {
+                "Co-op"
+            } else {
+                "Survival"
+            };
+            format!("{run} over\n{returns} returns in {time:.1}s\nScore {points}")
+        }
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(20.),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.7).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            GameOverScreen,
+            GameOverReveal {
+                timer: Timer::from_seconds(GAME_OVER_REVEAL_INTERVAL, TimerMode::Repeating),
+                next: 0,
+            },
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                TextBundle {
+                    visibility: hidden,
+                    ..TextBundle::from_section(
+                        headline,
+                        TextStyle {
+                            font_size: 60.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    )
+                    .with_text_alignment(TextAlignment::Center)
+                },
+                RevealStep(0),
+            ));
+
+            let [p1, p2] = &stats.players;
+            let best_of = matches!(config.mode, GameMode::Versus | GameMode::PointBallRace)
+                && config.best_of > 1;
+            let set_score = best_of.then(|| {
+                (
+                    "Games".to_string(),
+                    games.first_player.to_string(),
+                    games.second_player.to_string(),
+                )
+            });
+            let rows = [
+                Some(("".to_string(), names[0].to_string(), names[1].to_string())),
+                set_score,
+            ]
+            .into_iter()
+            .flatten()
+            .chain([
+                (
+                    "Goals".to_string(),
+                    p1.goals.to_string(),
+                    p2.goals.to_string(),
+                ),
+                (
+                    "Paddle hits".to_string(),
+                    p1.paddle_hits.to_string(),
+                    p2.paddle_hits.to_string(),
+                ),
+                (
+                    "Longest rally".to_string(),
+                    p1.longest_rally.to_string(),
+                    p2.longest_rally.to_string(),
+                ),
+                (
+                    "Fastest shot".to_string(),
+                    format!("{:.0}", p1.fastest_shot),
+                    format!("{:.0}", p2.fastest_shot),
+                ),
+                (
+                    "Point balls".to_string(),
+                    p1.point_balls.to_string(),
+                    p2.point_balls.to_string(),
+                ),
+            ])
+            .collect::<Vec<_>>();
+            let bracket_step = rows.len() + 1;
+            builder
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(4.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|builder| {
+                    for (step, (label, first, second)) in rows.into_iter().enumerate() {
+                        spawn_stat_row(builder, label, first, second, (step + 1, hidden));
+                    }
+                });
+
+            if let Some(tournament) = &tournament {
+                spawn_bracket(builder, tournament, (bracket_step, hidden));
+            }
+            let survival = match *outcome {
+                MatchOutcome::SurvivalOver { time, returns }
+                    if config.mode == GameMode::Survival =>
+                {
+                    Some(SurvivalScore { time, returns })
+                }
+                _ => None,
+            };
+            if let Some(survival) = survival {
+                builder.spawn((
+                    TextBundle {
+                        visibility: hidden,
+                        ..TextBundle::from_section(
+                            lifetime.high_score_table(Some(survival)),
+                            TextStyle {
+                                font_size: 20.0,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        )
+                    },
+                    RevealStep(bracket_step),
+                ));
+            }
+
+            builder.spawn((
+                TextBundle {
+                    visibility: hidden,
+                    ..TextBundle::from_section(
+                        match (&tournament, champion) {
+                            (Some(_), Some(_)) => {
+                                format!(
+                                    "Press {} to return to the menu",
+                                    key_name(bindings.restart)
+                                )
+                            }
+                            (Some(tournament), None) => {
+                                let [left, right] = tournament.next_names().unwrap_or_default();
+                                format!(
+                                    "Press {} for {left} vs {right}",
+                                    key_name(bindings.restart)
+                                )
+                            }
+                            (None, _) => {
+                                format!("Press {} to play again", key_name(bindings.restart))
+                            }
+                        },
+                        TextStyle {
+                            font_size: 24.0,
+                            color: Color::GRAY,
+                            ..default()
+                        },
+                    )
+                },
+                RevealStep(usize::MAX),
+            ));
+            if let Some(seed) = seed {
+                builder.spawn((
+                    TextBundle {
+                        visibility: hidden,
+                        ..TextBundle::from_section(
+                            format!("Seed {}", seed.0),
+                            TextStyle {
+                                font_size: 18.0,
+                                color: Color::DARK_GRAY,
+                                ..default()
+                            },
+                        )
+                    },
+                    RevealStep(usize::MAX),
+                ));
+            }
+        });
+}
+
+// One column per round, winners in white, knocked out entrants in gray and open slots as dashes
+fn spawn_bracket(
+    builder: &mut ChildBuilder,
+    tournament: &Tournament,
+    (step, visibility): (usize, Visibility),
+) {
+    builder
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(24.),
+                    ..default()
+                },
+                visibility,
+                ..default()
+            },
+            RevealStep(step),
+        ))
+        .with_children(|builder| {
+            for (round, slots) in tournament.rounds.iter().enumerate() {
+                builder
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            row_gap: Val::Px(4.),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|builder| {
+                        for (slot, entrant) in slots.iter().enumerate() {
+                            let knocked_out = tournament
+                                .rounds
+                                .get(round + 1)
+                                .and_then(|next| next[slot / 2])
+                                .is_some_and(|winner| Some(winner) != *entrant);
+                            let (text, color) = match entrant {
+                                Some(entrant) if knocked_out => {
+                                    (tournament.names[*entrant].as_str(), Color::GRAY)
+                                }
+                                Some(entrant) => {
+                                    (tournament.names[*entrant].as_str(), Color::WHITE)
+                                }
+                                None if round == 0 => ("bye", Color::DARK_GRAY),
+                                None => ("-", Color::DARK_GRAY),
+                            };
+                            builder.spawn(TextBundle::from_section(
+                                text,
+                                TextStyle {
+                                    font_size: 18.0,
+                                    color,
+                                    ..default()
+                                },
+                            ));
+                        }
+                    });
+            }
+        });
+}
+
+fn spawn_stat_row(
+    builder: &mut ChildBuilder,
+    label: String,
+    first: String,
+    second: String,
+    (step, visibility): (usize, Visibility),
+) {
+    builder
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    ..default()
+                },
+                visibility,
+                ..default()
+            },
+            RevealStep(step),
+        ))
+        .with_children(|builder| {
+            for (value, width) in [(label, 200.), (first, 120.), (second, 120.)] {
+                builder
+                    .spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(width),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|builder| {
+                        builder.spawn(TextBundle::from_section(
+                            value,
+                            TextStyle {
+                                font_size: 22.0,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+// Any key skips straight to the full summary
+fn reveal_game_over_screen(
+    mut commands: Commands,
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mut reveals: Query<&mut GameOverReveal>,
+    mut steps: Query<(&RevealStep, &mut Visibility)>,
+    sfx: Res<AudioAssets>,
+    audio: Res<AudioSettings>,
+    mut rng: ResMut<SfxRng>,
+) {
+    let Ok(mut reveal) = reveals.get_single_mut() else {
+        return;
+    };
+    let skip = keys.get_just_pressed().next().is_some();
+    reveal.timer.tick(time.delta());
+    if !skip && !reveal.timer.just_finished() {
+        return;
+    }
+
+    let last_step = steps
+        .iter()
+        .map(|(step, _)| step.0)
+        .filter(|step| *step != usize::MAX)
+        .max();
+    let mut revealed = false;
+    for (step, mut visibility) in steps.iter_mut() {
+        let due = skip
+            || step.0 == reveal.next
+            || (step.0 == usize::MAX && Some(reveal.next) > last_step);
+        if due && *visibility == Visibility::Hidden {
+            *visibility = Visibility::Inherited;
+            revealed = true;
+        }
+    }
+    reveal.next = reveal.next.saturating_add(1);
+
+    if revealed && !skip {
+        if let Some((source, speed)) = AudioAssets::pick(&sfx.wall_bounces, &mut rng) {
+            commands.spawn((
+                AudioBundle {
+                    source,
+                    settings: audio.sfx().with_speed(speed),
+                },
+                SoundEffect,
+            ));
+        }
+    }
+}
+
+fn spawn_intermission_screen(mut commands: Commands, games: Res<GamesWon>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.5).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            IntermissionScreen {
+                timer: Timer::from_seconds(INTERMISSION_SECONDS, TimerMode::Once),
+            },
+        ))
+        .with_children(|builder| {
+            let played = games.first_player + games.second_player;
+            for (text, font_size) in [
+                (format!("Game {played} over"), 60.),
+                (
+                    format!("Games {} - {}", games.first_player, games.second_player),
+                    36.,
+                ),
+                (format!("Game {} starting...", played + 1), 24.),
+            ] {
+                builder.spawn(TextBundle::from_section(
+                    text,
+                    TextStyle {
+                        font_size,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            }
+        });
+}
+
+fn tick_intermission(
+    time: Res<Time>,
+    mut screens: Query<&mut IntermissionScreen>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for mut screen in screens.iter_mut() {
+        if screen.timer.tick(time.delta()).just_finished() {
+            next_state.set(GameState::Playing);
+        }
+    }
+}
+
+fn despawn_intermission_screen(
+    mut commands: Commands,
+    screens: Query<Entity, With<IntermissionScreen>>,
+) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn despawn_game_over_screen(mut commands: Commands, screens: Query<Entity, With<GameOverScreen>>) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Built from the live bindings on every open so rebinding is reflected
+fn toggle_controls_help(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    overlays: Query<Entity, With<ControlsHelpOverlay>>,
+) {
+    if !keys.just_pressed(bindings.help) {
+        return;
+    }
+    if !overlays.is_empty() {
+        for entity in overlays.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(8.),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.8).into(),
+                z_index: ZIndex::Global(20),
+                ..default()
+            },
+            ControlsHelpOverlay,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                "Controls",
+                TextStyle {
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+            for (action, binding) in bindings.help_lines() {
+                builder.spawn(TextBundle::from_section(
+                    format!("{action}: {binding}"),
+                    TextStyle {
+                        font_size: 22.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            }
+        });
+}
+
+fn fade_out(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut fading: Query<(Entity, &mut Sprite, &mut FadeOut)>,
+) {
+    for (entity, mut sprite, mut fade) in fading.iter_mut() {
+        fade.timer.tick(time.delta());
+        if fade.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let alpha = fade.start_alpha * (1. - fade.timer.percent());
+        sprite.color.set_a(alpha);
+    }
+}
+
+// The bounce has already happened, so the contact is behind the ball's new heading
+fn spawn_collision_particles(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    mut paddle_hit: EventReader<PaddleHit>,
+    mut goal_scored: EventReader<GoalScored>,
+    mut collision_started: EventReader<CollisionStarted>,
+    balls: Query<(&Position, &LinearVelocity), With<Ball>>,
+    paddles: Query<(&Paddle, &Sprite)>,
+    walls: Query<(), Or<(With<Wall>, With<Obstacle>, With<Brick>)>>,
+) {
+    if config.reduced_motion {
+        paddle_hit.clear();
+        goal_scored.clear();
+        collision_started.clear();
+        return;
+    }
+    let contact = |ball: Entity| {
+        let (position, velocity) = balls.get(ball).ok()?;
+        Some(position.0 - velocity.normalize_or_zero() * BALL_RADIUS)
+    };
+    let mut bursts = Vec::new();
+    for hit in paddle_hit.iter() {
+        if let (Some(point), Ok((_, sprite))) = (contact(hit.ball), paddles.get(hit.paddle)) {
+            bursts.push((point, sprite.color, HIT_PARTICLES, PARTICLE_SPEED));
+        }
+    }
+    for CollisionStarted(entity1, entity2) in collision_started.iter() {
+        let ball = if walls.contains(*entity2) {
+            *entity1
+        } else if walls.contains(*entity1) {
+            *entity2
+        } else {
+            continue;
+        };
+        // Anything else touching a wall isn't a ball and has no contact
+        if let Some(point) = contact(ball) {
+            bursts.push((point, Color::GRAY, HIT_PARTICLES / 2, PARTICLE_SPEED * 0.7));
+        }
+    }
+    for goal in goal_scored.iter() {
+        let color = paddles
+            .iter()
+            .find(|(paddle, _)| Some(&paddle.side) == goal.scorer.as_ref())
+            .map_or(Color::WHITE, |(_, sprite)| sprite.color);
+        bursts.push((goal.position, color, GOAL_PARTICLES, PARTICLE_SPEED * 2.));
+    }
+
+    // Cosmetic only, the match's seeded RNG is left alone
+    let mut rng = rand::thread_rng();
+    for (point, color, count, speed) in bursts {
+        for _ in 0..count {
+            let angle = rng.gen_range(0.0..TAU);
+            let velocity = Vec2::from_angle(angle) * speed * rng.gen_range(0.4..=1.);
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::splat(PARTICLE_SIZE)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(point.extend(4.)),
+                    ..default()
+                },
+                Particle { velocity },
+                FadeOut {
+                    timer: Timer::from_seconds(PARTICLE_LIFETIME, TimerMode::Once),
+                    start_alpha: color.a(),
+                },
+                MatchEntity,
+            ));
+        }
+    }
+}
+
+fn move_particles(time: Res<Time>, mut particles: Query<(&mut Particle, &mut Transform)>) {
+    let drag = PARTICLE_DRAG.powf(time.delta_seconds());
+    for (mut particle, mut transform) in particles.iter_mut() {
+        particle.velocity *= drag;
+        transform.translation += (particle.velocity * time.delta_seconds()).extend(0.);
+    }
+}
+
+fn cycle_display_mode(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut config: ResMut<GameConfig>,
+) {
+    if keys.just_pressed(bindings.display_mode) {
+        config.display_mode = config.display_mode.next();
+    }
+}
+
+// The resize that follows a mode change refits the camera to the court
+fn apply_display_mode(
+    config: Res<GameConfig>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let mode = WindowMode::from(config.display_mode);
+    for mut window in windows.iter_mut() {
+        if window.mode != mode {
+            window.mode = mode;
+        }
+    }
+}
+
+// Scales every UI node, ball numbers are world text and follow in steady_ball_text
+fn apply_ui_scale(config: Res<GameConfig>, mut ui_scale: ResMut<UiScale>) {
+    let scale = config.ui_scale as f64;
+    if ui_scale.scale != scale {
+        ui_scale.scale = scale;
+    }
+}
+
+fn fit_camera_to_court(
+    court: Res<Court>,
+    mut resized: EventReader<WindowResized>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<&mut OrthographicProjection, With<MainCamera>>,
+) {
+    // Every event is read so none are left over for the next frame
+    if resized.iter().count() == 0 && !court.is_changed() {
+        return;
+    }
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let scale = court.camera_scale(Vec2::new(window.width(), window.height()));
+    for mut projection in cameras.iter_mut() {
+        projection.scale = scale;
+    }
+}
+
+fn add_shake_trauma(
+    mut goal_scored: EventReader<GoalScored>,
+    mut paddle_hit: EventReader<PaddleHit>,
+    balls: Query<&LinearVelocity, With<Ball>>,
+    game_speed: Res<GameSpeed>,
+    mut shake: ResMut<ScreenShake>,
+) {
+    let mut trauma = goal_scored.iter().count() as f32 * SHAKE_GOAL_TRAUMA;
+    for hit in paddle_hit.iter() {
+        let Ok(velocity) = balls.get(hit.ball) else {
+            continue;
+        };
+        let speed = velocity.length() / game_speed.max_ball_speed();
+        if speed > SHAKE_HARD_HIT_SPEED {
+            trauma += (speed - SHAKE_HARD_HIT_SPEED) / (1. - SHAKE_HARD_HIT_SPEED) * 0.4;
+        }
+    }
+    if trauma > 0. {
+        shake.trauma = (shake.trauma + trauma).min(1.);
+    }
+}
+
+// Cosmetic only, so it draws from the thread's RNG and leaves the match's seeded one alone
+fn shake_camera(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut shake: ResMut<ScreenShake>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    // The frame trauma runs out puts the camera back in place
+    if shake.trauma <= 0. {
+        return;
+    }
+    shake.trauma = (shake.trauma - SHAKE_DECAY * time.delta_seconds()).max(0.);
+    let intensity = if config.reduced_motion {
+        0.
+    } else {
+        config.screen_shake
+    };
+    let mut rng = rand::thread_rng();
+    let offset = Vec2::new(rng.gen_range(-1.0..=1.), rng.gen_range(-1.0..=1.))
+        * SHAKE_MAX_OFFSET
+        * intensity
+        * shake.trauma.powi(2);
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x = offset.x;
+        transform.translation.y = offset.y;
+    }
+}